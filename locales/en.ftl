@@ -0,0 +1,290 @@
+## Replies of the bot in English.
+
+# Errors
+error-bad-usage = Usage: `{ $usage }`
+error-permission-denied = You need to be at least { $level } to do this.
+error-discord = Discord did not accept my request, please try again in a few minutes.
+error-no-guild = I couldn't get the guild identifier.
+error-storage = I can't update the album right now, please try again in a few minutes.
+
+# Help
+help-album-summary = Number of albums: { $decks }, number of pictures: { $pictures }.
+    Album names: { $names }.
+help-commands = Commands:
+help-footer = Type `{ $help }` for more details.
+help-aliases = Aliases: { $aliases }
+help-restricted = Restricted to { $level } and above.
+help-unknown-command = I don't know the command { $name }.
+
+# Command descriptions
+cmd-aled = Lists albums and commands, or describes a command.
+cmd-aled-commande = command to describe
+cmd-add = Adds the pictures attached to the message to an album.
+cmd-add-deck = album to add the attachments to
+cmd-delete_last = Deletes the last picture sent.
+cmd-delete_pic = Deletes a picture from an album.
+cmd-delete_pic-deck = album holding the picture
+cmd-delete_pic-url = link of the picture to delete
+cmd-reset_nick = Resets the nickname of members whose nickname matches one of the given patterns.
+cmd-reset_nick-pseudos = exact nicknames, patterns with * and ?, contains:text or re:regex; --dry-run to only see who would be affected
+cmd-confirm = Confirms the last action you asked for.
+cmd-cancel = Cancels the last action you asked for.
+cmd-langue = Picks the language I reply in on this server.
+cmd-langue-langue = language code (fr, en)
+cmd-rr = Manages reaction role messages. Lists them when given no argument.
+cmd-rr-list = Lists reaction role messages and their emojis.
+cmd-rr-create = Posts a reaction role message in this channel.
+cmd-rr-create-texte = text of the message, between quotes
+cmd-rr-create-exclusif = "exclusive" so that only one role can be picked
+cmd-rr-add = Binds an emoji to a role on a reaction role message.
+cmd-rr-remove = Removes an emoji from a reaction role message.
+cmd-rr-exclusive = Makes a reaction role message exclusive or not.
+cmd-rr-exclusive-switch = on to allow a single role, off otherwise
+cmd-rr-delete = Forgets a reaction role message.
+cmd-rr-sync = Brings members' roles back in line with their reactions.
+cmd-rr-sync-dry-run = "dry-run" to only see what would change
+cmd-rr-picker = Shows a reaction role message with reactions, buttons or a menu.
+cmd-rr-picker-mode = reactions, buttons or select
+cmd-rr-message = id of the reaction role message
+cmd-rr-emoji = emoji, Unicode or custom
+cmd-rr-role = role, as a mention or an id
+cmd-guard = Manages members who must not be mentioned. Lists them when given no argument.
+cmd-guard-list = Lists protected members.
+cmd-guard-protect = Protects a member from mentions.
+cmd-guard-protect-nom = name given to the member in the warning
+cmd-guard-protect-avertissement = custom warning between quotes, {"{"}offender{"}"} and {"{"}name{"}"} are replaced in it
+cmd-guard-unprotect = Stops protecting a member from mentions.
+cmd-guard-forgive = Forgets the mentions made by a member.
+cmd-guard-membre = member, as a mention or an id
+cmd-audit = Shows the latest moderation actions.
+cmd-audit-filtre = member, or kind of action: picture_added, picture_deleted, nickname_reset, role_changed, reaction_roles, mention_guard, permissions, channel_scopes, schedules, announcements, custom_commands, guard_settings
+cmd-audit-channel = Picks the channel moderation actions are posted in.
+cmd-audit-channel-salon = channel, as a mention or an id, or "off"
+cmd-perm = Manages permission levels. Lists them when given no argument.
+cmd-perm-list = Lists levels granted to roles, members and commands.
+cmd-perm-role = Grants a level to a role.
+cmd-perm-user = Grants a level to a member.
+cmd-perm-command = Changes the level a command requires.
+cmd-perm-command-commande = command, between quotes if it has a subcommand
+cmd-perm-niveau = everyone, curator, moderator or admin, or "none" to remove
+cmd-scope = Manages the channels commands, pictures and the mention guard work in. Lists them when given no argument.
+cmd-scope-list = Lists allowed and denied channels.
+cmd-scope-allow = Allows a command in a channel, it then only works in allowed channels.
+cmd-scope-deny = Denies a command in a channel.
+cmd-scope-clear = Lets a command work everywhere.
+cmd-scope-commande = command between quotes, "*" for all of them, "pictures" for pictures or "mention_guard" for the mention guard
+cmd-scope-salon = channel, as a mention or an id
+cmd-stats = Shows the most requested decks, pictures and members.
+cmd-stats-periode = period: 24h, 7d, 2w or "all", 30 days by default
+cmd-stats-user = Shows what a member asks for.
+cmd-stats-membre = member, as a mention or an id
+cmd-stats-unused = Lists the decks nobody asked for.
+cmd-schedule = Manages the pictures posted on their own. Lists them without arguments.
+cmd-schedule-list = Lists the scheduled pictures and when they are next posted.
+cmd-schedule-add = Schedules a random picture in a channel.
+cmd-schedule-salon = channel, as a mention or an id
+cmd-schedule-cron = cron time in quotes, such as "0 9 * * *" for every day at 9
+cmd-schedule-deck = deck to pick from, every deck by default
+cmd-schedule-remove = Removes a scheduled picture.
+cmd-schedule-numero = number given by the list
+cmd-schedule-timezone = Changes the timezone of the times.
+cmd-schedule-fuseau = timezone, such as Europe/Paris
+cmd-announce = Manages the announcements posted again and again. Lists them without arguments.
+cmd-announce-list = Lists the announcements and when they are next posted.
+cmd-announce-add = Posts a template in channels at every time.
+cmd-announce-modele = template name, "etiquette" for the mention etiquette
+cmd-announce-salons = channels, as mentions or ids
+cmd-announce-remove = Removes an announcement.
+cmd-announce-numero = number given by the list
+cmd-announce-pause = Pauses an announcement.
+cmd-announce-resume = Resumes a paused announcement.
+cmd-announce-preview = Shows the text of an announcement and when it is next posted, without posting it.
+cmd-announce-template = Lists the announcement templates.
+cmd-announce-template-set = Writes an announcement template.
+cmd-announce-template-texte = text, {"{"}channel{"}"} is replaced by the channel
+cmd-announce-template-delete = Deletes an announcement template.
+cmd-cmd = Manages custom commands. Lists them without arguments.
+cmd-cmd-list = Lists the custom commands and their keywords.
+cmd-cmd-show = Shows the responses and keywords of a command.
+cmd-cmd-add = Adds a response to a command, creating it if needed. A response is picked at random.
+cmd-cmd-nom = name of the command, without "!"
+cmd-cmd-add-reponse = response, {"{"}user{"}"} and {"{"}channel{"}"} are replaced by the member and the channel
+cmd-cmd-remove = Deletes a command, or only one of its responses.
+cmd-cmd-remove-numero = number of the response given by "show"
+cmd-cmd-trigger = Makes a command answer messages containing a keyword, without "!".
+cmd-cmd-untrigger = Removes a keyword from a command.
+cmd-cmd-mot-cle = word or phrase, ignoring case and accents
+
+# Pictures
+picture-added = { $count ->
+    [one] I added { $count } picture!
+   *[other] I added { $count } pictures!
+}
+picture-no-attachment = I found no attachment to add.
+picture-last-deleted = I deleted picture { $url } from deck { $deck }
+picture-no-last = I don't remember the last picture sent, so I deleted nothing.
+picture-deleted = I deleted the picture!
+picture-not-deleted = I couldn't find this picture in deck { $deck }, I deleted nothing.
+picture-slow-down = Slow down! Wait a bit before asking for more pictures.
+
+# Nicknames
+nick-reset-done = I reset the nicknames of:{ $changed }
+nick-reset-failed = I couldn't change those of:{ $failed }
+nick-none-matched = No nickname matches.
+nick-bad-pattern = The pattern { $pattern } isn't valid.
+nick-dry-run = { $count } nickname(s) would be reset:
+nick-confirm = { $count } nicknames are about to be reset, type `{ $confirm }` to confirm or `{ $cancel }` to cancel within { $minutes } minutes:
+nick-preview-more = … and { $count } more
+
+# Confirmations
+confirm-nothing = You have nothing to confirm.
+confirm-cancelled = Cancelled.
+
+# Reaction roles
+rr-created = I posted message { $message }, add roles to it with `{ $add }`.
+rr-role-added = { $emoji } now gives role { $role }.
+rr-role-removed = { $emoji } no longer gives a role.
+rr-exclusive-on = Only one role can now be picked on message { $message }.
+rr-exclusive-off = Several roles can now be picked on message { $message }.
+rr-deleted = I forgot reaction role message { $message }.
+rr-list-empty = There is no reaction role message.
+rr-list-header = Reaction role messages:
+rr-list-entry = - { $message } in { $channel }: { $roles }
+rr-list-entry-exclusive = - { $message } in { $channel }, exclusive: { $roles }
+rr-unknown-message = I don't know any reaction role message { $message }.
+rr-unknown-emoji = This message gives no role for { $emoji }.
+rr-sync-done = Roles resynced: { $added } added, { $removed } removed.
+rr-sync-dry-run = A resync would add { $added } and remove { $removed } roles.
+picker-set = Message { $message } was updated with its new role picker.
+picker-no-channel = I don't know which channel message { $message } is in, react to it once so that I can find it.
+picker-placeholder = Pick your roles
+picker-added = Roles added: { $roles }
+picker-removed = Roles removed: { $roles }
+picker-failed = I couldn't change these roles, try again in a few minutes: { $roles }
+picker-unchanged = Your roles didn't change.
+picker-unknown = This role picker is no longer in use.
+
+# Locale
+locale-set = I will now speak English on this server.
+
+# Protected users
+guard-warning = Warning!!!
+    Please don't mention { $name }, Discord mentions can quickly get really annoying.
+
+    If you reply to one of their messages, always click "@ ON" (top right of the text box) before sending so that it shows "@ OFF"
+
+    DO NOT DELETE YOUR MESSAGE, getting a mention and not being able to find the message it came from is even worse.
+guard-timeout = You are timed out for { $minutes } minutes, this isn't the first time.
+guard-notify = { $offender } mentioned { $name } again, { $count } times so far: { $link }
+guard-list-empty = Nobody is protected from mentions.
+guard-list-header = Members protected from mentions:
+guard-protected = { $user } is now protected from mentions.
+guard-unprotected = { $user } is no longer protected from mentions.
+guard-not-protected = { $user } wasn't protected from mentions.
+guard-forgiven = I forgot the mentions made by { $user }.
+
+# Moderation log
+audit-picture-added = Picture added
+audit-picture-deleted = Picture deleted
+audit-nickname-reset = Nickname reset
+audit-role-changed = Role changed
+audit-reaction-roles = Reaction role message changed
+audit-mention-guard = Protected member mentioned
+audit-bot = the bot
+audit-field-actor = By
+audit-field-target = Target
+audit-field-before = Before
+audit-field-after = After
+audit-empty = No moderation action found.
+audit-header = Latest moderation actions:
+audit-channel-set = Moderation actions will be posted in { $channel }.
+audit-channel-off = Moderation actions will no longer be posted.
+audit-unknown-channel = I can't find channel { $channel } on this server.
+
+# Permissions
+level-everyone = everyone
+level-curator = curator
+level-moderator = moderator
+level-admin = admin
+perm-set = { $target } now has level { $level }.
+perm-unset = { $target } no longer has a level.
+perm-list-empty = No level is granted, only Discord admins can use restricted commands.
+perm-list-header = Granted levels:
+perm-locked = The level of { $command } cannot be changed.
+audit-permissions = Permission changed
+
+# Channel scopes
+scope-denied = This command doesn't work in this channel.
+scope-set = Channels of { $command }: { $scope }
+scope-cleared = { $command } now works in every channel.
+scope-list-empty = Every command works in every channel.
+scope-list-header = Allowed (+) and denied (-) channels:
+scope-locked = The channels of { $command } can't be limited.
+audit-channel-scopes = Command channels changed
+
+# Statistics
+stats-window-hours = { $count ->
+    [one] the last hour
+   *[other] the last { $count } hours
+}
+stats-window-days = { $count ->
+    [one] the last day
+   *[other] the last { $count } days
+}
+stats-window-all = all time
+stats-empty = No picture was requested over { $window }.
+stats-header = { $count } pictures requested over { $window }:
+stats-decks = Decks
+stats-pictures = Pictures
+stats-users = Members
+stats-user-header = { $count } pictures requested by { $user } over { $window }:
+stats-unused = { $count } decks weren't requested over { $window }:
+stats-unused-none = Every deck was requested over { $window }.
+
+# Schedules
+schedule-list-empty = No picture is scheduled.
+schedule-list-header = Scheduled pictures ({ $timezone } time):
+schedule-added = Picture scheduled: { $schedule }
+schedule-removed = Scheduled picture { $id } removed.
+schedule-unknown = No scheduled picture has number { $id }.
+schedule-bad-cron = The time { $cron } isn't valid, it needs five fields such as "0 9 * * *".
+schedule-unknown-deck = I don't know any deck called { $deck }.
+schedule-unknown-channel = I can't find channel { $channel } on this server.
+schedule-bad-timezone = I don't know timezone { $timezone }.
+schedule-timezone-set = Times now follow { $timezone } time.
+audit-schedules = Scheduled pictures changed
+
+# Announcements
+announce-list-empty = No announcement is scheduled.
+announce-list-header = Announcements ({ $timezone } time):
+announce-added = Announcement scheduled: { $announcement }
+announce-removed = Announcement { $id } removed.
+announce-unknown = No announcement has number { $id }.
+announce-unknown-template = I don't know any template called { $template }.
+announce-paused = Announcement { $id } is paused.
+announce-resumed = Announcement { $id } is running again.
+announce-paused-label = paused
+announce-preview = Announcement { $id }, next posts: { $runs }
+announce-templates = Templates: { $templates }
+announce-template-set = Template { $template } written.
+announce-template-deleted = Template { $template } deleted.
+announce-template-in-use = Template { $template } is still used by announcements.
+audit-announcements = Announcements changed
+
+# Custom commands
+custom-list-empty = There are no custom commands.
+custom-list-header = Custom commands (number of responses) and keywords:
+custom-show-header = Responses of { $name }:
+custom-show-triggers = Keywords: { $triggers }
+custom-added = { $name } now has { $count ->
+    [one] { $count } response
+   *[other] { $count } responses
+}.
+custom-removed = Command { $name } deleted.
+custom-response-removed = Response of { $name } deleted.
+custom-unknown = There is no custom command { $name }.
+custom-builtin = { $name } is a command of the bot, it can't be replaced.
+custom-trigger-added = { $name } now answers "{ $trigger }".
+custom-trigger-removed = { $name } no longer answers "{ $trigger }".
+audit-custom-commands = Custom command changed
+audit-guard-settings = Mention guard changed
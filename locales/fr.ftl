@@ -0,0 +1,289 @@
+## Replies of the bot, French is the default locale.
+## Every message here must also exist in every other locale, this is checked
+## by the tests in src/i18n.rs.
+
+# Errors
+error-bad-usage = Utilisation : `{ $usage }`
+error-permission-denied = Il faut être au moins { $level } pour faire ceci.
+error-discord = Discord n'a pas accepté ma requête, réessayez dans quelques minutes.
+error-no-guild = Je n'ai pas réussi à récupérer l'identifiant de la guilde.
+error-storage = Je n'arrive pas à modifier l'album, je pense que vous pouvez essayer à nouveau dans quelques minutes.
+
+# Help
+help-album-summary = Nombre d'albums: { $decks }, nombre de photos: { $pictures }.
+    Nom des albums: { $names }.
+help-commands = Commandes :
+help-footer = Tapez `{ $help }` pour plus de détails.
+help-aliases = Alias : { $aliases }
+help-restricted = Réservée au niveau { $level } et au-dessus.
+help-unknown-command = Je ne connais pas la commande { $name }.
+
+# Command descriptions
+cmd-aled = Liste les albums et les commandes, ou détaille une commande.
+cmd-aled-commande = commande dont on veut le détail
+cmd-add = Ajoute les images en pièce jointe du message à un album.
+cmd-add-deck = album dans lequel ajouter les pièces jointes
+cmd-delete_last = Supprime la dernière image envoyée.
+cmd-delete_pic = Supprime une image d'un album.
+cmd-delete_pic-deck = album contenant l'image
+cmd-delete_pic-url = lien de l'image à supprimer
+cmd-reset_nick = Remet à zéro le pseudo des membres dont le pseudo correspond à un des motifs donnés.
+cmd-reset_nick-pseudos = pseudos exacts, motifs avec * et ?, contains:texte ou re:regex ; --dry-run pour seulement voir qui serait concerné
+cmd-confirm = Confirme la dernière action demandée.
+cmd-cancel = Annule la dernière action demandée.
+cmd-langue = Choisit la langue dans laquelle je réponds sur ce serveur.
+cmd-langue-langue = code de la langue (fr, en)
+cmd-rr = Gère les messages de rôles par réaction. Sans argument, les liste.
+cmd-rr-list = Liste les messages de rôles et leurs emojis.
+cmd-rr-create = Publie un message de rôles dans ce salon.
+cmd-rr-create-texte = texte du message, entre guillemets
+cmd-rr-create-exclusif = « exclusif » pour qu'on ne puisse choisir qu'un seul rôle
+cmd-rr-add = Associe un emoji à un rôle sur un message de rôles.
+cmd-rr-remove = Retire un emoji d'un message de rôles.
+cmd-rr-exclusive = Rend un message de rôles exclusif ou non.
+cmd-rr-exclusive-switch = on pour n'autoriser qu'un seul rôle, off sinon
+cmd-rr-delete = Oublie un message de rôles.
+cmd-rr-sync = Remet les rôles des membres en accord avec leurs réactions.
+cmd-rr-sync-dry-run = « dry-run » pour seulement voir ce qui changerait
+cmd-rr-picker = Affiche un message de rôles avec des réactions, des boutons ou un menu.
+cmd-rr-picker-mode = reactions, buttons (boutons) ou select (menu)
+cmd-rr-message = identifiant du message de rôles
+cmd-rr-emoji = emoji, Unicode ou personnalisé
+cmd-rr-role = rôle, mentionné ou par identifiant
+cmd-guard = Gère les membres qu'il ne faut pas mentionner. Sans argument, les liste.
+cmd-guard-list = Liste les membres protégés.
+cmd-guard-protect = Protège un membre des mentions.
+cmd-guard-protect-nom = nom donné au membre dans l'avertissement
+cmd-guard-protect-avertissement = avertissement personnalisé entre guillemets, {"{"}offender{"}"} et {"{"}name{"}"} y sont remplacés
+cmd-guard-unprotect = Ne protège plus un membre des mentions.
+cmd-guard-forgive = Oublie les mentions faites par un membre.
+cmd-guard-membre = membre, mentionné ou par identifiant
+cmd-audit = Montre les dernières actions de modération.
+cmd-audit-filtre = membre, ou type d'action : picture_added, picture_deleted, nickname_reset, role_changed, reaction_roles, mention_guard, permissions, channel_scopes, schedules, announcements, custom_commands, guard_settings
+cmd-audit-channel = Choisit le salon où les actions de modération sont publiées.
+cmd-audit-channel-salon = salon, mentionné ou par identifiant, ou « off »
+cmd-perm = Gère les niveaux de permission. Sans argument, les liste.
+cmd-perm-list = Liste les niveaux attribués aux rôles, aux membres et aux commandes.
+cmd-perm-role = Donne un niveau à un rôle.
+cmd-perm-user = Donne un niveau à un membre.
+cmd-perm-command = Change le niveau requis par une commande.
+cmd-perm-command-commande = commande, entre guillemets si elle a une sous-commande
+cmd-perm-niveau = everyone, curator, moderator ou admin, ou « none » pour retirer
+cmd-scope = Gère les salons où marchent les commandes, les images et la garde des mentions. Sans argument, les liste.
+cmd-scope-list = Liste les salons autorisés et interdits.
+cmd-scope-allow = Autorise une commande dans un salon, elle ne marche plus que dans les salons autorisés.
+cmd-scope-deny = Interdit une commande dans un salon.
+cmd-scope-clear = Laisse une commande marcher partout.
+cmd-scope-commande = commande entre guillemets, « * » pour toutes, « pictures » pour les images ou « mention_guard » pour la garde des mentions
+cmd-scope-salon = salon, mentionné ou par identifiant
+cmd-stats = Montre les decks, images et membres les plus demandés.
+cmd-stats-periode = période : 24h, 7j, 2s ou « tout », 30 jours par défaut
+cmd-stats-user = Montre ce que demande un membre.
+cmd-stats-membre = membre, mentionné ou par identifiant
+cmd-stats-unused = Liste les decks que personne n'a demandés.
+cmd-schedule = Gère les images postées toutes seules. Sans argument, les liste.
+cmd-schedule-list = Liste les images programmées et leur prochain envoi.
+cmd-schedule-add = Programme une image au hasard dans un salon.
+cmd-schedule-salon = salon, mentionné ou par identifiant
+cmd-schedule-cron = horaire cron entre guillemets, comme « 0 9 * * * » pour tous les jours à 9 h
+cmd-schedule-deck = deck où piocher, tous les decks par défaut
+cmd-schedule-remove = Retire une image programmée.
+cmd-schedule-numero = numéro donné par la liste
+cmd-schedule-timezone = Change le fuseau horaire des horaires.
+cmd-schedule-fuseau = fuseau horaire, comme Europe/Paris
+cmd-announce = Gère les annonces postées régulièrement. Sans argument, les liste.
+cmd-announce-list = Liste les annonces et leur prochain envoi.
+cmd-announce-add = Poste un modèle dans des salons à chaque horaire.
+cmd-announce-modele = nom du modèle, « etiquette » pour les règles de mention
+cmd-announce-salons = salons, mentionnés ou par identifiant
+cmd-announce-remove = Retire une annonce.
+cmd-announce-numero = numéro donné par la liste
+cmd-announce-pause = Met une annonce en pause.
+cmd-announce-resume = Reprend une annonce en pause.
+cmd-announce-preview = Montre le texte d'une annonce et ses prochains envois, sans la poster.
+cmd-announce-template = Liste les modèles d'annonce.
+cmd-announce-template-set = Écrit un modèle d'annonce.
+cmd-announce-template-texte = texte, {"{"}channel{"}"} y est remplacé par le salon
+cmd-announce-template-delete = Supprime un modèle d'annonce.
+cmd-cmd = Gère les commandes personnalisées. Sans argument, les liste.
+cmd-cmd-list = Liste les commandes personnalisées et leurs mots-clés.
+cmd-cmd-show = Montre les réponses et les mots-clés d'une commande.
+cmd-cmd-add = Ajoute une réponse à une commande, la crée si besoin. Une réponse est tirée au hasard.
+cmd-cmd-nom = nom de la commande, sans « ! »
+cmd-cmd-add-reponse = réponse, {"{"}user{"}"} et {"{"}channel{"}"} y sont remplacés par le membre et le salon
+cmd-cmd-remove = Supprime une commande, ou seulement une de ses réponses.
+cmd-cmd-remove-numero = numéro de la réponse donné par « show »
+cmd-cmd-trigger = Fait répondre une commande aux messages contenant un mot-clé, sans « ! ».
+cmd-cmd-untrigger = Retire un mot-clé d'une commande.
+cmd-cmd-mot-cle = mot ou expression, sans tenir compte des majuscules ni des accents
+
+# Pictures
+picture-added = J'ai ajouté { $count } image·s !
+picture-no-attachment = Je n'ai rien trouvé en pièce jointe a ajouter.
+picture-last-deleted = Depuis le deck { $deck } j'ai supprimé l'image { $url }
+picture-no-last = Je ne me souviens pas de la dernière image envoyée, donc je n'ai rien supprimé.
+picture-deleted = J'ai supprimé l'image !
+picture-not-deleted = Je n'ai pas trouvé cette image dans le deck { $deck }, je n'ai rien supprimé.
+picture-slow-down = Doucement ! Attendez un peu avant de demander d'autres images.
+
+# Nicknames
+nick-reset-done = J'ai mis à zéro les noms d'utilisateur de :{ $changed }
+nick-reset-failed = Je n'ai pas réussi à changer ceux de :{ $failed }
+nick-none-matched = Aucun pseudo ne correspond.
+nick-bad-pattern = Le motif { $pattern } n'est pas valide.
+nick-dry-run = { $count } pseudo·s seraient remis à zéro :
+nick-confirm = { $count } pseudos vont être remis à zéro, tapez `{ $confirm }` pour confirmer ou `{ $cancel }` pour annuler dans les { $minutes } minutes :
+nick-preview-more = … et { $count } autre·s
+
+# Confirmations
+confirm-nothing = Vous n'avez rien à confirmer.
+confirm-cancelled = C'est annulé.
+
+# Reaction roles
+rr-created = J'ai publié le message { $message }, ajoutez-y des rôles avec `{ $add }`.
+rr-role-added = { $emoji } donne maintenant le rôle { $role }.
+rr-role-removed = { $emoji } ne donne plus de rôle.
+rr-exclusive-on = On ne peut plus choisir qu'un seul rôle sur le message { $message }.
+rr-exclusive-off = On peut choisir plusieurs rôles sur le message { $message }.
+rr-deleted = J'ai oublié le message de rôles { $message }.
+rr-list-empty = Il n'y a aucun message de rôles.
+rr-list-header = Messages de rôles :
+rr-list-entry = - { $message } dans { $channel } : { $roles }
+rr-list-entry-exclusive = - { $message } dans { $channel }, exclusif : { $roles }
+rr-unknown-message = Je ne connais pas de message de rôles { $message }.
+rr-unknown-emoji = Ce message ne donne aucun rôle pour { $emoji }.
+rr-sync-done = Rôles resynchronisés : { $added } ajout·s, { $removed } retrait·s.
+rr-sync-dry-run = Une resynchronisation ferait { $added } ajout·s et { $removed } retrait·s.
+picker-set = Le message { $message } a été mis à jour avec son nouveau sélecteur de rôles.
+picker-no-channel = Je ne sais pas dans quel salon est le message { $message }, réagissez-y une fois pour que je le retrouve.
+picker-placeholder = Choisissez vos rôles
+picker-added = Rôles ajoutés : { $roles }
+picker-removed = Rôles retirés : { $roles }
+picker-failed = Je n'ai pas réussi à changer ces rôles, réessayez dans quelques minutes : { $roles }
+picker-unchanged = Vos rôles n'ont pas changé.
+picker-unknown = Ce sélecteur de rôles n'est plus utilisé.
+
+# Locale
+locale-set = Je parlerai désormais français sur ce serveur.
+
+# Protected users
+guard-warning = Attention !!!
+    Il ne faut pas mentionner { $name }, parce que les mentions Discord ça peut vite devenir vraiment très relou.
+
+    Si vous répondez a un de ses messages, cliquez toujours sur "@ ACTIVÉ" (au dessus à droite de la boite de texte) avant l'envoi pour qu'il affiche "@ DÉSACTIVÉ"
+
+    NE SUPPRIMEZ PAS VOTRE MESSAGE c'est encore pire de recevoir une mention et de ne pas pouvoir retrouver le message d'où elle provient.
+guard-timeout = Vous êtes exclu·e pour { $minutes } minutes, ce n'est pas la première fois.
+guard-notify = { $offender } a encore mentionné { $name }, { $count } fois jusqu'ici : { $link }
+guard-list-empty = Personne n'est protégé des mentions.
+guard-list-header = Membres protégés des mentions :
+guard-protected = { $user } est maintenant protégé·e des mentions.
+guard-unprotected = { $user } n'est plus protégé·e des mentions.
+guard-not-protected = { $user } n'était pas protégé·e des mentions.
+guard-forgiven = J'ai oublié les mentions faites par { $user }.
+
+# Moderation log
+audit-picture-added = Image ajoutée
+audit-picture-deleted = Image supprimée
+audit-nickname-reset = Pseudo réinitialisé
+audit-role-changed = Rôle modifié
+audit-reaction-roles = Message de rôles modifié
+audit-mention-guard = Mention d'un membre protégé
+audit-bot = le bot
+audit-field-actor = Par
+audit-field-target = Cible
+audit-field-before = Avant
+audit-field-after = Après
+audit-empty = Aucune action de modération trouvée.
+audit-header = Dernières actions de modération :
+audit-channel-set = Les actions de modération seront publiées dans { $channel }.
+audit-channel-off = Les actions de modération ne seront plus publiées.
+audit-unknown-channel = Je ne trouve pas le salon { $channel } sur ce serveur.
+
+# Permissions
+level-everyone = tout le monde
+level-curator = curateur·ice
+level-moderator = modérateur·ice
+level-admin = admin
+perm-set = { $target } a maintenant le niveau { $level }.
+perm-unset = { $target } n'a plus de niveau attribué.
+perm-list-empty = Aucun niveau n'est attribué, seul·es les admins Discord peuvent utiliser les commandes réservées.
+perm-list-header = Niveaux attribués :
+perm-locked = Le niveau de { $command } ne peut pas être changé.
+audit-permissions = Permission modifiée
+
+# Channel scopes
+scope-denied = Cette commande ne marche pas dans ce salon.
+scope-set = Salons de { $command } : { $scope }
+scope-cleared = { $command } marche maintenant dans tous les salons.
+scope-list-empty = Toutes les commandes marchent dans tous les salons.
+scope-list-header = Salons autorisés (+) et interdits (-) :
+scope-locked = Les salons de { $command } ne peuvent pas être limités.
+audit-channel-scopes = Salons d'une commande modifiés
+
+# Statistics
+stats-window-hours = { $count ->
+    [one] la dernière heure
+   *[other] les { $count } dernières heures
+}
+stats-window-days = { $count ->
+    [one] le dernier jour
+   *[other] les { $count } derniers jours
+}
+stats-window-all = depuis le début
+stats-empty = Aucune image demandée sur { $window }.
+stats-header = { $count } images demandées sur { $window } :
+stats-decks = Decks
+stats-pictures = Images
+stats-users = Membres
+stats-user-header = { $count } images demandées par { $user } sur { $window } :
+stats-unused = { $count } decks n'ont pas été demandés sur { $window } :
+stats-unused-none = Tous les decks ont été demandés sur { $window }.
+
+# Schedules
+schedule-list-empty = Aucune image n'est programmée.
+schedule-list-header = Images programmées (fuseau { $timezone }) :
+schedule-added = Image programmée : { $schedule }
+schedule-removed = L'image programmée { $id } est retirée.
+schedule-unknown = Aucune image programmée ne porte le numéro { $id }.
+schedule-bad-cron = L'horaire { $cron } n'est pas valide, il faut cinq champs comme « 0 9 * * * ».
+schedule-unknown-deck = Je ne connais pas de deck { $deck }.
+schedule-unknown-channel = Je ne trouve pas le salon { $channel } sur ce serveur.
+schedule-bad-timezone = Je ne connais pas le fuseau horaire { $timezone }.
+schedule-timezone-set = Les horaires sont maintenant à l'heure de { $timezone }.
+audit-schedules = Images programmées modifiées
+
+# Announcements
+announce-list-empty = Aucune annonce n'est programmée.
+announce-list-header = Annonces (fuseau { $timezone }) :
+announce-added = Annonce programmée : { $announcement }
+announce-removed = L'annonce { $id } est retirée.
+announce-unknown = Aucune annonce ne porte le numéro { $id }.
+announce-unknown-template = Je ne connais pas de modèle { $template }.
+announce-paused = L'annonce { $id } est en pause.
+announce-resumed = L'annonce { $id } reprend.
+announce-paused-label = en pause
+announce-preview = Annonce { $id }, prochains envois : { $runs }
+announce-templates = Modèles : { $templates }
+announce-template-set = Le modèle { $template } est écrit.
+announce-template-deleted = Le modèle { $template } est supprimé.
+announce-template-in-use = Le modèle { $template } sert encore à des annonces.
+audit-announcements = Annonces modifiées
+
+# Custom commands
+custom-list-empty = Il n'y a aucune commande personnalisée.
+custom-list-header = Commandes personnalisées (nombre de réponses) et mots-clés :
+custom-show-header = Réponses de { $name } :
+custom-show-triggers = Mots-clés : { $triggers }
+custom-added = { $name } a maintenant { $count ->
+    [one] { $count } réponse
+   *[other] { $count } réponses
+}.
+custom-removed = La commande { $name } est supprimée.
+custom-response-removed = La réponse de { $name } est supprimée.
+custom-unknown = Il n'y a pas de commande personnalisée { $name }.
+custom-builtin = { $name } est une commande du bot, elle ne peut pas être remplacée.
+custom-trigger-added = { $name } répond maintenant à « { $trigger } ».
+custom-trigger-removed = { $name } ne répond plus à « { $trigger } ».
+audit-custom-commands = Commande personnalisée modifiée
+audit-guard-settings = Protection contre les mentions modifiée
@@ -1,3 +1,7 @@
+pub mod exchange;
+mod schema;
+
+use std::collections::HashMap;
 use std::io::Write;
 
 use multimap::MultiMap;
@@ -8,6 +12,7 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug)]
 pub enum ErrorKind {
     NotSourced,
+    NewerVersion,
 }
 
 impl ErrorKind {
@@ -16,6 +21,9 @@ impl ErrorKind {
             Self::NotSourced => {
                 "Album was not sourced from a file and cannot automatically be saved to one."
             }
+            Self::NewerVersion => {
+                "Album file was written by a newer version of the bot and cannot be overwritten."
+            }
         }
     }
 }
@@ -38,11 +46,12 @@ pub struct Picture {
     pub url: String,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Pictures by deck. Saved in the format described in [`schema`].
 pub struct Album {
     pictures: MultiMap<String, String>,
     last_sent: Option<Picture>,
-    #[serde(skip)]
+    /// Free text about pictures, such as who added them, by link.
+    metadata: HashMap<String, String>,
     source_file: Option<String>,
 }
 
@@ -51,34 +60,50 @@ impl Album {
         Album {
             pictures: MultiMap::new(),
             last_sent: None,
+            metadata: HashMap::new(),
             source_file: None,
         }
     }
 
     pub fn from_file(path: &str) -> anyhow::Result<Album> {
-        let file = std::fs::File::open(path)?;
-        let mut album: Album = serde_json::from_reader(&file)?;
+        let text = std::fs::read_to_string(path)?;
+        let mut album = schema::read(&text).map_err(|err| err.context(path.to_owned()))?;
         album.source_file = Some(path.to_owned());
-        return Ok(album);
+        Ok(album)
     }
 
-    pub fn save(self: &Self) -> Result<(), Box<dyn std::error::Error>> {
-        match &self.source_file {
-            Some(file_name) => {
-                let mut file = std::fs::File::create(&file_name)?;
-                let to_write = serde_json::to_string_pretty(&self)?;
-                writeln!(&mut file, "{}", to_write)?;
-                Ok(())
+    /// Saves the album in the current format, unless the file was replaced
+    /// by one from a newer version since it was loaded.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(file_name) = &self.source_file else {
+            return Err(ErrorKind::NotSourced.into());
+        };
+        match std::fs::read_to_string(file_name) {
+            Ok(text) => {
+                let version = serde_json::from_str(&text)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| schema::version(&file));
+                if version.is_ok_and(|version| version > schema::CURRENT_VERSION) {
+                    return Err(ErrorKind::NewerVersion.into());
+                }
             }
-            None => Err(Box::new(ErrorKind::NotSourced)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
         }
+        let to_write = schema::write(self)?;
+        let mut file = std::fs::File::create(file_name)?;
+        writeln!(&mut file, "{}", to_write)?;
+        Ok(())
     }
 
-    pub fn get_rand_pic(self: &mut Self, deck_name: &str) -> Option<&str> {
+    /// Deck names are compared without case nor accents.
+    fn same_deck(name: &str, other: &str) -> bool {
+        deunicode::deunicode(name).to_lowercase() == deunicode::deunicode(other).to_lowercase()
+    }
+
+    pub fn get_rand_pic(&mut self, deck_name: &str) -> Option<&str> {
         for deck in self.pictures.iter_all() {
-            if deunicode::deunicode(&deck.0).to_lowercase()
-                == deunicode::deunicode(deck_name).to_lowercase()
-            {
+            if Self::same_deck(deck.0, deck_name) {
                 println!("matched {} with {}", &deck.0, deck_name);
                 let mut rng = thread_rng();
                 let n = rng.gen_range(0..deck.1.len());
@@ -89,10 +114,20 @@ impl Album {
                 return Some(&deck.1[n]);
             }
         }
-        return None;
+        None
+    }
+
+    /// Picks a deck, bigger decks more often, so that every picture has the
+    /// same chance.
+    pub fn get_rand_deck(&self) -> Option<&str> {
+        let decks: Vec<(&String, &Vec<String>)> = self.pictures.iter_all().collect();
+        decks
+            .choose_weighted(&mut thread_rng(), |deck| deck.1.len())
+            .ok()
+            .map(|deck| deck.0.as_str())
     }
 
-    pub fn add_picture(self: &mut Self, deck_name: &str, picture_link: &str) {
+    pub fn add_picture(&mut self, deck_name: &str, picture_link: &str) {
         self.pictures
             .insert(deck_name.to_owned(), picture_link.to_owned());
     }
@@ -114,41 +149,96 @@ impl Album {
                 })
                 .collect();
             let deck2_len = deck2.len();
-            if deck2.len() != 0 {
+            if !deck2.is_empty() {
                 pictures.insert_many(deck_name.to_owned(), deck2);
             }
-            return deck2_len != deck.len();
+            deck2_len != deck.len()
         } else {
-            return false;
+            false
         }
     }
 
-    pub fn remove_picture<'a>(self: &mut Self, deck: &'a str, url: &'a str) -> bool {
+    pub fn remove_picture<'a>(&mut self, deck: &'a str, url: &'a str) -> bool {
         Self::deck_picture_remove(&mut self.pictures, deck, url)
     }
 
-    pub fn remove_last(self: &mut Self) -> Option<Picture> {
+    pub fn remove_last(&mut self) -> Option<Picture> {
         if let Some(picture) = &self.last_sent {
             Self::deck_picture_remove(&mut self.pictures, &picture.deck, &picture.url);
-            return self.last_sent.take();
+            self.last_sent.take()
         } else {
-            return None;
+            None
         }
     }
 
-    pub fn deck_count(self: &Self) -> usize {
+    pub fn deck_count(&self) -> usize {
         self.pictures.len()
     }
 
-    pub fn picture_count(self: &Self) -> usize {
+    pub fn picture_count(&self) -> usize {
         self.pictures.iter_all().map(|(_, deck)| deck.len()).sum()
     }
 
-    pub fn deck_names<'a>(self: &'a Self) -> impl Iterator<Item = &'a String> {
+    pub fn deck_names(&self) -> impl Iterator<Item = &String> {
         self.pictures
             .iter_all()
             .map(|(name, _)| -> &String { name })
     }
+
+    /// The deck called `deck_name`, with its name as stored.
+    pub fn deck(&self, deck_name: &str) -> Option<(&str, &[String])> {
+        self.pictures
+            .iter_all()
+            .find(|(name, _)| Self::same_deck(name, deck_name))
+            .map(|(name, deck)| (name.as_str(), deck.as_slice()))
+    }
+
+    pub fn contains(&self, deck_name: &str, picture_link: &str) -> bool {
+        self.pictures
+            .get_vec(deck_name)
+            .is_some_and(|deck| deck.iter().any(|link| link == picture_link))
+    }
+
+    /// Every picture, deck by deck.
+    pub fn pictures(&self) -> impl Iterator<Item = Picture> + '_ {
+        self.pictures.iter_all().flat_map(|(deck, links)| {
+            links.iter().map(|url| Picture {
+                deck: deck.to_owned(),
+                url: url.to_owned(),
+            })
+        })
+    }
+
+    pub fn metadata(&self, picture_link: &str) -> Option<&str> {
+        self.metadata.get(picture_link).map(String::as_str)
+    }
+
+    pub fn set_metadata(&mut self, picture_link: &str, metadata: Option<String>) {
+        match metadata {
+            Some(metadata) => self.metadata.insert(picture_link.to_owned(), metadata),
+            None => self.metadata.remove(picture_link),
+        };
+    }
+
+    /// Removes the deck called `deck_name` and returns its pictures.
+    pub fn remove_deck(&mut self, deck_name: &str) -> Option<Vec<String>> {
+        let (name, _) = self.deck(deck_name)?;
+        let name = name.to_owned();
+        self.pictures.remove(&name)
+    }
+
+    /// Moves the pictures of a deck to `new_name`, merging them into the
+    /// deck of that name if there is one. Returns how many were moved.
+    pub fn rename_deck(&mut self, deck_name: &str, new_name: &str) -> Option<usize> {
+        let links = self.remove_deck(deck_name)?;
+        let moved = links.len();
+        for link in links {
+            if !self.contains(new_name, &link) {
+                self.add_picture(new_name, &link);
+            }
+        }
+        Some(moved)
+    }
 }
 
 impl Default for Album {
@@ -164,7 +254,7 @@ impl Default for Album {
             deck: "riri".to_owned(),
             url: "http://example.com/riri1.png".to_owned(),
         });
-        return album;
+        album
     }
 }
 
@@ -230,6 +320,57 @@ mod tests {
     }
 
     #[test]
+    fn get_rand_deck() {
+        let album = Album::default();
+        for _ in 0..10 {
+            let deck = album.get_rand_deck().unwrap();
+            assert!(["mood", "tata", "riri"].contains(&deck));
+        }
+        assert_eq!(Album::new().get_rand_deck(), None);
+    }
+
+    #[test]
+    fn rename_deck_merges() {
+        let mut album = Album::default();
+        album.add_picture("mood", "http://example.com/riri2.png");
+
+        assert_eq!(album.rename_deck("RIRI", "mood"), Some(2));
+        assert!(album.deck("riri").is_none());
+        assert_eq!(album.deck("mood").unwrap().1.len(), 5);
+        assert_eq!(album.picture_count(), 6);
+        assert_eq!(album.rename_deck("riri", "mood"), None);
+    }
+
+    #[test]
+    fn remove_deck() {
+        let mut album = Album::default();
+
+        assert_eq!(album.remove_deck("Tata").unwrap().len(), 1);
+        assert_eq!(album.deck_count(), 2);
+        assert!(album.remove_deck("tata").is_none());
+    }
+
+    #[test]
+    fn newer_file_is_not_overwritten() {
+        let path =
+            std::env::temp_dir().join(format!("oxytrouille-album-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, include_str!("../tests/fixtures/album/v0.json")).unwrap();
+        let album = Album::from_file(path).unwrap();
+
+        let newer = format!(
+            r#"{{"version": {}, "decks": {{}}}}"#,
+            super::schema::CURRENT_VERSION + 1
+        );
+        std::fs::write(path, &newer).unwrap();
+        assert!(album.save().is_err());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), newer);
+        assert!(Album::from_file(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::single_match)]
     fn remove_last() {
         let mut album = Album::default();
         let old_len = album.picture_count();
@@ -0,0 +1,461 @@
+//! Exporting the album to other formats, and importing it back.
+//!
+//! Every format carries the same entries: a deck, a link and optional
+//! metadata. ZIP archives also bundle the downloaded pictures, next to a
+//! manifest of the entries. The Mongo format is the shape of the
+//! `pictures` collection the album was first pulled from.
+
+use std::collections::HashSet;
+use std::io::{Read, Seek, Write};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::Album;
+
+const MANIFEST: &str = "manifest.json";
+const MAX_REDIRECTS: usize = 5;
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+    Zip,
+    /// Documents of the old Mongo `pictures` collection.
+    Mongo,
+}
+
+impl Format {
+    /// Format of a file, from its extension.
+    pub fn of_file(path: &str) -> Option<Format> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?;
+        match extension.to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "zip" => Some(Format::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with the decks that are already in the album.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Merge {
+    /// Leave them as they are.
+    Skip,
+    /// Replace their pictures with the imported ones.
+    Overwrite,
+    /// Add the imported pictures they don't have yet.
+    #[default]
+    Append,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub deck: String,
+    pub url: String,
+    #[serde(default)]
+    pub metadata: Option<String>,
+}
+
+/// Every picture of the album.
+pub fn entries(album: &Album) -> Vec<Entry> {
+    album
+        .pictures()
+        .map(|picture| Entry {
+            metadata: album.metadata(&picture.url).map(str::to_owned),
+            deck: picture.deck,
+            url: picture.url,
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub added: usize,
+    pub skipped: usize,
+    pub replaced_decks: usize,
+}
+
+/// Adds `entries` to the album, following `strategy` for the decks it
+/// already has.
+pub fn merge(album: &mut Album, entries: &[Entry], strategy: Merge) -> MergeReport {
+    let mut report = MergeReport::default();
+    let mut decks: Vec<&str> = Vec::new();
+    for entry in entries {
+        if !decks.iter().any(|deck| Album::same_deck(deck, &entry.deck)) {
+            decks.push(&entry.deck);
+        }
+    }
+    for deck in decks {
+        let imported = entries
+            .iter()
+            .filter(|entry| Album::same_deck(&entry.deck, deck));
+        let existing = album.deck(deck).map(|(name, _)| name.to_owned());
+        let name = match (&existing, strategy) {
+            (Some(_), Merge::Skip) => {
+                report.skipped += imported.count();
+                continue;
+            }
+            (Some(name), Merge::Overwrite) => {
+                let removed = album.remove_deck(name).unwrap_or_default();
+                for url in removed {
+                    if !album.pictures().any(|picture| picture.url == url) {
+                        album.set_metadata(&url, None);
+                    }
+                }
+                report.replaced_decks += 1;
+                name.clone()
+            }
+            (Some(name), Merge::Append) => name.clone(),
+            (None, _) => deck.to_owned(),
+        };
+        for entry in imported {
+            if album.contains(&name, &entry.url) {
+                report.skipped += 1;
+                continue;
+            }
+            album.add_picture(&name, &entry.url);
+            if entry.metadata.is_some() {
+                album.set_metadata(&entry.url, entry.metadata.clone());
+            }
+            report.added += 1;
+        }
+    }
+    report
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MongoAuthor {
+    id: String,
+    tag: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MongoPicture {
+    link: String,
+    validated: bool,
+    author: MongoAuthor,
+    album: String,
+}
+
+/// Reads documents as a JSON array or one per line, as `mongoexport`
+/// writes them. Pictures that weren't validated are left out.
+fn read_mongo(text: &str) -> anyhow::Result<Vec<Entry>> {
+    let documents: Vec<MongoPicture> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(text)?
+    } else {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+    Ok(documents
+        .into_iter()
+        .filter(|picture| picture.validated)
+        .map(|picture| Entry {
+            deck: picture.album,
+            url: picture.link,
+            metadata: Some(picture.author.tag).filter(|tag| !tag.is_empty()),
+        })
+        .collect())
+}
+
+fn write_mongo(entries: &[Entry]) -> anyhow::Result<String> {
+    let documents: Vec<MongoPicture> = entries
+        .iter()
+        .map(|entry| MongoPicture {
+            link: entry.url.clone(),
+            validated: true,
+            author: MongoAuthor {
+                id: String::new(),
+                tag: entry.metadata.clone().unwrap_or_default(),
+            },
+            album: entry.deck.clone(),
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&documents)?)
+}
+
+fn read_csv(reader: impl Read) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in csv::Reader::from_reader(reader).deserialize() {
+        let entry: Entry = entry?;
+        entries.push(Entry {
+            metadata: entry.metadata.filter(|metadata| !metadata.is_empty()),
+            ..entry
+        });
+    }
+    Ok(entries)
+}
+
+fn write_csv(entries: &[Entry], writer: impl Write) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for entry in entries {
+        writer.serialize(entry)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// An entry of a ZIP manifest, with the picture's file when it could be
+/// downloaded.
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedEntry {
+    #[serde(flatten)]
+    entry: Entry,
+    file: Option<String>,
+}
+
+/// Name of the archived file of a picture.
+fn archive_name(entry: &Entry, index: usize) -> String {
+    let deck: String = deunicode::deunicode(&entry.deck)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = entry.url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension)
+        .filter(|extension| {
+            !extension.is_empty()
+                && extension.len() <= 4
+                && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+        .unwrap_or("bin");
+    format!("{}/{}.{}", deck, index, extension.to_lowercase())
+}
+
+/// Writes the pictures in `files`, in the order of `entries`, and the
+/// manifest.
+fn write_zip(
+    entries: &[Entry],
+    files: Vec<Option<Vec<u8>>>,
+    writer: impl Write + Seek,
+) -> anyhow::Result<()> {
+    let mut zip = zip::ZipWriter::new(writer);
+    let options = zip::write::FileOptions::default();
+    let mut manifest = Vec::new();
+    for (index, (entry, file)) in entries.iter().zip(files).enumerate() {
+        let name = match file {
+            Some(bytes) => {
+                let name = archive_name(entry, index);
+                zip.start_file(name.as_str(), options)?;
+                zip.write_all(&bytes)?;
+                Some(name)
+            }
+            None => None,
+        };
+        manifest.push(ArchivedEntry {
+            entry: entry.clone(),
+            file: name,
+        });
+    }
+    zip.start_file(MANIFEST, options)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Reads the entries of an archive's manifest.
+fn read_zip(reader: impl Read + Seek) -> anyhow::Result<Vec<Entry>> {
+    let mut zip = zip::ZipArchive::new(reader)?;
+    let manifest: Vec<ArchivedEntry> = serde_json::from_reader(zip.by_name(MANIFEST)?)?;
+    Ok(manifest
+        .into_iter()
+        .map(|archived| archived.entry)
+        .collect())
+}
+
+type HttpsClient = hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
+
+async fn download(client: &HttpsClient, url: &str) -> anyhow::Result<Vec<u8>> {
+    let mut url = url.to_owned();
+    for _ in 0..=MAX_REDIRECTS {
+        let response = tokio::time::timeout(DOWNLOAD_TIMEOUT, client.get(url.parse()?)).await??;
+        let status = response.status();
+        if status.is_redirection() {
+            let Some(location) = response.headers().get(hyper::header::LOCATION) else {
+                anyhow::bail!("redirected without a location");
+            };
+            url = location.to_str()?.to_owned();
+            continue;
+        }
+        if !status.is_success() {
+            anyhow::bail!("got {}", status);
+        }
+        return Ok(hyper::body::to_bytes(response.into_body()).await?.to_vec());
+    }
+    anyhow::bail!("too many redirects")
+}
+
+/// Downloads every picture, none for those that failed.
+async fn download_all(entries: &[Entry]) -> Vec<Option<Vec<u8>>> {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client: HttpsClient = hyper::Client::builder().build(connector);
+    let mut files = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match download(&client, &entry.url).await {
+            Ok(bytes) => files.push(Some(bytes)),
+            Err(err) => {
+                tracing::warn!(%err, url = entry.url, "failed to download picture");
+                files.push(None);
+            }
+        }
+    }
+    files
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub pictures: usize,
+    /// Pictures missing from a ZIP archive.
+    pub failed_downloads: usize,
+}
+
+/// Writes every picture of the album to `path`.
+pub async fn export(album: &Album, path: &str, format: Format) -> anyhow::Result<ExportReport> {
+    let entries = entries(album);
+    let mut report = ExportReport {
+        pictures: entries.len(),
+        failed_downloads: 0,
+    };
+    match format {
+        Format::Json => std::fs::write(path, serde_json::to_string_pretty(&entries)?)?,
+        Format::Mongo => std::fs::write(path, write_mongo(&entries)?)?,
+        Format::Csv => write_csv(&entries, std::fs::File::create(path)?)?,
+        Format::Zip => {
+            let files = download_all(&entries).await;
+            report.failed_downloads = files.iter().filter(|file| file.is_none()).count();
+            write_zip(&entries, files, std::fs::File::create(path)?)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Reads the entries of a file written by [`export`], or of a Mongo dump.
+pub fn import(path: &str, format: Format) -> anyhow::Result<Vec<Entry>> {
+    let entries = match format {
+        Format::Json => serde_json::from_reader(std::fs::File::open(path)?)?,
+        Format::Mongo => read_mongo(&std::fs::read_to_string(path)?)?,
+        Format::Csv => read_csv(std::fs::File::open(path)?)?,
+        Format::Zip => read_zip(std::fs::File::open(path)?)?,
+    };
+    Ok(dedup(entries))
+}
+
+/// Drops entries listed twice.
+fn dedup(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .filter(|entry| seen.insert((entry.deck.clone(), entry.url.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn entry(deck: &str, url: &str, metadata: Option<&str>) -> Entry {
+        Entry {
+            deck: deck.to_owned(),
+            url: url.to_owned(),
+            metadata: metadata.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn csv_roundtrip() {
+        let entries = vec![
+            entry("mood", "http://example.com/a,b.png", Some("by \"zoé\"")),
+            entry("tata", "http://example.com/tata.png", None),
+        ];
+        let mut csv = Vec::new();
+        write_csv(&entries, &mut csv).unwrap();
+        assert!(String::from_utf8_lossy(&csv).starts_with("deck,url,metadata\n"));
+        assert_eq!(read_csv(csv.as_slice()).unwrap(), entries);
+    }
+
+    #[test]
+    fn mongo_documents() {
+        let lines = r#"{"_id":{"$oid":"5f"},"link":"http://example.com/a.png","validated":true,"author":{"id":"1","tag":"zoe#0001"},"album":"mood"}
+{"link":"http://example.com/b.png","validated":false,"author":{"id":"2","tag":"max#0002"},"album":"mood"}"#;
+        let entries = read_mongo(lines).unwrap();
+        assert_eq!(
+            entries,
+            [entry("mood", "http://example.com/a.png", Some("zoe#0001"))]
+        );
+        assert_eq!(
+            read_mongo(&write_mongo(&entries).unwrap()).unwrap(),
+            entries
+        );
+    }
+
+    #[test]
+    fn zip_manifest() {
+        let entries = vec![
+            entry("Pâtes!", "https://example.com/p.JPG?size=2", None),
+            entry("mood", "https://example.com/missing", Some("lost")),
+        ];
+        let mut archive = Cursor::new(Vec::new());
+        write_zip(&entries, vec![Some(b"jpeg".to_vec()), None], &mut archive).unwrap();
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive.get_ref().clone())).unwrap();
+        let mut picture = String::new();
+        zip.by_name("Pates_/0.jpg")
+            .unwrap()
+            .read_to_string(&mut picture)
+            .unwrap();
+        assert_eq!(picture, "jpeg");
+        assert_eq!(zip.len(), 2);
+        assert_eq!(read_zip(archive).unwrap(), entries);
+    }
+
+    #[test]
+    fn merge_strategies() {
+        let imported = [
+            entry("MOOD", "http://example.com/mood1.png", None),
+            entry("MOOD", "http://example.com/new.png", Some("new")),
+            entry("chat", "http://example.com/chat.png", None),
+        ];
+
+        let mut album = Album::default();
+        let report = merge(&mut album, &imported, Merge::Skip);
+        assert_eq!((report.added, report.skipped), (1, 2));
+        assert_eq!(album.deck("mood").unwrap().1.len(), 3);
+
+        let mut album = Album::default();
+        let report = merge(&mut album, &imported, Merge::Append);
+        assert_eq!((report.added, report.skipped), (2, 1));
+        assert_eq!(album.deck("mood").unwrap().1.len(), 4);
+        assert_eq!(album.metadata("http://example.com/new.png"), Some("new"));
+
+        let mut album = Album::default();
+        let report = merge(&mut album, &imported, Merge::Overwrite);
+        assert_eq!(report.replaced_decks, 1);
+        assert_eq!(album.deck("mood").unwrap().1.len(), 2);
+        assert_eq!(album.picture_count(), 6);
+    }
+
+    #[test]
+    fn merge_overwrite_by_deck_spelling() {
+        let imported = [
+            entry("MOOD", "http://example.com/mood1.png", None),
+            entry("Mood", "http://example.com/new.png", None),
+        ];
+        let mut album = Album::default();
+        album.set_metadata("http://example.com/mood2.png", Some("old".to_owned()));
+        let report = merge(&mut album, &imported, Merge::Overwrite);
+        assert_eq!(report.replaced_decks, 1);
+        assert_eq!(album.deck("mood").unwrap().1.len(), 2);
+        assert_eq!(album.metadata("http://example.com/mood2.png"), None);
+    }
+}
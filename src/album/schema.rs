@@ -0,0 +1,192 @@
+//! On-disk format of the album.
+//!
+//! Files carry the version of their format. Older files are migrated one
+//! version at a time when loaded, and saved in the current format. Files
+//! from a newer version are neither read nor overwritten.
+//!
+//! - 0: no `version`; `pictures` maps decks to links, `metadata` maps links
+//!   to text.
+//! - 1: `decks` maps decks to pictures, which hold their link and metadata.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::{Album, Picture};
+
+type Migration = fn(Value) -> anyhow::Result<Value>;
+
+/// `MIGRATIONS[n]` turns a file of version `n` into one of version `n + 1`.
+const MIGRATIONS: [Migration; 1] = [v0_to_v1];
+
+pub const CURRENT_VERSION: u64 = MIGRATIONS.len() as u64;
+
+#[derive(Serialize, Deserialize)]
+struct StoredPicture {
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<String>,
+}
+
+/// The current format.
+#[derive(Serialize, Deserialize)]
+struct AlbumFile {
+    version: u64,
+    decks: BTreeMap<String, Vec<StoredPicture>>,
+    #[serde(default)]
+    last_sent: Option<Picture>,
+}
+
+fn v0_to_v1(file: Value) -> anyhow::Result<Value> {
+    let Some(pictures) = file.get("pictures").and_then(Value::as_object) else {
+        anyhow::bail!("no pictures in version 0 album");
+    };
+    let metadata = file.get("metadata").and_then(Value::as_object);
+    let mut decks = Map::new();
+    for (deck, links) in pictures {
+        let Some(links) = links.as_array() else {
+            anyhow::bail!("pictures of {} are not a list", deck);
+        };
+        let stored: Vec<Value> = links
+            .iter()
+            .map(|link| {
+                let mut picture = json!({ "url": link });
+                if let Some(text) = link.as_str().and_then(|link| metadata?.get(link)) {
+                    picture["metadata"] = text.clone();
+                }
+                picture
+            })
+            .collect();
+        decks.insert(deck.clone(), Value::Array(stored));
+    }
+    Ok(json!({
+        "version": 1,
+        "decks": decks,
+        "last_sent": file.get("last_sent").cloned().unwrap_or(Value::Null),
+    }))
+}
+
+/// Version of a file, 0 for files from before versions.
+pub fn version(file: &Value) -> anyhow::Result<u64> {
+    match file.get("version") {
+        None => Ok(0),
+        Some(version) => match version.as_u64() {
+            Some(version) => Ok(version),
+            None => anyhow::bail!("album version {} is not a number", version),
+        },
+    }
+}
+
+/// Reads an album file of any version up to the current one.
+pub fn read(text: &str) -> anyhow::Result<Album> {
+    let mut file: Value = serde_json::from_str(text)?;
+    let from = version(&file)?;
+    if from > CURRENT_VERSION {
+        anyhow::bail!(
+            "album is version {}, this bot only reads up to version {}",
+            from,
+            CURRENT_VERSION
+        );
+    }
+    for migration in &MIGRATIONS[from as usize..] {
+        file = migration(file)?;
+    }
+    let file: AlbumFile = serde_json::from_value(file)?;
+    let mut album = Album::new();
+    for (deck, pictures) in file.decks {
+        for picture in pictures {
+            album.add_picture(&deck, &picture.url);
+            album.set_metadata(&picture.url, picture.metadata);
+        }
+    }
+    album.last_sent = file.last_sent;
+    Ok(album)
+}
+
+/// Writes an album in the current format.
+pub fn write(album: &Album) -> anyhow::Result<String> {
+    let decks = album
+        .pictures
+        .iter_all()
+        .map(|(deck, links)| {
+            let pictures = links
+                .iter()
+                .map(|url| StoredPicture {
+                    url: url.clone(),
+                    metadata: album.metadata(url).map(str::to_owned),
+                })
+                .collect();
+            (deck.clone(), pictures)
+        })
+        .collect();
+    let file = AlbumFile {
+        version: CURRENT_VERSION,
+        decks,
+        last_sent: album.last_sent.as_ref().map(|picture| Picture {
+            deck: picture.deck.clone(),
+            url: picture.url.clone(),
+        }),
+    };
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixtures of every version, oldest first.
+    const FIXTURES: [&str; 2] = [
+        include_str!("../../tests/fixtures/album/v0.json"),
+        include_str!("../../tests/fixtures/album/v1.json"),
+    ];
+
+    #[test]
+    fn every_version_has_a_fixture() {
+        assert_eq!(FIXTURES.len() as u64, CURRENT_VERSION + 1);
+        for (expected, fixture) in FIXTURES.iter().enumerate() {
+            let file: Value = serde_json::from_str(fixture).unwrap();
+            assert_eq!(version(&file).unwrap(), expected as u64);
+        }
+    }
+
+    #[test]
+    fn fixtures_load_the_same_album() {
+        for fixture in FIXTURES {
+            let album = read(fixture).unwrap();
+            assert_eq!(album.deck_count(), 2);
+            assert_eq!(album.picture_count(), 3);
+            assert_eq!(album.deck("mood").unwrap().1.len(), 2);
+            assert_eq!(
+                album.metadata("http://example.com/mood2.png"),
+                Some("zoe#0001")
+            );
+            assert_eq!(album.last_sent.as_ref().unwrap().deck, "tata");
+        }
+    }
+
+    #[test]
+    fn saved_album_reads_back() {
+        let album = read(FIXTURES[0]).unwrap();
+        let saved = write(&album).unwrap();
+        assert_eq!(
+            version(&serde_json::from_str(&saved).unwrap()).unwrap(),
+            CURRENT_VERSION
+        );
+        let again = read(&saved).unwrap();
+        assert_eq!(write(&again).unwrap(), saved);
+    }
+
+    #[test]
+    fn todays_save_loads() {
+        let album = read(include_str!("../../tests/fixtures/album/v0-save.json")).unwrap();
+        assert_eq!(album.deck_count(), 52);
+        assert_eq!(album.picture_count(), 199);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let newer = json!({ "version": CURRENT_VERSION + 1, "decks": {} });
+        assert!(read(&newer.to_string()).is_err());
+    }
+}
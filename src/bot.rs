@@ -1,27 +1,88 @@
 use std::{env, error::Error, sync::Arc, sync::Mutex};
+use tracing::Instrument;
+use twilight_cache_inmemory::InMemoryCache;
 use twilight_gateway::Event;
 use twilight_http::Client as HttpClient;
+use twilight_model::channel::message::AllowedMentions;
+use twilight_model::gateway::payload::outgoing::RequestGuildMembers;
 
 use crate::album::Album;
+use crate::config::Config;
+use crate::i18n::{Locale, Message};
+use discord::Discord;
+use error::CommandError;
 
-struct BotState {
+#[derive(Clone)]
+pub(crate) struct BotState {
     album: Arc<Mutex<crate::album::Album>>,
-    admin_roles: Vec<id::Id<id::marker::RoleMarker>>,
+    config: Arc<Mutex<Config>>,
+    offenses: Arc<Mutex<guard::Offenses>>,
+    audit: Arc<Mutex<audit::AuditLog>>,
+    usage: Arc<Mutex<stats::UsageLog>>,
+    cache: Arc<InMemoryCache>,
+    confirmations: Arc<Mutex<confirm::Confirmations>>,
+    rate_limiter: Arc<Mutex<rate_limit::RateLimiter>>,
 }
 
 impl BotState {
     fn new(
         alb: Arc<Mutex<crate::album::Album>>,
-        admin_roles: Vec<id::Id<id::marker::RoleMarker>>,
+        config: Arc<Mutex<Config>>,
+        offenses: Arc<Mutex<guard::Offenses>>,
+        audit: Arc<Mutex<audit::AuditLog>>,
+        usage: Arc<Mutex<stats::UsageLog>>,
+        cache: Arc<InMemoryCache>,
     ) -> Self {
         Self {
             album: alb,
-            admin_roles,
+            config,
+            offenses,
+            audit,
+            usage,
+            cache,
+            confirmations: Arc::new(Mutex::new(confirm::Confirmations::default())),
+            rate_limiter: Arc::new(Mutex::new(rate_limit::RateLimiter::default())),
+        }
+    }
+
+    /// Applies `change` to the configuration and saves it.
+    fn update_config<T>(&self, change: impl FnOnce(&mut Config) -> T) -> Result<T, CommandError> {
+        let mut config = self.config.lock()?;
+        let out = change(&mut config);
+        config
+            .save()
+            .map_err(|err| CommandError::Storage(err.to_string()))?;
+        Ok(out)
+    }
+
+    fn locale(&self, guild_id: Option<id::Id<id::marker::GuildMarker>>) -> Locale {
+        match self.config.lock() {
+            Ok(config) => config.locale(guild_id),
+            Err(_) => Locale::default(),
         }
     }
 }
 
+pub mod announcements;
+pub mod audit;
 mod command;
+mod confirm;
+pub mod custom;
+mod discord;
+mod error;
+pub mod guard;
+mod lookup;
+pub mod nicknames;
+mod outbox;
+pub mod permissions;
+pub mod rate_limit;
+pub mod reaction_roles;
+pub mod recording;
+mod registry;
+mod role_picker;
+pub mod schedules;
+pub mod scopes;
+pub mod stats;
 
 pub fn set_sigint_handler(alb: Arc<Mutex<Album>>) -> Result<(), ctrlc::Error> {
     let res = ctrlc::set_handler(move || {
@@ -43,12 +104,44 @@ pub fn set_sigint_handler(alb: Arc<Mutex<Album>>) -> Result<(), ctrlc::Error> {
         }
         std::process::exit(0);
     });
-    return res;
+    res
 }
 
-pub async fn start(alb: crate::album::Album) -> anyhow::Result<()> {
+/// Points the bot to another Discord API, such as the fake server of the
+/// integration tests. Requests go to `<url>/api/v10/..`, the gateway is
+/// reached over WebSocket at the same address.
+const API_URL_VAR: &str = "DISCORD_API_URL";
+
+/// Host of an API URL, and whether it is reached over plain HTTP.
+fn parse_api_url(url: &str) -> anyhow::Result<(String, bool)> {
+    let url = url.trim_end_matches('/');
+    if let Some(host) = url.strip_prefix("http://") {
+        Ok((host.to_owned(), true))
+    } else if let Some(host) = url.strip_prefix("https://") {
+        Ok((host.to_owned(), false))
+    } else {
+        anyhow::bail!(
+            "{} must start with http:// or https://, got {}",
+            API_URL_VAR,
+            url
+        )
+    }
+}
+
+pub async fn start(
+    alb: crate::album::Album,
+    config: Config,
+    offenses: guard::Offenses,
+    audit: audit::AuditLog,
+    usage: stats::UsageLog,
+    record_to: Option<&str>,
+) -> anyhow::Result<()> {
     let token = env::var("DISCORD_TOKEN")?;
     let alb = Arc::new(Mutex::new(alb));
+    let config = Arc::new(Mutex::new(config));
+    let offenses = Arc::new(Mutex::new(offenses));
+    let audit = Arc::new(Mutex::new(audit));
+    let usage = Arc::new(Mutex::new(usage));
 
     tracing_subscriber::fmt::init();
 
@@ -59,25 +152,77 @@ pub async fn start(alb: crate::album::Album) -> anyhow::Result<()> {
         );
     }
 
-    let intents = twilight_gateway::Intents::GUILD_MESSAGES
+    let intents = twilight_gateway::Intents::GUILDS
+        | twilight_gateway::Intents::GUILD_MESSAGES
         | twilight_gateway::Intents::MESSAGE_CONTENT
         | twilight_gateway::Intents::GUILD_MESSAGE_REACTIONS
         | twilight_model::gateway::Intents::GUILD_MEMBERS;
 
-    let mut shard =
-        twilight_gateway::Shard::new(twilight_gateway::ShardId::ONE, token.clone(), intents);
+    let mut http = HttpClient::builder().token(token.clone());
+    let mut gateway = twilight_gateway::Config::builder(token, intents);
+    if let Ok(url) = env::var(API_URL_VAR) {
+        let (host, plain) = parse_api_url(&url)?;
+        let scheme = if plain { "ws" } else { "wss" };
+        tracing::info!(%url, "using another Discord API");
+        gateway = gateway.proxy_url(format!("{}://{}", scheme, host));
+        http = http.proxy(host, plain);
+    }
 
-    let client = Arc::new(HttpClient::new(token));
+    let mut shard =
+        twilight_gateway::Shard::with_config(twilight_gateway::ShardId::ONE, gateway.build());
+
+    let client: Arc<dyn Discord> = Arc::new(http.build());
+
+    let cache = Arc::new(
+        InMemoryCache::builder()
+            .resource_types(lookup::RESOURCE_TYPES)
+            .build(),
+    );
+
+    let state = BotState::new(alb, config, offenses, audit, usage, Arc::clone(&cache));
+
+    let resync_mode = state
+        .config
+        .lock()
+        .map(|config| config.reaction_roles_resync);
+    if let Ok(mode) = resync_mode {
+        if mode != reaction_roles::ResyncMode::Off {
+            let client = Arc::clone(&client);
+            let state = state.clone();
+            let dry_run = mode == reaction_roles::ResyncMode::DryRun;
+            if dry_run {
+                tracing::warn!(
+                    "reaction roles are only checked on start, set \"reaction_roles_resync\" to \"apply\" in the config to fix them"
+                );
+            }
+            tokio::spawn(async move {
+                match reaction_roles::resync(&*client, &state, dry_run, None).await {
+                    Ok(changes) => {
+                        tracing::info!(changes = changes.len(), dry_run, "reaction roles resynced")
+                    }
+                    Err(err) => tracing::error!(%err, "failed to resync reaction roles"),
+                }
+            });
+        }
+    }
 
-    let admin_roles = find_roles_admin(&client).await?;
+    tokio::spawn(schedules::run(Arc::clone(&client), state.clone()));
+    tokio::spawn(announcements::run(Arc::clone(&client), state.clone()));
 
-    let cache = twilight_cache_inmemory::InMemoryCache::new();
+    let mut recorder = match record_to {
+        Some(path) => Some(recording::Recorder::create(path)?),
+        None => None,
+    };
 
     tracing::info!("ready, starting loop");
 
     loop {
-        let event = match shard.next_event().await {
-            Ok(event) => event,
+        let text = match shard.next_message().await {
+            Ok(twilight_gateway::Message::Text(text)) => text,
+            Ok(twilight_gateway::Message::Close(frame)) => {
+                tracing::debug!(?frame, "gateway connection closed");
+                continue;
+            }
             Err(source) => {
                 tracing::warn!(?source, "error receiving event");
                 if source.is_fatal() {
@@ -86,34 +231,34 @@ pub async fn start(alb: crate::album::Album) -> anyhow::Result<()> {
                 continue;
             }
         };
+        if let Some(recorder) = &mut recorder {
+            recorder.record(&text);
+        }
+        let Some(event) = recording::parse(text, shard.config().event_types()) else {
+            continue;
+        };
 
         cache.update(&event);
 
-        tokio::spawn(handle_event(
-            event,
-            Arc::clone(&client),
-            BotState::new(Arc::clone(&alb), Vec::clone(&admin_roles)),
-        ));
-    }
-    return Ok(());
-}
-
-async fn find_roles_admin(
-    client: &HttpClient,
-) -> Result<Vec<id::Id<id::marker::RoleMarker>>, anyhow::Error> {
-    let mut out: Vec<id::Id<id::marker::RoleMarker>> = Vec::new();
-
-    let roles = client.roles(GUILD_ID).await?.model().await?;
-    for role in roles {
-        if role
-            .permissions
-            .contains(twilight_model::guild::Permissions::ADMINISTRATOR)
-        {
-            out.push(role.id);
+        // Gets every member in the cache, lookups fall back to HTTP until
+        // they all arrived.
+        if let Event::GuildCreate(guild) = &event {
+            let request = RequestGuildMembers::builder(guild.id).query("", None);
+            if let Err(err) = shard.command(&request).await {
+                tracing::warn!(%err, guild = %guild.id, "failed to request guild members");
+            }
         }
-    }
 
-    return Ok(out);
+        let kind = event.kind();
+        let client = Arc::clone(&client);
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_event(event, client, state).await {
+                tracing::error!(?kind, %err, "failed to handle event");
+            }
+        });
+    }
+    Ok(())
 }
 
 use twilight_model::id;
@@ -121,9 +266,6 @@ use twilight_model::id;
 const GUILD_ID: id::Id<id::marker::GuildMarker> =
     unsafe { id::Id::new_unchecked(416194652744450048) };
 
-const PRONOUN_MESSAGE_ID: id::Id<id::marker::MessageMarker> =
-    unsafe { id::Id::new_unchecked(606807344759963688) };
-
 const PROTECTED_USER_ID: id::Id<id::marker::UserMarker> =
     unsafe { id::Id::new_unchecked(350629483042177025) };
 
@@ -132,7 +274,7 @@ const BAN_EMOJI_ID: id::Id<id::marker::EmojiMarker> =
 
 async fn handle_event(
     event: Event,
-    client: Arc<HttpClient>,
+    client: Arc<dyn Discord>,
     state: BotState,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     //let trace = tracing::span!(tracing::Level::INFO, "handle event");
@@ -140,160 +282,528 @@ async fn handle_event(
         Event::MessageCreate(msg) if msg.author.bot => {
             eprintln!("ignoring bot command from {}", msg.author.id);
         }
-        Event::MessageCreate(msg) if msg.content.starts_with("!reset_nick") => {
-            if admin_guard(&msg, &state, &client).await? {
-                command::reset_nick(msg, &client).await?;
-            }
-        }
-        Event::MessageCreate(msg) if msg.content.starts_with("!gouvernement") => {
-            client
-                .create_message(msg.channel_id)
-                .reply(msg.id)
-                .content(
-                    "https://clips.twitch.tv/FriendlyResilientSlothShazBotstix-HWxnFQWq6iPPsVZf",
-                )?
-                .await?;
+        Event::MessageCreate(msg) if registry::parse(&msg.content).is_some() => {
+            dispatch(msg, client, state).await?;
         }
-        Event::MessageCreate(msg) if msg.content.starts_with("!add") => {
-            if admin_guard(&msg, &state, &client).await? {
-                command::picture_add(msg, &state.album, &client).await?;
-            }
-        }
-        Event::MessageCreate(msg) if msg.content.starts_with("!delete_last") => {
-            if admin_guard(&msg, &state, &client).await? {
-                command::delete_last(msg, &state.album, &client).await?;
-            }
+        Event::ReactionAdd(reaction) => {
+            reaction_roles::on_reaction_add(&reaction, &*client, &state).await?;
         }
-        Event::MessageCreate(msg) if msg.content.starts_with("!delete_pic") => {
-            if admin_guard(&msg, &state, &client).await? {
-                command::delete_picture(msg, &state.album, &client).await?;
-            }
+        Event::ReactionRemove(reaction) => {
+            reaction_roles::on_reaction_remove(&reaction, &*client, &state).await?;
         }
-        Event::MessageCreate(msg) if msg.content.starts_with("!aled") => {
-            command::helper(state.album, msg, client).await?;
+        Event::MemberAdd(member) if !member.user.bot => {
+            nicknames::on_member(
+                &*client,
+                &state,
+                member.guild_id,
+                member.user.id,
+                member.nick.as_deref(),
+                &member.roles,
+            )
+            .await?;
         }
-        Event::MessageCreate(msg) if msg.content.len() > 1 && msg.content.starts_with("!") => {
-            command::picture_find_and_send(state.album, msg, client).await?;
+        Event::MemberUpdate(member) if !member.user.bot => {
+            nicknames::on_member(
+                &*client,
+                &state,
+                member.guild_id,
+                member.user.id,
+                member.nick.as_deref(),
+                &member.roles,
+            )
+            .await?;
         }
-        Event::MessageCreate(msg)
-            if msg
-                .mentions
-                .iter()
-                .filter(|mention| mention.id == PROTECTED_USER_ID)
-                .count()
-                != 0
-                && !is_admin(&msg, &state).await? =>
-        {
-            let emoji = twilight_http::request::channel::reaction::RequestReactionType::Custom {
-                id: BAN_EMOJI_ID,
-                name: Some("ban"),
-            };
-            client
-                .create_reaction(msg.channel_id, msg.id, &emoji)
-                .await?;
-            client
-                .create_message(msg.channel_id)
-                .reply(msg.id)
-                .content(
-                    "<:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871>",
-                )?
-                .await?;
-            client
-                .create_message(msg.channel_id)
-                .content("Attention !!!\nIl ne faut pas mentionner Julia, parce que les mentions Discord ça peut vite devenir vraiment très relou.\n\nSi vous répondez a un de ses messages, cliquez toujours sur \"@ ACTIVÉ\" (au dessus à droite de la boite de texte) avant l'envoi pour qu'il affiche \"@ DÉSACTIVÉ\"\n\nNE SUPPRIMEZ PAS VOTRE MESSAGE c'est encore pire de recevoir une mention et de ne pas pouvoir retrouver le message d'où elle provient.")?
-                .await?;
-            client
-                .create_message(msg.channel_id)
-                .content(
-                    "<:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871>",
-                )?
-                .await?;
+        Event::InteractionCreate(interaction) => {
+            role_picker::on_interaction(&interaction, &*client, &state).await?;
         }
-        Event::ReactionAdd(reaction) => {
-            if reaction.message_id == PRONOUN_MESSAGE_ID {
-                if let twilight_model::channel::message::ReactionType::Unicode { name } =
-                    &reaction.emoji
-                {
-                    let role_id = role_from_emoji(name);
-                    if let Some(role_id) = role_id {
-                        client
-                            .add_guild_member_role(GUILD_ID, reaction.user_id, role_id)
-                            .await
-                            .unwrap();
-                    }
-                }
+        Event::MessageCreate(message) => match guard::mentioned(&message, &state) {
+            Some(protected)
+                if permissions::of_message(&message, &state) < registry::Permission::Moderator
+                    && scopes::permits(&state, scopes::MENTION_GUARD, message.channel_id) =>
+            {
+                guard::on_mention(&message, &protected, &*client, &state).await?;
             }
-        }
-        Event::ReactionRemove(reaction) => {
-            if reaction.message_id == PRONOUN_MESSAGE_ID {
-                if let twilight_model::channel::message::ReactionType::Unicode { name } =
-                    &reaction.emoji
-                {
-                    let role_id = role_from_emoji(name);
-                    if let Some(role_id) = role_id {
-                        client
-                            .remove_guild_member_role(GUILD_ID, reaction.user_id, role_id)
-                            .await
-                            .unwrap();
-                    }
-                }
+            _ if custom::on_message(&message, &*client, &state).await? => {}
+            _ => {
+                let author = &message.author.name;
+                let content = &message.content;
+                tracing::debug!(author, content, "no action for this message");
+                //eprintln!("nothing to do with {:?}", message);
             }
-        }
-        Event::MessageCreate(message) => {
-            let author = &message.author.name;
-            let content = &message.content;
-            tracing::debug!(author, content, "no action for this message");
-            //eprintln!("nothing to do with {:?}", message);
-        }
+        },
         _ => {}
     }
 
     Ok(())
 }
 
-async fn admin_guard(
-    msg: &Box<twilight_model::gateway::payload::incoming::MessageCreate>,
-    state: &BotState,
-    client: &Arc<HttpClient>,
-) -> Result<bool, Box<dyn Error + Send + Sync>> {
-    let is_adm = is_admin(msg, state).await?;
-
-    if !is_adm {
-        client
-            .create_message(msg.channel_id)
-            .reply(msg.id)
-            .content("Seul un·e admin peut faire ceci.")?
+async fn dispatch(
+    msg: Box<twilight_model::gateway::payload::incoming::MessageCreate>,
+    client: Arc<dyn Discord>,
+    state: BotState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(parsed) = registry::parse(&msg.content) else {
+        return Ok(());
+    };
+    let Some(cmd) = registry::find(parsed.name) else {
+        if custom::on_command(parsed.name, &msg, &*client, &state).await? {
+            return Ok(());
+        }
+        if !scopes::permits(&state, scopes::PICTURES, msg.channel_id) {
+            return Ok(());
+        }
+        let span = tracing::info_span!("picture", user = %msg.author.id);
+        command::picture_find_and_send(state, msg, client)
+            .instrument(span)
             .await?;
+        return Ok(());
+    };
+
+    let span = tracing::info_span!(
+        "command",
+        command = cmd.name,
+        user = %msg.author.id,
+        user_name = %msg.author.name,
+    );
+    let channel_id = msg.channel_id;
+    let msg_id = msg.id;
+    let locale = state.locale(msg.guild_id);
+    let rest = parsed.rest.to_owned();
+    let invocation = registry::Invocation {
+        msg,
+        args: Vec::new(),
+        usage: cmd.usage(),
+        http: Arc::clone(&client),
+        state,
+        locale,
+    };
+    let result = run_command(cmd, rest, invocation)
+        .instrument(span.clone())
+        .await;
+
+    if let Err(err) = result {
+        let _enter = span.enter();
+        if err.is_internal() {
+            tracing::error!(%err, "command failed");
+        } else {
+            tracing::info!(%err, "command refused");
+        }
+        // Errors may quote what the user wrote, which must not ping anyone.
+        outbox::Outgoing::new(channel_id)
+            .allowed_mentions(AllowedMentions {
+                replied_user: true,
+                ..Default::default()
+            })
+            .reply(msg_id)
+            .content(err.reply(locale))
+            .send(&*client)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Checks permissions and arguments, then runs the command. `inv` comes in
+/// with no arguments, they are parsed from `rest`.
+async fn run_command(
+    cmd: &'static registry::CommandSpec,
+    rest: String,
+    mut inv: registry::Invocation,
+) -> registry::CommandResult {
+    let Ok(args) = registry::tokenize(&rest) else {
+        return Err(CommandError::BadUsage(cmd.usage()));
+    };
+    let resolved = registry::resolve(cmd, args);
+    let spec = resolved.spec;
+
+    if !scopes::permits_command(&inv.state, &resolved.path, inv.msg.channel_id) {
+        return Err(CommandError::NotFound(Message::new("scope-denied")));
+    }
+
+    let required = permissions::required(&inv.state, &resolved.path, spec.permission);
+    if permissions::of_message(&inv.msg, &inv.state) < required {
+        return Err(CommandError::PermissionDenied(required));
+    }
+    if !spec.check_args(&resolved.args) {
+        return Err(CommandError::BadUsage(resolved.usage()));
     }
-    Ok(is_adm)
+
+    inv.usage = resolved.usage();
+    inv.args = resolved.args;
+    (spec.handler)(inv).await
 }
 
-async fn is_admin(
-    msg: &Box<twilight_model::gateway::payload::incoming::MessageCreate>,
-    state: &BotState,
-) -> Result<bool, Box<dyn Error + Send + Sync>> {
-    Ok(if let Some(member) = &msg.member {
-        let first_admin_role = member
-            .roles
-            .iter()
-            .find(|item| state.admin_roles.contains(item));
-        if let Some(_) = first_admin_role {
-            true
-        } else {
-            false
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+    use twilight_model::gateway::payload::incoming::{
+        InteractionCreate, MessageCreate, ReactionAdd,
+    };
+
+    use super::discord::fake::{Call, RecordingDiscord};
+    use super::reaction_roles::{ReactionRole, ReactionRoleMessage, RoleEmoji};
+    use super::registry::Permission;
+    use super::*;
+
+    const AUTHOR: u64 = 42;
+    const CHANNEL: u64 = 600;
+    const MESSAGE: u64 = 500;
+
+    /// Bot state backed by files in a directory of its own, removed once
+    /// the test is over.
+    struct Fixture {
+        dir: PathBuf,
+        state: BotState,
+        discord: Arc<RecordingDiscord>,
+    }
+
+    impl Fixture {
+        fn new(name: &str, album: Album, discord: RecordingDiscord) -> Self {
+            let dir = env::temp_dir().join(format!("oxytrouille-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = |file: &str| dir.join(file).to_str().unwrap().to_owned();
+            let state = BotState::new(
+                Arc::new(Mutex::new(album)),
+                Arc::new(Mutex::new(Config::from_file(&path("config.json")).unwrap())),
+                Arc::new(Mutex::new(
+                    guard::Offenses::from_file(&path("offenses.json")).unwrap(),
+                )),
+                Arc::new(Mutex::new(audit::AuditLog::new(&path("audit.jsonl")))),
+                Arc::new(Mutex::new(stats::UsageLog::new(&path("usage.jsonl")))),
+                Arc::new(InMemoryCache::new()),
+            );
+            Self {
+                dir,
+                state,
+                discord: Arc::new(discord),
+            }
         }
-    } else {
-        false
-    })
-}
 
-fn role_from_emoji(name: &String) -> Option<id::Id<id::marker::RoleMarker>> {
-    match name.as_str() {
-        "🌻" => unsafe { Some(twilight_model::id::Id::new_unchecked(606807806938447872)) },
-        "🌸" => unsafe { Some(twilight_model::id::Id::new_unchecked(606807957052588042)) },
-        "🍀" => unsafe { Some(twilight_model::id::Id::new_unchecked(606808023108943872)) },
-        "🌼" => unsafe { Some(twilight_model::id::Id::new_unchecked(606808071834173451)) },
+        async fn handle(&self, event: Event) {
+            let client: Arc<dyn Discord> = self.discord.clone();
+            handle_event(event, client, self.state.clone())
+                .await
+                .unwrap();
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn message(content: &str, mentions: &[u64]) -> Event {
+        let user = |id: u64| {
+            json!({
+                "id": id.to_string(),
+                "username": format!("user{}", id),
+                "discriminator": "0001",
+                "avatar": null,
+                "public_flags": 0,
+            })
+        };
+        let message: MessageCreate = serde_json::from_value(json!({
+            "id": MESSAGE.to_string(),
+            "channel_id": CHANNEL.to_string(),
+            "guild_id": GUILD_ID.to_string(),
+            "author": user(AUTHOR),
+            "member": {
+                "roles": [],
+                "joined_at": "2020-01-01T00:00:00.000000+00:00",
+                "deaf": false,
+                "mute": false,
+                "flags": 0,
+            },
+            "content": content,
+            "timestamp": "2024-01-01T00:00:00.000000+00:00",
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": mentions.iter().map(|id| user(*id)).collect::<Vec<_>>(),
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        }))
+        .unwrap();
+        Event::MessageCreate(Box::new(message))
+    }
 
-        _ => None,
+    fn reply(content: &str) -> Call {
+        Call::CreateMessage {
+            channel_id: id::Id::new(CHANNEL),
+            reply_to: Some(id::Id::new(MESSAGE)),
+            content: Some(content.to_owned()),
+            embeds: 0,
+            allowed_mentions: Some(AllowedMentions {
+                replied_user: true,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn api_url_host() {
+        assert_eq!(
+            parse_api_url("http://127.0.0.1:8080/").unwrap(),
+            ("127.0.0.1:8080".to_owned(), true)
+        );
+        assert_eq!(
+            parse_api_url("https://proxy.internal").unwrap(),
+            ("proxy.internal".to_owned(), false)
+        );
+        assert!(parse_api_url("127.0.0.1:8080").is_err());
+    }
+
+    #[tokio::test]
+    async fn unknown_command_sends_a_picture() {
+        let mut album = Album::new();
+        album.add_picture("chat", "https://example.com/chat.png");
+        let fixture = Fixture::new("picture", album, RecordingDiscord::default());
+        fixture.handle(message("!chat", &[])).await;
+        fixture.handle(message("!chien", &[])).await;
+        assert_eq!(
+            fixture.discord.calls(),
+            [reply("https://example.com/chat.png")]
+        );
+        let usage = fixture.state.usage.lock().unwrap().read().unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].deck, "chat");
+        assert_eq!(usage[0].user, id::Id::new(AUTHOR));
+    }
+
+    #[tokio::test]
+    async fn restricted_command_is_refused() {
+        let fixture = Fixture::new("refused", Album::new(), RecordingDiscord::default());
+        fixture.handle(message("!langue en", &[])).await;
+        assert_eq!(
+            fixture.discord.sent(),
+            [CommandError::PermissionDenied(Permission::Admin).reply(Locale::Fr)]
+        );
+        assert_eq!(fixture.state.locale(Some(GUILD_ID)), Locale::Fr);
+    }
+
+    #[tokio::test]
+    async fn error_replies_mention_no_one() {
+        let fixture = Fixture::new("error-mentions", Album::new(), RecordingDiscord::default());
+        fixture.handle(message("!aled @everyone", &[])).await;
+        let calls = fixture.discord.calls();
+        let [Call::CreateMessage {
+            content: Some(content),
+            allowed_mentions: Some(mentions),
+            ..
+        }] = calls.as_slice()
+        else {
+            panic!("expected a reply with allowed mentions, got {:?}", calls);
+        };
+        assert!(content.contains("@everyone"));
+        assert!(mentions.parse.is_empty());
+        assert!(mentions.roles.is_empty() && mentions.users.is_empty());
+    }
+
+    #[tokio::test]
+    async fn admin_command_changes_config() {
+        let fixture = Fixture::new("admin", Album::new(), RecordingDiscord::default());
+        fixture
+            .state
+            .config
+            .lock()
+            .unwrap()
+            .permissions
+            .users
+            .insert(id::Id::new(AUTHOR), Permission::Admin);
+        fixture.handle(message("!langue en", &[])).await;
+        assert_eq!(fixture.state.locale(Some(GUILD_ID)), Locale::En);
+        assert_eq!(
+            fixture.discord.sent(),
+            [Message::new("locale-set").render(Locale::En)]
+        );
+    }
+
+    #[tokio::test]
+    async fn reaction_adds_role() {
+        let fixture = Fixture::new("reaction", Album::new(), RecordingDiscord::default());
+        fixture.state.config.lock().unwrap().reaction_roles = vec![ReactionRoleMessage {
+            guild_id: GUILD_ID,
+            channel_id: Some(id::Id::new(CHANNEL)),
+            message_id: id::Id::new(MESSAGE),
+            exclusive: false,
+            picker: Default::default(),
+            roles: vec![ReactionRole {
+                emoji: RoleEmoji::Unicode("🦊".to_owned()),
+                role_id: id::Id::new(77),
+            }],
+        }];
+        let reaction: ReactionAdd = serde_json::from_value(json!({
+            "channel_id": CHANNEL.to_string(),
+            "guild_id": GUILD_ID.to_string(),
+            "message_id": MESSAGE.to_string(),
+            "user_id": AUTHOR.to_string(),
+            "emoji": { "name": "🦊" },
+        }))
+        .unwrap();
+        fixture.handle(Event::ReactionAdd(Box::new(reaction))).await;
+        assert_eq!(
+            fixture.discord.calls(),
+            [Call::AddRole {
+                user_id: id::Id::new(AUTHOR),
+                role_id: id::Id::new(77),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn button_click_is_deferred_then_answered() {
+        let fixture = Fixture::new("picker", Album::new(), RecordingDiscord::default());
+        fixture.state.config.lock().unwrap().reaction_roles = vec![ReactionRoleMessage {
+            guild_id: GUILD_ID,
+            channel_id: Some(id::Id::new(CHANNEL)),
+            message_id: id::Id::new(MESSAGE),
+            exclusive: false,
+            picker: role_picker::Picker::Buttons,
+            roles: vec![ReactionRole {
+                emoji: RoleEmoji::Unicode("🦊".to_owned()),
+                role_id: id::Id::new(77),
+            }],
+        }];
+        let user = json!({
+            "id": AUTHOR.to_string(),
+            "username": "user",
+            "discriminator": "0001",
+            "avatar": null,
+        });
+        let interaction: InteractionCreate = serde_json::from_value(json!({
+            "id": "900",
+            "application_id": "901",
+            "type": 3,
+            "token": "token",
+            "version": 1,
+            "guild_id": GUILD_ID.to_string(),
+            "channel_id": CHANNEL.to_string(),
+            "locale": "fr",
+            "data": { "custom_id": "rr:77", "component_type": 2 },
+            "member": {
+                "user": user,
+                "roles": [],
+                "joined_at": "2020-01-01T00:00:00.000000+00:00",
+                "deaf": false,
+                "mute": false,
+                "flags": 0,
+            },
+            "message": {
+                "id": MESSAGE.to_string(),
+                "channel_id": CHANNEL.to_string(),
+                "author": user,
+                "content": "",
+                "timestamp": "2024-01-01T00:00:00.000000+00:00",
+                "tts": false,
+                "mention_everyone": false,
+                "mentions": [],
+                "mention_roles": [],
+                "attachments": [],
+                "embeds": [],
+                "pinned": false,
+                "type": 0,
+            },
+        }))
+        .unwrap();
+        fixture
+            .handle(Event::InteractionCreate(Box::new(interaction)))
+            .await;
+        assert_eq!(
+            fixture.discord.calls(),
+            [
+                Call::RespondToInteraction { content: None },
+                Call::AddRole {
+                    user_id: id::Id::new(AUTHOR),
+                    role_id: id::Id::new(77),
+                },
+                Call::UpdateInteractionResponse {
+                    content: Message::new("picker-added")
+                        .arg("roles", "<@&77>")
+                        .render(Locale::Fr),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn mention_of_protected_user_is_warned() {
+        let fixture = Fixture::new("guard", Album::new(), RecordingDiscord::default());
+        fixture
+            .handle(message("coucou", &[PROTECTED_USER_ID.get()]))
+            .await;
+        let calls = fixture.discord.calls();
+        assert!(matches!(calls[0], Call::CreateReaction { .. }));
+        let warning =
+            guard::GuardConfig::default().protected[0].warning(id::Id::new(AUTHOR), Locale::Fr);
+        assert!(fixture.discord.sent()[0].contains(&warning));
+    }
+
+    #[tokio::test]
+    async fn reply_to_protected_user_without_mention_goes_through() {
+        let fixture = Fixture::new("guard-reply", Album::new(), RecordingDiscord::default());
+        let Event::MessageCreate(msg) = message("merci", &[]) else {
+            unreachable!();
+        };
+        let mut reply = serde_json::to_value(&msg.0).unwrap();
+        let mut replied = reply.clone();
+        replied["id"] = json!((MESSAGE - 1).to_string());
+        replied["author"]["id"] = json!(PROTECTED_USER_ID.to_string());
+        reply["type"] = json!(19);
+        reply["message_reference"] = json!({
+            "channel_id": CHANNEL.to_string(),
+            "guild_id": GUILD_ID.to_string(),
+            "message_id": (MESSAGE - 1).to_string(),
+        });
+        reply["referenced_message"] = replied;
+        let reply: MessageCreate = serde_json::from_value(reply).unwrap();
+        assert!(reply.referenced_message.is_some());
+        fixture.handle(Event::MessageCreate(Box::new(reply))).await;
+        assert!(fixture.discord.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn custom_commands_come_before_pictures() {
+        let mut album = Album::new();
+        album.add_picture("salut", "https://example.com/salut.png");
+        let fixture = Fixture::new("custom", album, RecordingDiscord::default());
+        fixture
+            .state
+            .config
+            .lock()
+            .unwrap()
+            .custom_commands
+            .commands
+            .push(custom::CustomCommand {
+                name: "salut".to_owned(),
+                responses: vec!["Salut {user} !".to_owned()],
+                triggers: vec!["coucou".to_owned()],
+            });
+        fixture.handle(message("!salut", &[])).await;
+        fixture.handle(message("Coucou tout le monde", &[])).await;
+        fixture.handle(message("!gouvernement", &[])).await;
+        let greeting = format!("Salut <@{}> !", AUTHOR);
+        assert_eq!(
+            fixture.discord.calls(),
+            [
+                reply(&greeting),
+                reply(&greeting),
+                reply("https://clips.twitch.tv/FriendlyResilientSlothShazBotstix-HWxnFQWq6iPPsVZf"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn bots_are_ignored() {
+        let fixture = Fixture::new("bot", Album::new(), RecordingDiscord::default());
+        let Event::MessageCreate(mut msg) = message("!gouvernement", &[]) else {
+            unreachable!();
+        };
+        msg.author.bot = true;
+        fixture.handle(Event::MessageCreate(msg)).await;
+        assert!(fixture.discord.calls().is_empty());
     }
 }
@@ -0,0 +1,560 @@
+//! Announcements: reminders such as the rules, posted again and again to
+//! some channels.
+//!
+//! Each announcement posts a template following a cron expression, read in
+//! the timezone of the scheduled pictures. Templates are written with
+//! `!announce template set`, and `etiquette` posts the mention etiquette of
+//! the mention guard unless a template of that name was written.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use twilight_model::channel::message::AllowedMentions;
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::lookup;
+use super::outbox::Outgoing;
+use super::registry::{self, CommandResult, Invocation};
+use super::schedules;
+use super::BotState;
+use crate::config::Config;
+use crate::i18n::{Locale, Message};
+
+/// Template of the mention etiquette.
+pub const ETIQUETTE: &str = "etiquette";
+/// Runs listed by `!announce preview`.
+const PREVIEW_RUNS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Announcement {
+    pub id: u32,
+    pub template: String,
+    /// Cron expression, with or without seconds.
+    pub cron: String,
+    pub channels: Vec<Id<ChannelMarker>>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl Announcement {
+    pub fn next_after(&self, after: DateTime<Utc>, timezone: Tz) -> Option<DateTime<Utc>> {
+        if self.paused {
+            return None;
+        }
+        schedules::next_run(&self.cron, after, timezone)
+    }
+
+    fn describe(&self, timezone: Tz, locale: Locale) -> String {
+        let channels: Vec<String> = self
+            .channels
+            .iter()
+            .map(|channel_id| format!("<#{}>", channel_id))
+            .collect();
+        let mut line = format!(
+            "`{}` {} `{}` {}",
+            self.id,
+            self.template,
+            self.cron,
+            channels.join(" ")
+        );
+        if self.paused {
+            line.push_str(&format!(
+                " ({})",
+                Message::new("announce-paused-label").render(locale)
+            ));
+        } else if let Some(next) = self.next_after(Utc::now(), timezone) {
+            line.push_str(&format!(" → <t:{}:f>", next.timestamp()));
+        }
+        line
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnouncementConfig {
+    /// Texts by name. `{channel}` is replaced by a mention of the channel
+    /// posted to.
+    pub templates: BTreeMap<String, String>,
+    pub announcements: Vec<Announcement>,
+}
+
+impl AnnouncementConfig {
+    fn next_id(&self) -> u32 {
+        self.announcements
+            .iter()
+            .map(|announcement| announcement.id + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn find_mut(&mut self, id: u32) -> Option<&mut Announcement> {
+        self.announcements
+            .iter_mut()
+            .find(|announcement| announcement.id == id)
+    }
+
+    fn has_template(&self, name: &str) -> bool {
+        name == ETIQUETTE || self.templates.contains_key(name)
+    }
+
+    pub fn due(&self, from: DateTime<Utc>, to: DateTime<Utc>, timezone: Tz) -> Vec<&Announcement> {
+        self.announcements
+            .iter()
+            .filter(|announcement| {
+                announcement
+                    .next_after(from, timezone)
+                    .is_some_and(|next| next <= to)
+            })
+            .collect()
+    }
+
+    pub fn next_after(&self, after: DateTime<Utc>, timezone: Tz) -> Option<DateTime<Utc>> {
+        self.announcements
+            .iter()
+            .filter_map(|announcement| announcement.next_after(after, timezone))
+            .min()
+    }
+}
+
+/// Text of the template `name` as posted to `channel_id`, none if there is
+/// no such template.
+pub fn render(
+    config: &Config,
+    name: &str,
+    channel_id: Id<ChannelMarker>,
+    locale: Locale,
+) -> Option<String> {
+    let text = match config.announcements.templates.get(name) {
+        Some(text) => text.clone(),
+        None if name == ETIQUETTE => {
+            let names: Vec<&str> = config
+                .guard
+                .protected
+                .iter()
+                .map(|protected| protected.name.as_str())
+                .collect();
+            Message::new("guard-warning")
+                .arg("name", names.join(", "))
+                .render(locale)
+        }
+        None => return None,
+    };
+    Some(text.replace("{channel}", &format!("<#{}>", channel_id)))
+}
+
+/// Posts announcements, forever.
+pub async fn run(http: Arc<dyn Discord>, state: BotState) {
+    let mut last = Utc::now();
+    loop {
+        let now = schedules::sleep_until_next(&state, last, |config, after| {
+            config
+                .announcements
+                .next_after(after, config.schedules.timezone)
+        })
+        .await;
+        let posts: Vec<(u32, Id<ChannelMarker>, String)> = match state.config.lock() {
+            Ok(config) => {
+                let timezone = config.schedules.timezone;
+                config
+                    .announcements
+                    .due(last, now, timezone)
+                    .into_iter()
+                    .flat_map(|announcement| {
+                        announcement.channels.iter().map(|&channel_id| {
+                            (announcement.id, channel_id, announcement.template.clone())
+                        })
+                    })
+                    .collect()
+            }
+            Err(_) => Vec::new(),
+        };
+        last = now;
+        for (id, channel_id, template) in posts {
+            // Each channel gets the language of its own guild.
+            let guild_id = match lookup::channel_guild(&*http, &state, channel_id).await {
+                Ok(guild_id) => guild_id,
+                Err(err) => {
+                    tracing::warn!(%err, id, channel = %channel_id, "failed to find the guild of the channel");
+                    None
+                }
+            };
+            let text = match state.config.lock() {
+                Ok(config) => render(&config, &template, channel_id, config.locale(guild_id)),
+                Err(_) => continue,
+            };
+            let Some(text) = text else {
+                tracing::warn!(id, "announcement template is missing");
+                continue;
+            };
+            tracing::info!(id, channel = %channel_id, "posting announcement");
+            let result = Outgoing::new(channel_id)
+                .allowed_mentions(AllowedMentions::default())
+                .content(text)
+                .send(&*http)
+                .await;
+            if let Err(err) = result {
+                tracing::warn!(%err, id, channel = %channel_id, "failed to post announcement");
+            }
+        }
+    }
+}
+
+fn parse_id(inv: &Invocation) -> Result<u32, CommandError> {
+    inv.args[0].parse().map_err(|_| inv.bad_usage())
+}
+
+fn unknown(id: u32) -> CommandError {
+    CommandError::NotFound(Message::new("announce-unknown").arg("id", id.to_string()))
+}
+
+pub async fn list(inv: Invocation) -> CommandResult {
+    let (announcements, timezone) = {
+        let config = inv.state.config.lock()?;
+        (
+            config.announcements.announcements.clone(),
+            config.schedules.timezone,
+        )
+    };
+    let response = if announcements.is_empty() {
+        Message::new("announce-list-empty").render(inv.locale)
+    } else {
+        let mut response = Message::new("announce-list-header")
+            .arg("timezone", timezone.name())
+            .render(inv.locale);
+        for announcement in &announcements {
+            response.push_str("\n- ");
+            response.push_str(&announcement.describe(timezone, inv.locale));
+        }
+        response
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn add(inv: Invocation) -> CommandResult {
+    let template = inv.args[0].clone();
+    let cron = inv.args[1].trim().to_owned();
+    if !inv
+        .state
+        .config
+        .lock()?
+        .announcements
+        .has_template(&template)
+    {
+        return Err(CommandError::NotFound(
+            Message::new("announce-unknown-template").arg("template", template),
+        ));
+    }
+    if schedules::parse_cron(&cron).is_none() {
+        return Err(CommandError::NotFound(
+            Message::new("schedule-bad-cron").arg("cron", cron),
+        ));
+    }
+    let mut channels = Vec::new();
+    for arg in &inv.args[2..] {
+        let Some(channel_id) = registry::parse_channel(arg) else {
+            return Err(inv.bad_usage());
+        };
+        let guild_id = lookup::channel_guild(&*inv.http, &inv.state, channel_id).await?;
+        if guild_id.is_none() || guild_id != inv.msg.guild_id {
+            return Err(CommandError::NotFound(
+                Message::new("schedule-unknown-channel").arg("channel", channel_id.to_string()),
+            ));
+        }
+        if !channels.contains(&channel_id) {
+            channels.push(channel_id);
+        }
+    }
+
+    let (announcement, timezone) = inv.state.update_config(|config| {
+        let announcement = Announcement {
+            id: config.announcements.next_id(),
+            template,
+            cron,
+            channels,
+            paused: false,
+        };
+        config
+            .announcements
+            .announcements
+            .push(announcement.clone());
+        (announcement, config.schedules.timezone)
+    })?;
+    let description = announcement.describe(timezone, inv.locale);
+    let record = AuditRecord::new(
+        Action::Announcements,
+        Some(inv.msg.author.id),
+        format!("#{}", announcement.id),
+    )
+    .after(description.as_str());
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("announce-added")
+        .arg("announcement", description)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn remove(inv: Invocation) -> CommandResult {
+    let id = parse_id(&inv)?;
+    let removed = inv.state.update_config(|config| {
+        let announcements = &mut config.announcements.announcements;
+        let index = announcements
+            .iter()
+            .position(|announcement| announcement.id == id)?;
+        Some(announcements.remove(index))
+    })?;
+    let Some(removed) = removed else {
+        return Err(unknown(id));
+    };
+    let record = AuditRecord::new(
+        Action::Announcements,
+        Some(inv.msg.author.id),
+        format!("#{}", id),
+    )
+    .before(format!("{} `{}`", removed.template, removed.cron));
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("announce-removed")
+        .arg("id", id.to_string())
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+async fn set_paused(inv: Invocation, paused: bool) -> CommandResult {
+    let id = parse_id(&inv)?;
+    let found = inv.state.update_config(|config| {
+        let announcement = config.announcements.find_mut(id)?;
+        announcement.paused = paused;
+        Some(())
+    })?;
+    if found.is_none() {
+        return Err(unknown(id));
+    }
+    let state = if paused { "paused" } else { "running" };
+    let record = AuditRecord::new(
+        Action::Announcements,
+        Some(inv.msg.author.id),
+        format!("#{}", id),
+    )
+    .after(state);
+    audit::log(&*inv.http, &inv.state, record).await;
+    let key = if paused {
+        "announce-paused"
+    } else {
+        "announce-resumed"
+    };
+    let response = Message::new(key)
+        .arg("id", id.to_string())
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn pause(inv: Invocation) -> CommandResult {
+    set_paused(inv, true).await
+}
+
+pub async fn resume(inv: Invocation) -> CommandResult {
+    set_paused(inv, false).await
+}
+
+/// Shows what an announcement would post and when, without posting it.
+pub async fn preview(inv: Invocation) -> CommandResult {
+    let id = parse_id(&inv)?;
+    let response = {
+        let config = inv.state.config.lock()?;
+        let timezone = config.schedules.timezone;
+        let Some(announcement) = config
+            .announcements
+            .announcements
+            .iter()
+            .find(|announcement| announcement.id == id)
+        else {
+            return Err(unknown(id));
+        };
+        let mut runs = Vec::new();
+        let mut after = Utc::now();
+        while runs.len() < PREVIEW_RUNS {
+            let Some(next) = schedules::next_run(&announcement.cron, after, timezone) else {
+                break;
+            };
+            runs.push(format!("<t:{}:f>", next.timestamp()));
+            after = next;
+        }
+        let channel_id = announcement
+            .channels
+            .first()
+            .copied()
+            .unwrap_or(inv.msg.channel_id);
+        let text =
+            render(&config, &announcement.template, channel_id, inv.locale).ok_or_else(|| {
+                CommandError::NotFound(
+                    Message::new("announce-unknown-template")
+                        .arg("template", announcement.template.as_str()),
+                )
+            })?;
+        let mut response = Message::new("announce-preview")
+            .arg("id", id.to_string())
+            .arg("runs", runs.join(", "))
+            .render(inv.locale);
+        if announcement.paused {
+            response.push_str(&format!(
+                " ({})",
+                Message::new("announce-paused-label").render(inv.locale)
+            ));
+        }
+        response.push_str("\n\n");
+        response.push_str(&text);
+        response
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn templates(inv: Invocation) -> CommandResult {
+    let mut names: Vec<String> = inv
+        .state
+        .config
+        .lock()?
+        .announcements
+        .templates
+        .keys()
+        .cloned()
+        .collect();
+    if !names.iter().any(|name| name == ETIQUETTE) {
+        names.push(ETIQUETTE.to_owned());
+        names.sort();
+    }
+    let response = Message::new("announce-templates")
+        .arg("templates", names.join(", "))
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn set_template(inv: Invocation) -> CommandResult {
+    let name = inv.args[0].clone();
+    let text = inv.args[1..].join(" ");
+    let before = inv.state.update_config(|config| {
+        config
+            .announcements
+            .templates
+            .insert(name.clone(), text.clone())
+    })?;
+    let mut record = AuditRecord::new(
+        Action::Announcements,
+        Some(inv.msg.author.id),
+        name.as_str(),
+    )
+    .after(text.as_str());
+    if let Some(before) = before {
+        record = record.before(before);
+    }
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("announce-template-set")
+        .arg("template", name)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn delete_template(inv: Invocation) -> CommandResult {
+    let name = inv.args[0].clone();
+    let result = inv.state.update_config(|config| {
+        let used = config
+            .announcements
+            .announcements
+            .iter()
+            .any(|announcement| announcement.template == name);
+        if used && name != ETIQUETTE {
+            return Err("announce-template-in-use");
+        }
+        config
+            .announcements
+            .templates
+            .remove(&name)
+            .ok_or("announce-unknown-template")
+    })?;
+    let before = match result {
+        Ok(before) => before,
+        Err(key) => {
+            return Err(CommandError::NotFound(
+                Message::new(key).arg("template", name),
+            ))
+        }
+    };
+    let record = AuditRecord::new(
+        Action::Announcements,
+        Some(inv.msg.author.id),
+        name.as_str(),
+    )
+    .before(before);
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("announce-template-deleted")
+        .arg("template", name)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn announcement(id: u32, cron: &str, paused: bool) -> Announcement {
+        Announcement {
+            id,
+            template: "rules".to_owned(),
+            cron: cron.to_owned(),
+            channels: vec![Id::new(5)],
+            paused,
+        }
+    }
+
+    #[test]
+    fn paused_announcements_are_not_due() {
+        let config = AnnouncementConfig {
+            templates: BTreeMap::new(),
+            announcements: vec![
+                announcement(1, "0 9 * * *", false),
+                announcement(2, "0 9 * * *", true),
+                announcement(3, "0 12 * * Mon", false),
+            ],
+        };
+        let utc = chrono_tz::UTC;
+        // 2024-03-04 is a Monday.
+        let at = |day, hour| Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap();
+        let ids = |due: Vec<&Announcement>| due.iter().map(|a| a.id).collect::<Vec<_>>();
+        assert_eq!(ids(config.due(at(4, 8), at(4, 13), utc)), vec![1, 3]);
+        assert_eq!(ids(config.due(at(5, 8), at(5, 13), utc)), vec![1]);
+        assert_eq!(config.next_after(at(5, 10), utc), Some(at(6, 9)));
+        assert_eq!(config.next_id(), 4);
+    }
+
+    #[test]
+    fn templates_render() {
+        let mut config = Config::default();
+        config.announcements.templates.insert(
+            "rules".to_owned(),
+            "Les règles de {channel} sont épinglées.".to_owned(),
+        );
+        assert_eq!(
+            render(&config, "rules", Id::new(5), Locale::Fr).unwrap(),
+            "Les règles de <#5> sont épinglées."
+        );
+        let etiquette = render(&config, ETIQUETTE, Id::new(5), Locale::Fr).unwrap();
+        assert!(etiquette.contains("Julia"));
+        assert_eq!(render(&config, "missing", Id::new(5), Locale::Fr), None);
+
+        config
+            .announcements
+            .templates
+            .insert(ETIQUETTE.to_owned(), "Pas de mentions.".to_owned());
+        assert_eq!(
+            render(&config, ETIQUETTE, Id::new(5), Locale::Fr).unwrap(),
+            "Pas de mentions."
+        );
+    }
+}
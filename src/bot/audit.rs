@@ -0,0 +1,475 @@
+//! Moderation log: admin actions are appended to an audit file, one JSON
+//! record per line, and posted to the mod-log channel when one is set.
+//!
+//! Logging never makes an action fail, errors are only traced.
+
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use twilight_model::channel::message::embed::{Embed, EmbedField};
+use twilight_model::id::{marker::UserMarker, Id};
+use twilight_model::util::Timestamp;
+
+use super::command::reply_in_chann;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::lookup;
+use super::outbox::Outgoing;
+use super::registry::{self, CommandResult, Invocation};
+use super::BotState;
+use crate::i18n::{Locale, Message};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    PictureAdded,
+    PictureDeleted,
+    NicknameReset,
+    /// A member's role was changed by the bot on an admin's behalf.
+    RoleChanged,
+    /// A reaction role message was set up or changed.
+    ReactionRoles,
+    MentionGuard,
+    /// A permission level was granted or changed.
+    Permissions,
+    /// The channels a command works in were changed.
+    ChannelScopes,
+    /// Scheduled pictures were changed.
+    Schedules,
+    /// Announcements or their templates were changed.
+    Announcements,
+    /// Custom commands or their keywords were changed.
+    CustomCommands,
+    /// Protected users of the mention guard or offense counters were changed.
+    GuardSettings,
+}
+
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Self::PictureAdded,
+        Self::PictureDeleted,
+        Self::NicknameReset,
+        Self::RoleChanged,
+        Self::ReactionRoles,
+        Self::MentionGuard,
+        Self::Permissions,
+        Self::ChannelScopes,
+        Self::Schedules,
+        Self::Announcements,
+        Self::CustomCommands,
+        Self::GuardSettings,
+    ];
+
+    /// Name used in the audit file and to filter with `!audit`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PictureAdded => "picture_added",
+            Self::PictureDeleted => "picture_deleted",
+            Self::NicknameReset => "nickname_reset",
+            Self::RoleChanged => "role_changed",
+            Self::ReactionRoles => "reaction_roles",
+            Self::MentionGuard => "mention_guard",
+            Self::Permissions => "permissions",
+            Self::ChannelScopes => "channel_scopes",
+            Self::Schedules => "schedules",
+            Self::Announcements => "announcements",
+            Self::CustomCommands => "custom_commands",
+            Self::GuardSettings => "guard_settings",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn label(self, locale: Locale) -> String {
+        let key = match self {
+            Self::PictureAdded => "audit-picture-added",
+            Self::PictureDeleted => "audit-picture-deleted",
+            Self::NicknameReset => "audit-nickname-reset",
+            Self::RoleChanged => "audit-role-changed",
+            Self::ReactionRoles => "audit-reaction-roles",
+            Self::MentionGuard => "audit-mention-guard",
+            Self::Permissions => "audit-permissions",
+            Self::ChannelScopes => "audit-channel-scopes",
+            Self::Schedules => "audit-schedules",
+            Self::Announcements => "audit-announcements",
+            Self::CustomCommands => "audit-custom-commands",
+            Self::GuardSettings => "audit-guard-settings",
+        };
+        Message::new(key).render(locale)
+    }
+
+    fn color(self) -> u32 {
+        match self {
+            Self::PictureAdded => 0x57f287,
+            Self::PictureDeleted | Self::NicknameReset => 0xfee75c,
+            Self::RoleChanged
+            | Self::ReactionRoles
+            | Self::Permissions
+            | Self::ChannelScopes
+            | Self::Schedules
+            | Self::Announcements
+            | Self::CustomCommands
+            | Self::GuardSettings => 0x5865f2,
+            Self::MentionGuard => 0xed4245,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Unix time, in seconds.
+    pub time: u64,
+    pub action: Action,
+    /// Who did it, none when the bot acted on its own.
+    pub actor: Option<Id<UserMarker>>,
+    /// What the action applied to, as shown in the log.
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(action: Action, actor: Option<Id<UserMarker>>, target: impl Into<String>) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        Self {
+            time,
+            action,
+            actor,
+            target: target.into(),
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn before(mut self, before: impl Into<String>) -> Self {
+        self.before = Some(before.into());
+        self
+    }
+
+    pub fn after(mut self, after: impl Into<String>) -> Self {
+        self.after = Some(after.into());
+        self
+    }
+
+    fn actor_mention(&self, locale: Locale) -> String {
+        match self.actor {
+            Some(actor) => format!("<@{}>", actor),
+            None => Message::new("audit-bot").render(locale),
+        }
+    }
+
+    /// One line summary, for `!audit`.
+    fn line(&self, locale: Locale) -> String {
+        let mut line = format!(
+            "<t:{}:g> {} : {} → {}",
+            self.time,
+            self.action.label(locale),
+            self.actor_mention(locale),
+            self.target
+        );
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => line.push_str(&format!(" ({} → {})", before, after)),
+            (Some(before), None) => line.push_str(&format!(" (- {})", before)),
+            (None, Some(after)) => line.push_str(&format!(" (+ {})", after)),
+            (None, None) => {}
+        }
+        line
+    }
+
+    fn embed(&self, locale: Locale) -> Embed {
+        let field = |key: &'static str, value: &str, inline| EmbedField {
+            inline,
+            name: Message::new(key).render(locale),
+            value: clip(value, FIELD_MAX_CHARS),
+        };
+        let mut fields = vec![
+            field("audit-field-actor", &self.actor_mention(locale), true),
+            field("audit-field-target", &self.target, true),
+        ];
+        if let Some(before) = &self.before {
+            fields.push(field("audit-field-before", before, false));
+        }
+        if let Some(after) = &self.after {
+            fields.push(field("audit-field-after", after, false));
+        }
+        Embed {
+            author: None,
+            color: Some(self.action.color()),
+            description: None,
+            fields,
+            footer: None,
+            image: None,
+            kind: "rich".to_owned(),
+            provider: None,
+            thumbnail: None,
+            timestamp: Timestamp::from_secs(self.time as i64).ok(),
+            title: Some(self.action.label(locale)),
+            url: None,
+            video: None,
+        }
+    }
+}
+
+/// Discord rejects embed fields longer than this.
+const FIELD_MAX_CHARS: usize = 1024;
+
+fn clip(text: &str, max: usize) -> String {
+    if text.is_empty() {
+        return "-".to_owned();
+    }
+    if text.chars().count() <= max {
+        return text.to_owned();
+    }
+    let mut clipped: String = text.chars().take(max - 1).collect();
+    clipped.push('…');
+    clipped
+}
+
+/// What to look for with `!audit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Records made by or about a user.
+    User(Id<UserMarker>),
+    Action(Action),
+}
+
+impl Filter {
+    pub fn parse(arg: &str) -> Option<Filter> {
+        if let Some(action) = Action::from_name(arg) {
+            return Some(Self::Action(action));
+        }
+        registry::parse_user(arg).map(Self::User)
+    }
+
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        match self {
+            Self::User(user_id) => {
+                record.actor == Some(*user_id) || record.target.contains(&format!("<@{}>", user_id))
+            }
+            Self::Action(action) => record.action == *action,
+        }
+    }
+}
+
+/// Newest records first, at most `limit` of them.
+pub fn query(records: &[AuditRecord], filter: Option<Filter>, limit: usize) -> Vec<&AuditRecord> {
+    records
+        .iter()
+        .rev()
+        .filter(|record| filter.is_none_or(|filter| filter.matches(record)))
+        .take(limit)
+        .collect()
+}
+
+/// The audit file.
+pub struct AuditLog {
+    path: String,
+}
+
+impl AuditLog {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+        }
+    }
+
+    pub fn append(&self, record: &AuditRecord) -> anyhow::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(&mut file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    /// Every record, oldest first. Lines that can't be read are skipped.
+    pub fn read(&self) -> anyhow::Result<Vec<AuditRecord>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut records = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            match serde_json::from_str(&line?) {
+                Ok(record) => records.push(record),
+                Err(err) => tracing::warn!(%err, line = number + 1, "skipping audit record"),
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Records `record` in the audit file and the mod-log channel.
+pub async fn log(http: &dyn Discord, state: &BotState, record: AuditRecord) {
+    tracing::info!(
+        action = record.action.name(),
+        actor = ?record.actor,
+        target = record.target,
+        "audit"
+    );
+    match state.audit.lock() {
+        Ok(audit) => {
+            if let Err(err) = audit.append(&record) {
+                tracing::warn!(%err, "failed to write audit record");
+            }
+        }
+        Err(err) => tracing::warn!(%err, "failed to lock audit log"),
+    }
+
+    let channel_id = match state.config.lock() {
+        Ok(config) => config.mod_log_channel,
+        Err(_) => return,
+    };
+    let Some(channel_id) = channel_id else {
+        return;
+    };
+    // The embed is in the language of the guild of the mod-log channel.
+    let guild_id = match lookup::channel_guild(http, state, channel_id).await {
+        Ok(guild_id) => guild_id,
+        Err(err) => {
+            tracing::warn!(%err, "failed to find the guild of the mod-log channel");
+            None
+        }
+    };
+    let locale = state.locale(guild_id);
+    // Mentions in embeds never ping anyone.
+    let result = Outgoing::new(channel_id)
+        .embeds(vec![record.embed(locale)])
+        .send(http)
+        .await;
+    if let Err(err) = result {
+        tracing::warn!(%err, "failed to post to the mod-log channel");
+    }
+}
+
+/// Most records listed by `!audit`.
+const AUDIT_MAX_LINES: usize = 15;
+/// Keeps `!audit` replies under Discord's message length.
+const AUDIT_MAX_CHARS: usize = 1900;
+
+pub async fn show(inv: Invocation) -> CommandResult {
+    let filter = match inv.args.first() {
+        None => None,
+        Some(arg) => Some(Filter::parse(arg).ok_or_else(|| inv.bad_usage())?),
+    };
+    let records = inv
+        .state
+        .audit
+        .lock()?
+        .read()
+        .map_err(|err| CommandError::Storage(err.to_string()))?;
+    let found = query(&records, filter, AUDIT_MAX_LINES);
+    if found.is_empty() {
+        let response = Message::new("audit-empty").render(inv.locale);
+        return reply_in_chann(&*inv.http, &inv.msg, &response).await;
+    }
+
+    let mut response = Message::new("audit-header").render(inv.locale);
+    for record in found {
+        let line = record.line(inv.locale);
+        if response.chars().count() + line.chars().count() > AUDIT_MAX_CHARS {
+            response.push_str("\n…");
+            break;
+        }
+        response.push('\n');
+        response.push_str(&line);
+    }
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn set_channel(inv: Invocation) -> CommandResult {
+    let channel_id = match inv.args[0].as_str() {
+        "off" | "non" | "aucun" => None,
+        arg => Some(registry::parse_channel(arg).ok_or_else(|| inv.bad_usage())?),
+    };
+    if let Some(channel_id) = channel_id {
+        let guild_id = lookup::channel_guild(&*inv.http, &inv.state, channel_id).await?;
+        if guild_id.is_none() || guild_id != inv.msg.guild_id {
+            return Err(CommandError::NotFound(
+                Message::new("audit-unknown-channel").arg("channel", channel_id.to_string()),
+            ));
+        }
+    }
+    inv.state
+        .update_config(|config| config.mod_log_channel = channel_id)?;
+    let response = match channel_id {
+        Some(channel_id) => {
+            Message::new("audit-channel-set").arg("channel", format!("<#{}>", channel_id))
+        }
+        None => Message::new("audit-channel-off"),
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(time: u64, action: Action, actor: u64, target: &str) -> AuditRecord {
+        AuditRecord {
+            time,
+            action,
+            actor: Some(Id::new(actor)),
+            target: target.to_owned(),
+            before: None,
+            after: None,
+        }
+    }
+
+    #[test]
+    fn query_filters_newest_first() {
+        let records = vec![
+            record(1, Action::PictureAdded, 10, "mood"),
+            record(2, Action::NicknameReset, 10, "<@20>"),
+            record(3, Action::MentionGuard, 20, "<@30>"),
+            record(4, Action::PictureDeleted, 11, "mood"),
+        ];
+        let times = |found: Vec<&AuditRecord>| found.iter().map(|r| r.time).collect::<Vec<_>>();
+        assert_eq!(times(query(&records, None, 3)), vec![4, 3, 2]);
+        assert_eq!(
+            times(query(&records, Filter::parse("<@20>"), 10)),
+            vec![3, 2]
+        );
+        assert_eq!(
+            times(query(&records, Filter::parse("picture_added"), 10)),
+            vec![1]
+        );
+        assert_eq!(Filter::parse("mood"), None);
+    }
+
+    #[test]
+    fn records_roundtrip_through_file() {
+        let dir = std::env::temp_dir().join(format!("audit-test-{}", std::process::id()));
+        let log = AuditLog::new(dir.to_str().unwrap());
+        assert!(log.read().unwrap().is_empty());
+        let first = record(1, Action::PictureDeleted, 10, "mood").before("https://a");
+        let second = AuditRecord {
+            actor: None,
+            ..record(2, Action::RoleChanged, 10, "<@20>").after("<@&30>")
+        };
+        log.append(&first).unwrap();
+        log.append(&second).unwrap();
+        assert_eq!(log.read().unwrap(), vec![first, second]);
+        std::fs::remove_file(dir).unwrap();
+    }
+
+    #[test]
+    fn embed_fields_are_clipped() {
+        let long = "a".repeat(2000);
+        let embed = record(1, Action::PictureAdded, 10, "mood")
+            .after(long)
+            .embed(Locale::Fr);
+        assert_eq!(embed.fields.len(), 3);
+        assert_eq!(embed.fields[2].value.chars().count(), FIELD_MAX_CHARS);
+    }
+}
@@ -1,52 +1,91 @@
-use std::error::Error;
 use std::sync::Arc;
-use std::sync::Mutex;
-use twilight_http::Client as HttpClient;
+use std::time::Instant;
 use twilight_model::channel::message::AllowedMentions;
 use twilight_model::gateway::payload::incoming::MessageCreate;
 
-async fn reply_in_chann(
-    http: &Arc<HttpClient>,
-    msg: Box<MessageCreate>,
+use super::audit::{self, Action, AuditRecord};
+use super::discord::Discord;
+use super::error::CommandError;
+use super::outbox::Outgoing;
+use super::permissions;
+use super::rate_limit::Decision;
+use super::registry::{self, CommandResult, Invocation};
+use super::stats;
+use super::BotState;
+use crate::i18n::{Locale, Message};
+
+pub async fn reply_in_chann(
+    http: &dyn Discord,
+    msg: &MessageCreate,
     response: &str,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
+) -> CommandResult {
     // NOTE: Allowed mentions are there to ensure we don't mention someone
     // by accident.
-    let mut mentions = AllowedMentions::default();
-    mentions.replied_user = true;
-    http.create_message(msg.channel_id)
-        .allowed_mentions(Some(&mentions))
+    let mentions = AllowedMentions {
+        replied_user: true,
+        ..Default::default()
+    };
+    Outgoing::new(msg.channel_id)
+        .allowed_mentions(mentions)
         .reply(msg.id)
-        .content(response)?
+        .content(response)
+        .send(http)
         .await?;
     Ok(())
 }
 
 pub async fn picture_find_and_send(
-    album: Arc<Mutex<crate::album::Album>>,
+    state: BotState,
     msg: Box<MessageCreate>,
-    http: Arc<HttpClient>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let link = if let Some(deck_name) = msg.content.strip_prefix("!") {
-        match album.lock() {
-            Ok(mut album) => {
-                if let Some(link) = album.get_rand_pic(deck_name) {
-                    Some(link.to_owned())
-                } else {
-                    None
-                }
-            }
-            Err(_) => None,
-        }
-    } else {
-        None
+    http: Arc<dyn Discord>,
+) -> CommandResult {
+    let Some(deck_name) = msg.content.strip_prefix(registry::PREFIX) else {
+        return Ok(());
     };
-    Ok(match link {
-        Some(link) => {
-            reply_in_chann(&http, msg, &link).await?;
+    let Some(deck) = state
+        .album
+        .lock()?
+        .deck(deck_name)
+        .map(|(name, _)| name.to_owned())
+    else {
+        return Ok(());
+    };
+    match picture_rate_limit(&state, &msg)? {
+        Decision::Allowed => {
+            // Only picked once allowed, so that `last_sent` is the picture
+            // actually posted.
+            let link = state
+                .album
+                .lock()?
+                .get_rand_pic(deck_name)
+                .map(|link| link.to_owned());
+            let Some(link) = link else {
+                return Ok(());
+            };
+            reply_in_chann(&*http, &msg, &link).await?;
+            stats::count(&state, &deck, &link, msg.author.id);
+            Ok(())
+        }
+        Decision::Warn => {
+            tracing::info!(user = %msg.author.id, "picture rate limit reached");
+            let response = Message::new("picture-slow-down").render(state.locale(msg.guild_id));
+            reply_in_chann(&*http, &msg, &response).await
         }
-        _ => {}
-    })
+        Decision::Drop => Ok(()),
+    }
+}
+
+fn picture_rate_limit(state: &BotState, msg: &MessageCreate) -> Result<Decision, CommandError> {
+    let config = state.config.lock()?.picture_rate_limit.clone();
+    if permissions::of_message(msg, state) >= config.exempt {
+        return Ok(Decision::Allowed);
+    }
+    let decision =
+        state
+            .rate_limiter
+            .lock()?
+            .check(&config, msg.author.id, msg.channel_id, Instant::now());
+    Ok(decision)
 }
 
 fn mk_names_str(mut deck_names: Vec<&String>) -> String {
@@ -61,192 +100,119 @@ fn mk_names_str(mut deck_names: Vec<&String>) -> String {
         }
         names_str.push_str(name);
     }
-    return names_str;
+    names_str
 }
 
-pub async fn helper(
-    album: Arc<Mutex<crate::album::Album>>,
-    msg: Box<MessageCreate>,
-    http: Arc<HttpClient>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let reply = match album.lock() {
-        Ok(album) => Some(format!(
-            "Nombre d'albums: {}, nombre de photos: {}.\nNom des albums: {}.",
-            album.deck_count(),
-            album.picture_count(),
-            mk_names_str(album.deck_names().collect())
-        )),
-        Err(_) => None,
-    };
-    if let Some(reply) = reply {
-        reply_in_chann(&http, msg, &reply).await?;
+pub async fn helper(inv: Invocation) -> CommandResult {
+    if let Some((name, sub_args)) = inv.args.split_first() {
+        let name = name.trim_start_matches(registry::PREFIX);
+        let Some(cmd) = registry::find(name) else {
+            return Err(CommandError::NotFound(
+                Message::new("help-unknown-command")
+                    .arg("name", format!("{}{}", registry::PREFIX, name)),
+            ));
+        };
+        let help = registry::resolve(cmd, sub_args.to_vec()).help(inv.locale);
+        reply_in_chann(&*inv.http, &inv.msg, &help).await?;
+        return Ok(());
     }
-    Ok(())
+
+    let reply = {
+        let album = inv.state.album.lock()?;
+        let stats = Message::new("help-album-summary")
+            .arg("decks", album.deck_count())
+            .arg("pictures", album.picture_count())
+            .arg("names", mk_names_str(album.deck_names().collect()));
+        format!(
+            "{}\n\n{}",
+            stats.render(inv.locale),
+            registry::summary(inv.locale)
+        )
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &reply).await
 }
 
-pub async fn picture_add(
-    msg: Box<MessageCreate>,
-    album: &Arc<Mutex<crate::album::Album>>,
-    http: &Arc<HttpClient>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut num_added = 0;
-    let mut split = msg.content.split(' ');
-    let mut response = "Je n'ai rien trouvé en pièce jointe a ajouter.";
-    split.next();
-    if let Some(deck_name) = split.next() {
-        match album.lock() {
-            Ok(mut album) => {
-                for att in &msg.attachments {
-                    album.add_picture(deck_name, &att.url);
-                    num_added += 1;
-                }
-                match album.save() {
-                    Ok(_) => println!("album save sucessful"),
-                    Err(_) => eprintln!("failed to save album, data loss is possible"),
-                }
-            }
-            Err(_) => response = "Je n'arrive pas à modifier l'album, je pense que vous pouvez essayer à nouveau dans quelques minutes.",
-        }
+pub async fn picture_add(inv: Invocation) -> CommandResult {
+    let deck_name = &inv.args[0];
+    if inv.msg.attachments.is_empty() {
+        return Err(CommandError::NotFound(Message::new(
+            "picture-no-attachment",
+        )));
     }
-    if num_added > 0 {
-        let response = format!("J'ai ajouté {} image·s !", num_added);
-        reply_in_chann(http, msg, &response).await?;
-    } else {
-        reply_in_chann(http, msg, response).await?;
+    {
+        let mut album = inv.state.album.lock()?;
+        for att in &inv.msg.attachments {
+            album.add_picture(deck_name, &att.url);
+        }
+        album
+            .save()
+            .map_err(|err| CommandError::Storage(err.to_string()))?;
+        tracing::info!(deck_name, "album save sucessful");
     }
-    Ok(())
+    let urls: Vec<&str> = inv
+        .msg
+        .attachments
+        .iter()
+        .map(|att| att.url.as_str())
+        .collect();
+    let record = AuditRecord::new(
+        Action::PictureAdded,
+        Some(inv.msg.author.id),
+        deck_name.as_str(),
+    )
+    .after(urls.join("\n"));
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("picture-added").arg("count", inv.msg.attachments.len());
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
 }
 
-pub async fn delete_last(
-    msg: Box<MessageCreate>,
-    album: &Arc<Mutex<crate::album::Album>>,
-    http: &Arc<HttpClient>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let response =
-        "Je ne me souvient pas de la dernière image envoyée, donc je n'ai rien supprimé.";
-
-    let removed = match album.lock() {
-        Ok(mut album) => album.remove_last(),
-        Err(_) => None,
+pub async fn delete_last(inv: Invocation) -> CommandResult {
+    let Some(removed) = inv.state.album.lock()?.remove_last() else {
+        return Err(CommandError::NotFound(Message::new("picture-no-last")));
     };
-
-    if let Some(removed) = removed {
-        let response = format!(
-            "Depuis le deck {} j'ai supprimé l'image {}",
-            removed.deck, removed.url
-        );
-        reply_in_chann(http, msg, &response).await?;
-    } else {
-        reply_in_chann(http, msg, response).await?;
-    }
-
-    Ok(())
+    let record = AuditRecord::new(
+        Action::PictureDeleted,
+        Some(inv.msg.author.id),
+        removed.deck.as_str(),
+    )
+    .before(removed.url.as_str());
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let response = Message::new("picture-last-deleted")
+        .arg("deck", removed.deck)
+        .arg("url", removed.url);
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
 }
 
-pub async fn delete_picture(
-    msg: Box<MessageCreate>,
-    album: &Arc<Mutex<crate::album::Album>>,
-    http: &Arc<HttpClient>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut response = "Je n'ai rien supprimé.";
-    let mut split = msg.content.split(' ');
+pub async fn delete_picture(inv: Invocation) -> CommandResult {
+    let (deck_name, url) = (&inv.args[0], &inv.args[1]);
 
-    split.next();
-    let removed = if let Some(deck_name) = split.next() {
-        if let Some(url) = split.next() {
-            match album.lock() {
-                Ok(mut album) => album.remove_picture(deck_name, url),
-                Err(_) => false,
-            }
-        } else {
-            false
-        }
-    } else {
-        false
-    };
-
-    if removed {
-        response = "J'ai supprimé l'image !";
+    if !inv.state.album.lock()?.remove_picture(deck_name, url) {
+        return Err(CommandError::NotFound(
+            Message::new("picture-not-deleted").arg("deck", deck_name.as_str()),
+        ));
     }
-    reply_in_chann(http, msg, response).await?;
-    Ok(())
+    let record = AuditRecord::new(
+        Action::PictureDeleted,
+        Some(inv.msg.author.id),
+        deck_name.as_str(),
+    )
+    .before(url.as_str());
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("picture-deleted").render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
 }
 
-async fn get_gild_members(
-    guild_id: &twilight_model::id::Id<twilight_model::id::marker::GuildMarker>,
-    http: &Arc<HttpClient>,
-) -> Result<Vec<twilight_model::guild::Member>, &'static str> {
-    let Ok(resp) = http.guild_members(*guild_id).await else {
-        return Err("api didn't respond with the member list");
+pub async fn set_locale(inv: Invocation) -> CommandResult {
+    let Some(locale) = Locale::from_tag(&inv.args[0]) else {
+        return Err(inv.bad_usage());
     };
-
-    let Ok(members) = resp.models().await else {
-        return Err("could not make member models out of api response");
+    let Some(guild_id) = inv.msg.guild_id else {
+        return Err(CommandError::NotFound(Message::new("error-no-guild")));
     };
-    return Ok(members);
-}
-
-async fn member_reset_nickname(
-    http: &Arc<HttpClient>,
-    guild_id: twilight_model::id::Id<twilight_model::id::marker::GuildMarker>,
-    user_id: twilight_model::id::Id<twilight_model::id::marker::UserMarker>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut update_builder = http.update_guild_member(guild_id, user_id);
-    update_builder = update_builder.nick(None)?;
-    update_builder.await?;
-    Ok(())
-}
-pub async fn reset_nick(
-    msg: Box<MessageCreate>,
-    http: &Arc<HttpClient>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut split = msg.content.split(' ');
-    split.next();
-    let to_reset: Vec<&str> = split.collect();
-    let Some(guild_id) = msg.guild_id else {
-        reply_in_chann(
-            http,
-            msg,
-            "Je n'ai pas réussi à récupérer l'identifiant de la guilde",
-        )
-        .await?;
-        return Ok(());
-    };
-    let resp = http.guild_members(guild_id).limit(1000)?.await.unwrap();
-    let members = resp.models().await.unwrap();
-    //   let Ok(members) = get_gild_members(&guild_id, http).await else {
-    //       reply_in_chann(http, msg, "Je n'ai pas réussi à récupérer la liste de membres").await?;
-    //       return Ok(());
-    //   };
-    let members_to_reset = members.iter().filter(|item| match &item.nick {
-        Some(nick) if to_reset.contains(&(nick as &str)) => true,
-        _ => false,
-    });
-
-    let mut changed_str: String = String::new();
-    let mut failed_str: String = String::new();
-    let mut response = "J'ai mis à zéro les noms d'utilisateur de :".to_owned();
-    for member in members_to_reset {
-        match member_reset_nickname(http, guild_id, member.user.id).await {
-            Ok(_) => changed_str.push_str(&format!(
-                " {}#{}",
-                member.user.name, member.user.discriminator
-            )),
-            Err(err) => {
-                failed_str.push_str(&format!(
-                    " {}#{}",
-                    member.user.name, member.user.discriminator
-                ));
-                eprintln!("failed to change nickanme because: {}", err)
-            }
-        }
-    }
-    response.push_str(&changed_str);
-    if failed_str.len() > 0 {
-        response.push_str("\nJe n'ai pas réussi à changer ceux de :");
-        response.push_str(&failed_str);
-    }
-
-    reply_in_chann(http, msg, &response).await?;
-    Ok(())
+    inv.state.update_config(|config| {
+        config.guild_locales.insert(guild_id, locale);
+    })?;
+    let response = Message::new("locale-set").render(locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
 }
@@ -0,0 +1,114 @@
+//! Confirmation of mass changes: a command keeps what it is about to do
+//! until its author runs `!confirm`, or drops it with `!cancel`.
+//!
+//! Pending actions are only kept in memory for a short while.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use twilight_model::id::{
+    marker::{GuildMarker, UserMarker},
+    Id,
+};
+
+use super::command::reply_in_chann;
+use super::lookup::MemberInfo;
+use super::nicknames;
+use super::registry::{CommandResult, Invocation};
+use crate::i18n::Message;
+
+/// How long an action waits for its confirmation.
+pub const TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pending {
+    ResetNick {
+        guild_id: Id<GuildMarker>,
+        members: Vec<MemberInfo>,
+    },
+}
+
+/// Pending actions, one per member who asked for them.
+#[derive(Debug, Default)]
+pub struct Confirmations {
+    pending: HashMap<Id<UserMarker>, (Instant, Pending)>,
+}
+
+impl Confirmations {
+    /// Keeps `pending` for `user_id`, replacing what they asked for before.
+    pub fn ask(&mut self, user_id: Id<UserMarker>, pending: Pending, now: Instant) {
+        self.pending
+            .retain(|_, (asked, _)| now.duration_since(*asked) < TIMEOUT);
+        self.pending.insert(user_id, (now, pending));
+    }
+
+    /// Takes the action `user_id` asked for, unless it expired.
+    pub fn take(&mut self, user_id: Id<UserMarker>, now: Instant) -> Option<Pending> {
+        let (asked, pending) = self.pending.remove(&user_id)?;
+        (now.duration_since(asked) < TIMEOUT).then_some(pending)
+    }
+
+    pub fn cancel(&mut self, user_id: Id<UserMarker>) -> bool {
+        self.pending.remove(&user_id).is_some()
+    }
+}
+
+pub async fn confirm(inv: Invocation) -> CommandResult {
+    let pending = inv
+        .state
+        .confirmations
+        .lock()?
+        .take(inv.msg.author.id, Instant::now());
+    match pending {
+        Some(Pending::ResetNick { guild_id, members }) => {
+            nicknames::apply_reset(&inv, guild_id, &members).await
+        }
+        None => {
+            let response = Message::new("confirm-nothing").render(inv.locale);
+            reply_in_chann(&*inv.http, &inv.msg, &response).await
+        }
+    }
+}
+
+pub async fn cancel(inv: Invocation) -> CommandResult {
+    let cancelled = inv.state.confirmations.lock()?.cancel(inv.msg.author.id);
+    let key = if cancelled {
+        "confirm-cancelled"
+    } else {
+        "confirm-nothing"
+    };
+    let response = Message::new(key).render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending() -> Pending {
+        Pending::ResetNick {
+            guild_id: Id::new(1),
+            members: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn taken_once_before_expiry() {
+        let now = Instant::now();
+        let mut confirmations = Confirmations::default();
+        confirmations.ask(Id::new(5), pending(), now);
+        assert_eq!(confirmations.take(Id::new(6), now), None);
+        assert_eq!(
+            confirmations.take(Id::new(5), now + Duration::from_secs(10)),
+            Some(pending())
+        );
+        assert_eq!(confirmations.take(Id::new(5), now), None);
+
+        confirmations.ask(Id::new(5), pending(), now);
+        assert_eq!(confirmations.take(Id::new(5), now + TIMEOUT), None);
+
+        confirmations.ask(Id::new(5), pending(), now);
+        assert!(confirmations.cancel(Id::new(5)));
+        assert!(!confirmations.cancel(Id::new(5)));
+    }
+}
@@ -0,0 +1,419 @@
+//! Custom commands: text responses written by admins at runtime with
+//! `!cmd`, so that new jokes don't need a new release.
+//!
+//! A custom command answers `!name` with one of its responses, picked at
+//! random, and may also answer messages containing one of its keywords.
+//! Built-in commands come first, custom ones before pictures.
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use twilight_model::gateway::payload::incoming::MessageCreate;
+use twilight_model::id::{
+    marker::{ChannelMarker, UserMarker},
+    Id,
+};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::registry::{self, CommandResult, Invocation};
+use super::scopes;
+use super::BotState;
+use crate::i18n::Message;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomCommand {
+    pub name: String,
+    /// One is picked at random. `{user}` and `{channel}` are replaced by
+    /// mentions of the author and of the channel.
+    pub responses: Vec<String>,
+    /// Words answered to without the prefix.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<String>,
+}
+
+impl CustomCommand {
+    fn respond(&self, user: Id<UserMarker>, channel: Id<ChannelMarker>) -> Option<String> {
+        let response = self.responses.choose(&mut rand::thread_rng())?;
+        Some(render(response, user, channel))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomConfig {
+    pub commands: Vec<CustomCommand>,
+}
+
+impl Default for CustomConfig {
+    /// `!gouvernement`, which used to be built in.
+    fn default() -> Self {
+        Self {
+            commands: vec![CustomCommand {
+                name: "gouvernement".to_owned(),
+                responses: vec![
+                    "https://clips.twitch.tv/FriendlyResilientSlothShazBotstix-HWxnFQWq6iPPsVZf"
+                        .to_owned(),
+                ],
+                triggers: Vec::new(),
+            }],
+        }
+    }
+}
+
+impl CustomConfig {
+    pub fn find(&self, name: &str) -> Option<&CustomCommand> {
+        self.commands
+            .iter()
+            .find(|command| command.name.eq_ignore_ascii_case(name))
+    }
+
+    fn find_mut(&mut self, name: &str) -> Option<&mut CustomCommand> {
+        self.commands
+            .iter_mut()
+            .find(|command| command.name.eq_ignore_ascii_case(name))
+    }
+
+    /// First command with a keyword in `content`.
+    pub fn triggered(&self, content: &str) -> Option<&CustomCommand> {
+        let content = words(content);
+        self.commands.iter().find(|command| {
+            command.triggers.iter().any(|trigger| {
+                let trigger = words(trigger);
+                !trigger.is_empty()
+                    && content
+                        .windows(trigger.len())
+                        .any(|window| window == trigger)
+            })
+        })
+    }
+}
+
+/// Words of `text`, lowercase and without accents.
+fn words(text: &str) -> Vec<String> {
+    deunicode::deunicode(text)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+pub fn render(response: &str, user: Id<UserMarker>, channel: Id<ChannelMarker>) -> String {
+    response
+        .replace("{user}", &format!("<@{}>", user))
+        .replace("{channel}", &format!("<#{}>", channel))
+}
+
+/// Answers `!name` if it is a custom command. Returns whether it was one.
+pub async fn on_command(
+    name: &str,
+    msg: &MessageCreate,
+    http: &dyn Discord,
+    state: &BotState,
+) -> Result<bool, CommandError> {
+    let response = {
+        let config = state.config.lock()?;
+        match config.custom_commands.find(name) {
+            Some(command) => command.respond(msg.author.id, msg.channel_id),
+            None => return Ok(false),
+        }
+    };
+    if let Some(response) = response {
+        if scopes::permits(state, scopes::ALL_COMMANDS, msg.channel_id) {
+            reply_in_chann(http, msg, &response).await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Answers a message containing a keyword. Returns whether one was found.
+pub async fn on_message(
+    msg: &MessageCreate,
+    http: &dyn Discord,
+    state: &BotState,
+) -> Result<bool, CommandError> {
+    let response = {
+        let config = state.config.lock()?;
+        match config.custom_commands.triggered(&msg.content) {
+            Some(command) => command.respond(msg.author.id, msg.channel_id),
+            None => return Ok(false),
+        }
+    };
+    let Some(response) = response else {
+        return Ok(false);
+    };
+    if !scopes::permits(state, scopes::ALL_COMMANDS, msg.channel_id) {
+        return Ok(false);
+    }
+    tracing::info!(user = %msg.author.id, "custom command triggered");
+    reply_in_chann(http, msg, &response).await?;
+    Ok(true)
+}
+
+fn unknown(name: &str) -> CommandError {
+    CommandError::NotFound(
+        Message::new("custom-unknown").arg("name", format!("{}{}", registry::PREFIX, name)),
+    )
+}
+
+/// Name given to `!cmd`, without its prefix.
+fn name_arg(inv: &Invocation) -> String {
+    inv.args[0]
+        .trim_start_matches(registry::PREFIX)
+        .to_lowercase()
+}
+
+async fn log(inv: &Invocation, target: &str, before: Option<String>, after: Option<String>) {
+    let mut record = AuditRecord::new(
+        Action::CustomCommands,
+        Some(inv.msg.author.id),
+        format!("{}{}", registry::PREFIX, target),
+    );
+    if let Some(before) = before {
+        record = record.before(before);
+    }
+    if let Some(after) = after {
+        record = record.after(after);
+    }
+    audit::log(&*inv.http, &inv.state, record).await;
+}
+
+pub async fn list(inv: Invocation) -> CommandResult {
+    let mut lines: Vec<String> = inv
+        .state
+        .config
+        .lock()?
+        .custom_commands
+        .commands
+        .iter()
+        .map(|command| {
+            let mut line = format!(
+                "- `{}{}` ({})",
+                registry::PREFIX,
+                command.name,
+                command.responses.len()
+            );
+            if !command.triggers.is_empty() {
+                line.push_str(&format!(" : {}", command.triggers.join(", ")));
+            }
+            line
+        })
+        .collect();
+    lines.sort();
+    let response = if lines.is_empty() {
+        Message::new("custom-list-empty").render(inv.locale)
+    } else {
+        format!(
+            "{}\n{}",
+            Message::new("custom-list-header").render(inv.locale),
+            lines.join("\n")
+        )
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn show(inv: Invocation) -> CommandResult {
+    let name = name_arg(&inv);
+    let command = inv
+        .state
+        .config
+        .lock()?
+        .custom_commands
+        .find(&name)
+        .cloned();
+    let Some(command) = command else {
+        return Err(unknown(&name));
+    };
+    let mut response = Message::new("custom-show-header")
+        .arg("name", format!("{}{}", registry::PREFIX, command.name))
+        .render(inv.locale);
+    for (number, text) in command.responses.iter().enumerate() {
+        response.push_str(&format!("\n{}. {}", number + 1, text));
+    }
+    if !command.triggers.is_empty() {
+        response.push('\n');
+        response.push_str(
+            &Message::new("custom-show-triggers")
+                .arg("triggers", command.triggers.join(", "))
+                .render(inv.locale),
+        );
+    }
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn add(inv: Invocation) -> CommandResult {
+    let name = name_arg(&inv);
+    let text = inv.args[1..].join(" ");
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(inv.bad_usage());
+    }
+    if registry::find(&name).is_some() {
+        return Err(CommandError::NotFound(
+            Message::new("custom-builtin").arg("name", format!("{}{}", registry::PREFIX, name)),
+        ));
+    }
+    let count = inv.state.update_config(|config| {
+        let commands = &mut config.custom_commands;
+        match commands.find_mut(&name) {
+            Some(command) => {
+                command.responses.push(text.clone());
+                command.responses.len()
+            }
+            None => {
+                commands.commands.push(CustomCommand {
+                    name: name.clone(),
+                    responses: vec![text.clone()],
+                    triggers: Vec::new(),
+                });
+                1
+            }
+        }
+    })?;
+    log(&inv, &name, None, Some(text)).await;
+    let response = Message::new("custom-added")
+        .arg("name", format!("{}{}", registry::PREFIX, name))
+        .arg("count", count)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn remove(inv: Invocation) -> CommandResult {
+    let name = name_arg(&inv);
+    let number = match inv.args.get(1) {
+        None => None,
+        Some(arg) => Some(arg.parse::<usize>().map_err(|_| inv.bad_usage())?),
+    };
+    let removed = inv.state.update_config(|config| {
+        let commands = &mut config.custom_commands.commands;
+        let index = commands
+            .iter()
+            .position(|command| command.name.eq_ignore_ascii_case(&name))?;
+        match number {
+            None => Some(Some(commands.remove(index).responses.join(" | "))),
+            Some(number) if (1..=commands[index].responses.len()).contains(&number) => {
+                let response = commands[index].responses.remove(number - 1);
+                if commands[index].responses.is_empty() {
+                    commands.remove(index);
+                }
+                Some(Some(response))
+            }
+            Some(_) => Some(None),
+        }
+    })?;
+    let before = match removed {
+        None => return Err(unknown(&name)),
+        Some(None) => return Err(inv.bad_usage()),
+        Some(Some(before)) => before,
+    };
+    log(&inv, &name, Some(before), None).await;
+    let key = match number {
+        None => "custom-removed",
+        Some(_) => "custom-response-removed",
+    };
+    let response = Message::new(key)
+        .arg("name", format!("{}{}", registry::PREFIX, name))
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+/// Adds or removes a keyword of a command.
+async fn update_trigger(inv: Invocation, add: bool) -> CommandResult {
+    let name = name_arg(&inv);
+    let trigger = inv.args[1..].join(" ").to_lowercase();
+    if words(&trigger).is_empty() {
+        return Err(inv.bad_usage());
+    }
+    let changed = inv.state.update_config(|config| {
+        let command = config.custom_commands.find_mut(&name)?;
+        let known = command.triggers.contains(&trigger);
+        if add && !known {
+            command.triggers.push(trigger.clone());
+        } else if !add {
+            command.triggers.retain(|existing| *existing != trigger);
+        }
+        Some(add != known)
+    })?;
+    match changed {
+        None => return Err(unknown(&name)),
+        Some(true) if add => log(&inv, &name, None, Some(trigger.clone())).await,
+        Some(true) => log(&inv, &name, Some(trigger.clone()), None).await,
+        Some(false) => {}
+    }
+    let key = if add {
+        "custom-trigger-added"
+    } else {
+        "custom-trigger-removed"
+    };
+    let response = Message::new(key)
+        .arg("name", format!("{}{}", registry::PREFIX, name))
+        .arg("trigger", trigger)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn trigger(inv: Invocation) -> CommandResult {
+    update_trigger(inv, true).await
+}
+
+pub async fn untrigger(inv: Invocation) -> CommandResult {
+    update_trigger(inv, false).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CustomConfig {
+        CustomConfig {
+            commands: vec![
+                CustomCommand {
+                    name: "salut".to_owned(),
+                    responses: vec!["Salut {user} dans {channel} !".to_owned()],
+                    triggers: vec!["bonjour".to_owned()],
+                },
+                CustomCommand {
+                    name: "cafe".to_owned(),
+                    responses: vec!["☕".to_owned(), "🍵".to_owned()],
+                    triggers: vec!["pause café".to_owned()],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn responses_are_templated() {
+        let config = config();
+        let salut = config.find("SALUT").unwrap();
+        assert_eq!(
+            salut.respond(Id::new(1), Id::new(2)).unwrap(),
+            "Salut <@1> dans <#2> !"
+        );
+        let cafe = config.find("cafe").unwrap();
+        for _ in 0..10 {
+            assert!(cafe
+                .responses
+                .contains(&cafe.respond(Id::new(1), Id::new(2)).unwrap()));
+        }
+        assert!(config.find("poney").is_none());
+    }
+
+    #[test]
+    fn triggers_match_whole_words() {
+        let config = config();
+        let name = |content| config.triggered(content).map(|c| c.name.as_str());
+        assert_eq!(name("Bonjour tout le monde"), Some("salut"));
+        assert_eq!(name("on fait une PAUSE CAFE ?"), Some("cafe"));
+        assert_eq!(name("bonjours"), None);
+        assert_eq!(name("une pause, puis un café"), None);
+    }
+
+    #[test]
+    fn gouvernement_is_a_default() {
+        let config: CustomConfig = serde_json::from_str("{}").unwrap();
+        assert!(config.find("gouvernement").is_some());
+        let config: CustomConfig = serde_json::from_str(r#"{"commands":[]}"#).unwrap();
+        assert!(config.find("gouvernement").is_none());
+    }
+}
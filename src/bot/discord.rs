@@ -0,0 +1,757 @@
+//! Outbound Discord operations.
+//!
+//! Handlers go through [`Discord`] rather than the HTTP client itself, so
+//! that tests can run them against `fake::RecordingDiscord`, which records
+//! every call instead of sending it, and recordings can be replayed without
+//! touching Discord.
+
+use std::collections::HashMap;
+
+use futures::future::BoxFuture;
+use twilight_http::error::ErrorType;
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_http::Client as HttpClient;
+use twilight_model::channel::message::{AllowedMentions, Component};
+use twilight_model::channel::ChannelType;
+use twilight_model::http::interaction::InteractionResponse;
+use twilight_model::id::{
+    marker::{
+        ApplicationMarker, ChannelMarker, GuildMarker, InteractionMarker, MessageMarker,
+        RoleMarker, UserMarker,
+    },
+    Id,
+};
+use twilight_model::util::Timestamp;
+
+use super::error::CommandError;
+use super::lookup::MemberInfo;
+use super::outbox::Outgoing;
+
+pub type DiscordResult<T> = Result<T, CommandError>;
+
+/// Names of the roles of a guild.
+pub type RoleNames = HashMap<Id<RoleMarker>, String>;
+
+/// A user who reacted to a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reactor {
+    pub user_id: Id<UserMarker>,
+    pub bot: bool,
+}
+
+/// Everything the bot asks Discord to do.
+pub trait Discord: Send + Sync {
+    /// Sends `message` right away, returns the id of the posted message.
+    /// Use [`Outgoing::send`] to queue and retry it.
+    fn create_message<'a>(
+        &'a self,
+        message: &'a Outgoing,
+    ) -> BoxFuture<'a, DiscordResult<Id<MessageMarker>>>;
+
+    fn update_components<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        components: &'a [Component],
+    ) -> BoxFuture<'a, DiscordResult<()>>;
+
+    fn create_reaction<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+    ) -> BoxFuture<'a, DiscordResult<()>>;
+
+    /// Removes the reaction of the bot.
+    fn delete_own_reaction<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+    ) -> BoxFuture<'a, DiscordResult<()>>;
+
+    fn delete_reaction<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+        user_id: Id<UserMarker>,
+    ) -> BoxFuture<'a, DiscordResult<()>>;
+
+    /// One page of the users who reacted with `emoji`, after `after`.
+    fn reactions<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+        after: Option<Id<UserMarker>>,
+        limit: u16,
+    ) -> BoxFuture<'a, DiscordResult<Vec<Reactor>>>;
+
+    fn add_role(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        role_id: Id<RoleMarker>,
+    ) -> BoxFuture<'_, DiscordResult<()>>;
+
+    fn remove_role(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        role_id: Id<RoleMarker>,
+    ) -> BoxFuture<'_, DiscordResult<()>>;
+
+    /// Sets the nickname of the member, none resets it.
+    fn set_nickname<'a>(
+        &'a self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        nick: Option<&'a str>,
+    ) -> BoxFuture<'a, DiscordResult<()>>;
+
+    fn time_out(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        until: Option<Timestamp>,
+    ) -> BoxFuture<'_, DiscordResult<()>>;
+
+    /// One page of the members of the guild, after `after`.
+    fn guild_members(
+        &self,
+        guild_id: Id<GuildMarker>,
+        after: Option<Id<UserMarker>>,
+        limit: u16,
+    ) -> BoxFuture<'_, DiscordResult<Vec<MemberInfo>>>;
+
+    fn roles(&self, guild_id: Id<GuildMarker>) -> BoxFuture<'_, DiscordResult<RoleNames>>;
+
+    /// Guild of a channel, none when the channel doesn't exist or belongs to
+    /// no guild.
+    fn channel_guild(
+        &self,
+        channel_id: Id<ChannelMarker>,
+    ) -> BoxFuture<'_, DiscordResult<Option<Id<GuildMarker>>>>;
+
+    /// Channels of the guild that hold messages.
+    fn text_channels(
+        &self,
+        guild_id: Id<GuildMarker>,
+    ) -> BoxFuture<'_, DiscordResult<Vec<Id<ChannelMarker>>>>;
+
+    /// Whether the message is in the channel.
+    fn message_exists(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    ) -> BoxFuture<'_, DiscordResult<bool>>;
+
+    fn respond_to_interaction<'a>(
+        &'a self,
+        application_id: Id<ApplicationMarker>,
+        interaction_id: Id<InteractionMarker>,
+        token: &'a str,
+        response: &'a InteractionResponse,
+    ) -> BoxFuture<'a, DiscordResult<()>>;
+
+    /// Replaces the content of the response to an interaction, such as a
+    /// deferred one.
+    fn update_interaction_response<'a>(
+        &'a self,
+        application_id: Id<ApplicationMarker>,
+        token: &'a str,
+        content: &'a str,
+    ) -> BoxFuture<'a, DiscordResult<()>>;
+}
+
+fn is_unknown(err: &twilight_http::Error) -> bool {
+    matches!(err.kind(), ErrorType::Response { status, .. } if status.get() == 404)
+}
+
+/// Whether channels of this kind hold messages.
+pub fn is_text(kind: ChannelType) -> bool {
+    matches!(
+        kind,
+        ChannelType::GuildText | ChannelType::GuildAnnouncement | ChannelType::GuildVoice
+    )
+}
+
+impl Discord for HttpClient {
+    fn create_message<'a>(
+        &'a self,
+        message: &'a Outgoing,
+    ) -> BoxFuture<'a, DiscordResult<Id<MessageMarker>>> {
+        Box::pin(async move {
+            let mut request = self.create_message(message.channel_id);
+            if let Some(mentions) = &message.allowed_mentions {
+                request = request.allowed_mentions(Some(mentions));
+            }
+            if let Some(message_id) = message.reply_to {
+                request = request.reply(message_id);
+            }
+            if let Some(content) = &message.content {
+                request = request.content(content)?;
+            }
+            if !message.embeds.is_empty() {
+                request = request.embeds(&message.embeds)?;
+            }
+            Ok(request.await?.model().await?.id)
+        })
+    }
+
+    fn update_components<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        components: &'a [Component],
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        Box::pin(async move {
+            self.update_message(channel_id, message_id)
+                .components(Some(components))?
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn create_reaction<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        Box::pin(async move {
+            self.create_reaction(channel_id, message_id, emoji).await?;
+            Ok(())
+        })
+    }
+
+    fn delete_own_reaction<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        Box::pin(async move {
+            self.delete_current_user_reaction(channel_id, message_id, emoji)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn delete_reaction<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+        user_id: Id<UserMarker>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        Box::pin(async move {
+            self.delete_reaction(channel_id, message_id, emoji, user_id)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn reactions<'a>(
+        &'a self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+        after: Option<Id<UserMarker>>,
+        limit: u16,
+    ) -> BoxFuture<'a, DiscordResult<Vec<Reactor>>> {
+        Box::pin(async move {
+            let mut request = self.reactions(channel_id, message_id, emoji).limit(limit)?;
+            if let Some(after) = after {
+                request = request.after(after);
+            }
+            let users = request.await?.models().await?;
+            Ok(users
+                .into_iter()
+                .map(|user| Reactor {
+                    user_id: user.id,
+                    bot: user.bot,
+                })
+                .collect())
+        })
+    }
+
+    fn add_role(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        role_id: Id<RoleMarker>,
+    ) -> BoxFuture<'_, DiscordResult<()>> {
+        Box::pin(async move {
+            self.add_guild_member_role(guild_id, user_id, role_id)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn remove_role(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        role_id: Id<RoleMarker>,
+    ) -> BoxFuture<'_, DiscordResult<()>> {
+        Box::pin(async move {
+            self.remove_guild_member_role(guild_id, user_id, role_id)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn set_nickname<'a>(
+        &'a self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        nick: Option<&'a str>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        Box::pin(async move {
+            self.update_guild_member(guild_id, user_id)
+                .nick(nick)?
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn time_out(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        until: Option<Timestamp>,
+    ) -> BoxFuture<'_, DiscordResult<()>> {
+        Box::pin(async move {
+            self.update_guild_member(guild_id, user_id)
+                .communication_disabled_until(until)?
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn guild_members(
+        &self,
+        guild_id: Id<GuildMarker>,
+        after: Option<Id<UserMarker>>,
+        limit: u16,
+    ) -> BoxFuture<'_, DiscordResult<Vec<MemberInfo>>> {
+        Box::pin(async move {
+            let mut request = self.guild_members(guild_id).limit(limit)?;
+            if let Some(after) = after {
+                request = request.after(after);
+            }
+            let members = request.await?.models().await?;
+            Ok(members.into_iter().map(MemberInfo::from).collect())
+        })
+    }
+
+    fn roles(&self, guild_id: Id<GuildMarker>) -> BoxFuture<'_, DiscordResult<RoleNames>> {
+        Box::pin(async move {
+            let roles = self.roles(guild_id).await?.models().await?;
+            Ok(roles.into_iter().map(|role| (role.id, role.name)).collect())
+        })
+    }
+
+    fn channel_guild(
+        &self,
+        channel_id: Id<ChannelMarker>,
+    ) -> BoxFuture<'_, DiscordResult<Option<Id<GuildMarker>>>> {
+        Box::pin(async move {
+            match self.channel(channel_id).await {
+                Ok(response) => Ok(response.model().await?.guild_id),
+                Err(err) if is_unknown(&err) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    fn text_channels(
+        &self,
+        guild_id: Id<GuildMarker>,
+    ) -> BoxFuture<'_, DiscordResult<Vec<Id<ChannelMarker>>>> {
+        Box::pin(async move {
+            let channels = self.guild_channels(guild_id).await?.models().await?;
+            Ok(channels
+                .into_iter()
+                .filter(|channel| is_text(channel.kind))
+                .map(|channel| channel.id)
+                .collect())
+        })
+    }
+
+    fn message_exists(
+        &self,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    ) -> BoxFuture<'_, DiscordResult<bool>> {
+        Box::pin(async move {
+            match self.message(channel_id, message_id).await {
+                Ok(_) => Ok(true),
+                Err(err) if is_unknown(&err) => Ok(false),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    fn respond_to_interaction<'a>(
+        &'a self,
+        application_id: Id<ApplicationMarker>,
+        interaction_id: Id<InteractionMarker>,
+        token: &'a str,
+        response: &'a InteractionResponse,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        Box::pin(async move {
+            self.interaction(application_id)
+                .create_response(interaction_id, token, response)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn update_interaction_response<'a>(
+        &'a self,
+        application_id: Id<ApplicationMarker>,
+        token: &'a str,
+        content: &'a str,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        Box::pin(async move {
+            let mentions = AllowedMentions::default();
+            self.interaction(application_id)
+                .update_response(token)
+                .allowed_mentions(Some(&mentions))
+                .content(Some(content))?
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    //! A [`Discord`] that records calls rather than making them.
+
+    use std::sync::{Mutex, PoisonError};
+
+    use super::*;
+
+    /// A call made to Discord, with what matters to assert on.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Call {
+        CreateMessage {
+            channel_id: Id<ChannelMarker>,
+            reply_to: Option<Id<MessageMarker>>,
+            content: Option<String>,
+            embeds: usize,
+            allowed_mentions: Option<AllowedMentions>,
+        },
+        UpdateComponents {
+            message_id: Id<MessageMarker>,
+            components: usize,
+        },
+        CreateReaction {
+            message_id: Id<MessageMarker>,
+            emoji: String,
+        },
+        DeleteOwnReaction {
+            message_id: Id<MessageMarker>,
+            emoji: String,
+        },
+        DeleteReaction {
+            message_id: Id<MessageMarker>,
+            emoji: String,
+            user_id: Id<UserMarker>,
+        },
+        AddRole {
+            user_id: Id<UserMarker>,
+            role_id: Id<RoleMarker>,
+        },
+        RemoveRole {
+            user_id: Id<UserMarker>,
+            role_id: Id<RoleMarker>,
+        },
+        SetNickname {
+            user_id: Id<UserMarker>,
+            nick: Option<String>,
+        },
+        TimeOut {
+            user_id: Id<UserMarker>,
+            until: Option<Timestamp>,
+        },
+        RespondToInteraction {
+            content: Option<String>,
+        },
+        UpdateInteractionResponse {
+            content: String,
+        },
+    }
+
+    /// Answers lookups from what it was given and records every change.
+    #[derive(Default)]
+    pub struct RecordingDiscord {
+        pub members: Vec<MemberInfo>,
+        pub roles: RoleNames,
+        /// Users who reacted, by emoji as formatted in requests.
+        pub reactions: HashMap<String, Vec<Reactor>>,
+        pub channels: HashMap<Id<ChannelMarker>, Id<GuildMarker>>,
+        /// Channel of each message.
+        pub messages: HashMap<Id<MessageMarker>, Id<ChannelMarker>>,
+        calls: Mutex<Vec<Call>>,
+    }
+
+    impl RecordingDiscord {
+        /// Calls made so far, even if a test panicked while recording one.
+        pub fn calls(&self) -> Vec<Call> {
+            self.calls
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        }
+
+        /// Contents of the messages sent so far.
+        pub fn sent(&self) -> Vec<String> {
+            self.calls()
+                .into_iter()
+                .filter_map(|call| match call {
+                    Call::CreateMessage { content, .. } => content,
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn record(&self, call: Call) -> DiscordResult<()> {
+            self.calls.lock()?.push(call);
+            Ok(())
+        }
+    }
+
+    impl Discord for RecordingDiscord {
+        fn create_message<'a>(
+            &'a self,
+            message: &'a Outgoing,
+        ) -> BoxFuture<'a, DiscordResult<Id<MessageMarker>>> {
+            Box::pin(async move {
+                let mut calls = self.calls.lock()?;
+                calls.push(Call::CreateMessage {
+                    channel_id: message.channel_id,
+                    reply_to: message.reply_to,
+                    content: message.content.clone(),
+                    embeds: message.embeds.len(),
+                    allowed_mentions: message.allowed_mentions.clone(),
+                });
+                Ok(Id::new(1_000_000 + calls.len() as u64))
+            })
+        }
+
+        fn update_components<'a>(
+            &'a self,
+            _channel_id: Id<ChannelMarker>,
+            message_id: Id<MessageMarker>,
+            components: &'a [Component],
+        ) -> BoxFuture<'a, DiscordResult<()>> {
+            Box::pin(async move {
+                self.record(Call::UpdateComponents {
+                    message_id,
+                    components: components.len(),
+                })
+            })
+        }
+
+        fn create_reaction<'a>(
+            &'a self,
+            _channel_id: Id<ChannelMarker>,
+            message_id: Id<MessageMarker>,
+            emoji: &'a RequestReactionType<'a>,
+        ) -> BoxFuture<'a, DiscordResult<()>> {
+            Box::pin(async move {
+                self.record(Call::CreateReaction {
+                    message_id,
+                    emoji: emoji.to_string(),
+                })
+            })
+        }
+
+        fn delete_own_reaction<'a>(
+            &'a self,
+            _channel_id: Id<ChannelMarker>,
+            message_id: Id<MessageMarker>,
+            emoji: &'a RequestReactionType<'a>,
+        ) -> BoxFuture<'a, DiscordResult<()>> {
+            Box::pin(async move {
+                self.record(Call::DeleteOwnReaction {
+                    message_id,
+                    emoji: emoji.to_string(),
+                })
+            })
+        }
+
+        fn delete_reaction<'a>(
+            &'a self,
+            _channel_id: Id<ChannelMarker>,
+            message_id: Id<MessageMarker>,
+            emoji: &'a RequestReactionType<'a>,
+            user_id: Id<UserMarker>,
+        ) -> BoxFuture<'a, DiscordResult<()>> {
+            Box::pin(async move {
+                self.record(Call::DeleteReaction {
+                    message_id,
+                    emoji: emoji.to_string(),
+                    user_id,
+                })
+            })
+        }
+
+        fn reactions<'a>(
+            &'a self,
+            _channel_id: Id<ChannelMarker>,
+            _message_id: Id<MessageMarker>,
+            emoji: &'a RequestReactionType<'a>,
+            after: Option<Id<UserMarker>>,
+            limit: u16,
+        ) -> BoxFuture<'a, DiscordResult<Vec<Reactor>>> {
+            Box::pin(async move {
+                let reactors = self
+                    .reactions
+                    .get(&emoji.to_string())
+                    .cloned()
+                    .unwrap_or_default();
+                Ok(page(reactors, |reactor| reactor.user_id, after, limit))
+            })
+        }
+
+        fn add_role(
+            &self,
+            _guild_id: Id<GuildMarker>,
+            user_id: Id<UserMarker>,
+            role_id: Id<RoleMarker>,
+        ) -> BoxFuture<'_, DiscordResult<()>> {
+            Box::pin(async move { self.record(Call::AddRole { user_id, role_id }) })
+        }
+
+        fn remove_role(
+            &self,
+            _guild_id: Id<GuildMarker>,
+            user_id: Id<UserMarker>,
+            role_id: Id<RoleMarker>,
+        ) -> BoxFuture<'_, DiscordResult<()>> {
+            Box::pin(async move { self.record(Call::RemoveRole { user_id, role_id }) })
+        }
+
+        fn set_nickname<'a>(
+            &'a self,
+            _guild_id: Id<GuildMarker>,
+            user_id: Id<UserMarker>,
+            nick: Option<&'a str>,
+        ) -> BoxFuture<'a, DiscordResult<()>> {
+            Box::pin(async move {
+                self.record(Call::SetNickname {
+                    user_id,
+                    nick: nick.map(str::to_owned),
+                })
+            })
+        }
+
+        fn time_out(
+            &self,
+            _guild_id: Id<GuildMarker>,
+            user_id: Id<UserMarker>,
+            until: Option<Timestamp>,
+        ) -> BoxFuture<'_, DiscordResult<()>> {
+            Box::pin(async move { self.record(Call::TimeOut { user_id, until }) })
+        }
+
+        fn guild_members(
+            &self,
+            _guild_id: Id<GuildMarker>,
+            after: Option<Id<UserMarker>>,
+            limit: u16,
+        ) -> BoxFuture<'_, DiscordResult<Vec<MemberInfo>>> {
+            Box::pin(async move {
+                Ok(page(
+                    self.members.clone(),
+                    |member| member.user_id,
+                    after,
+                    limit,
+                ))
+            })
+        }
+
+        fn roles(&self, _guild_id: Id<GuildMarker>) -> BoxFuture<'_, DiscordResult<RoleNames>> {
+            Box::pin(async move { Ok(self.roles.clone()) })
+        }
+
+        fn channel_guild(
+            &self,
+            channel_id: Id<ChannelMarker>,
+        ) -> BoxFuture<'_, DiscordResult<Option<Id<GuildMarker>>>> {
+            Box::pin(async move { Ok(self.channels.get(&channel_id).copied()) })
+        }
+
+        fn text_channels(
+            &self,
+            guild_id: Id<GuildMarker>,
+        ) -> BoxFuture<'_, DiscordResult<Vec<Id<ChannelMarker>>>> {
+            Box::pin(async move {
+                let mut channels: Vec<Id<ChannelMarker>> = self
+                    .channels
+                    .iter()
+                    .filter(|(_, guild)| **guild == guild_id)
+                    .map(|(channel_id, _)| *channel_id)
+                    .collect();
+                channels.sort();
+                Ok(channels)
+            })
+        }
+
+        fn message_exists(
+            &self,
+            channel_id: Id<ChannelMarker>,
+            message_id: Id<MessageMarker>,
+        ) -> BoxFuture<'_, DiscordResult<bool>> {
+            Box::pin(async move { Ok(self.messages.get(&message_id) == Some(&channel_id)) })
+        }
+
+        fn respond_to_interaction<'a>(
+            &'a self,
+            _application_id: Id<ApplicationMarker>,
+            _interaction_id: Id<InteractionMarker>,
+            _token: &'a str,
+            response: &'a InteractionResponse,
+        ) -> BoxFuture<'a, DiscordResult<()>> {
+            Box::pin(async move {
+                self.record(Call::RespondToInteraction {
+                    content: response.data.as_ref().and_then(|data| data.content.clone()),
+                })
+            })
+        }
+
+        fn update_interaction_response<'a>(
+            &'a self,
+            _application_id: Id<ApplicationMarker>,
+            _token: &'a str,
+            content: &'a str,
+        ) -> BoxFuture<'a, DiscordResult<()>> {
+            Box::pin(async move {
+                self.record(Call::UpdateInteractionResponse {
+                    content: content.to_owned(),
+                })
+            })
+        }
+    }
+
+    /// Paginates like Discord does: by increasing user id, after `after`.
+    fn page<T>(
+        mut items: Vec<T>,
+        user_id: impl Fn(&T) -> Id<UserMarker>,
+        after: Option<Id<UserMarker>>,
+        limit: u16,
+    ) -> Vec<T> {
+        items.sort_by_key(&user_id);
+        items
+            .into_iter()
+            .filter(|item| after.is_none_or(|after| user_id(item) > after))
+            .take(limit as usize)
+            .collect()
+    }
+}
@@ -0,0 +1,93 @@
+use std::error::Error;
+
+use super::registry::Permission;
+use crate::i18n::{Locale, Message};
+
+/// Everything that can go wrong while running a command.
+///
+/// Each variant maps to a single reply from the message catalog, so that
+/// users always get the same message for the same kind of failure.
+#[derive(Debug)]
+pub enum CommandError {
+    /// Arguments don't match the command's schema, holds its usage line.
+    BadUsage(String),
+    /// Holds the level the command requires.
+    PermissionDenied(Permission),
+    /// Holds the reply explaining what was looked for.
+    NotFound(Message),
+    Discord(Box<dyn Error + Send + Sync>),
+    Storage(String),
+}
+
+impl CommandError {
+    /// Message sent back to the user who ran the command.
+    pub fn reply(&self, locale: Locale) -> String {
+        let msg = match self {
+            Self::BadUsage(usage) => Message::new("error-bad-usage").arg("usage", usage.as_str()),
+            Self::PermissionDenied(level) => {
+                Message::new("error-permission-denied").arg("level", level.label(locale))
+            }
+            Self::NotFound(msg) => msg.clone(),
+            Self::Discord(_) => Message::new("error-discord"),
+            Self::Storage(_) => Message::new("error-storage"),
+        };
+        msg.render(locale)
+    }
+
+    /// Whether the error comes from the bot or its environment rather than
+    /// from the user.
+    pub fn is_internal(&self) -> bool {
+        matches!(self, Self::Discord(_) | Self::Storage(_))
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::BadUsage(usage) => write!(f, "bad usage, expected {}", usage),
+            Self::PermissionDenied(level) => write!(f, "permission denied, requires {:?}", level),
+            Self::NotFound(msg) => write!(f, "not found: {}", msg.key),
+            Self::Discord(err) => write!(f, "discord api error: {}", err),
+            Self::Storage(err) => write!(f, "storage error: {}", err),
+        }
+    }
+}
+
+impl Error for CommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Discord(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<twilight_http::Error> for CommandError {
+    fn from(err: twilight_http::Error) -> Self {
+        Self::Discord(Box::new(err))
+    }
+}
+
+impl From<twilight_http::response::DeserializeBodyError> for CommandError {
+    fn from(err: twilight_http::response::DeserializeBodyError) -> Self {
+        Self::Discord(Box::new(err))
+    }
+}
+
+impl From<twilight_validate::message::MessageValidationError> for CommandError {
+    fn from(err: twilight_validate::message::MessageValidationError) -> Self {
+        Self::Discord(Box::new(err))
+    }
+}
+
+impl From<twilight_validate::request::ValidationError> for CommandError {
+    fn from(err: twilight_validate::request::ValidationError) -> Self {
+        Self::Discord(Box::new(err))
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for CommandError {
+    fn from(err: std::sync::PoisonError<T>) -> Self {
+        Self::Storage(err.to_string())
+    }
+}
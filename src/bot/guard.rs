@@ -0,0 +1,501 @@
+//! Mention guard: members who mention a protected user get warned, and
+//! repeat offenders are timed out and reported to the moderators.
+//!
+//! Protected users and escalation thresholds live in the configuration,
+//! offense counters in their own file so that they survive restarts.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::channel::message::AllowedMentions;
+use twilight_model::gateway::payload::incoming::MessageCreate;
+use twilight_model::id::{
+    marker::{ChannelMarker, UserMarker},
+    Id,
+};
+use twilight_model::util::Timestamp;
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::outbox::Outgoing;
+use super::registry::{self, CommandResult, Invocation};
+use super::BotState;
+use crate::i18n::{Locale, Message};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectedUser {
+    pub user_id: Id<UserMarker>,
+    /// How the user is called in the warning.
+    pub name: String,
+    /// Replaces the warning from the message catalog. `{offender}` and
+    /// `{name}` are replaced by a mention of the offender and `name`.
+    #[serde(default)]
+    pub warning: Option<String>,
+}
+
+impl ProtectedUser {
+    /// How the user is protected, for the audit log.
+    fn describe(&self) -> String {
+        match &self.warning {
+            Some(warning) => format!("{}: {}", self.name, warning),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn warning(&self, offender: Id<UserMarker>, locale: Locale) -> String {
+        match &self.warning {
+            Some(template) => template
+                .replace("{offender}", &format!("<@{}>", offender))
+                .replace("{name}", &self.name),
+            None => Message::new("guard-warning")
+                .arg("name", self.name.as_str())
+                .render(locale),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardConfig {
+    pub protected: Vec<ProtectedUser>,
+    /// Further mentions by the same member within this delay only get a
+    /// reaction, they are neither warned about nor counted.
+    pub cooldown_secs: u64,
+    /// Offenses older than this are forgotten.
+    pub forget_after_secs: u64,
+    /// Offense from which members are timed out, 0 to never time out.
+    pub timeout_after: u32,
+    pub timeout_minutes: u32,
+    /// Offense from which moderators are notified, 0 to never notify.
+    pub notify_after: u32,
+    /// Where moderators are notified, the mod-log channel when unset.
+    pub notify_channel: Option<Id<ChannelMarker>>,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            protected: vec![ProtectedUser {
+                user_id: super::PROTECTED_USER_ID,
+                name: "Julia".to_owned(),
+                warning: None,
+            }],
+            cooldown_secs: 5 * 60,
+            forget_after_secs: 30 * 24 * 60 * 60,
+            timeout_after: 3,
+            timeout_minutes: 10,
+            notify_after: 5,
+            notify_channel: None,
+        }
+    }
+}
+
+/// What is done about an offense, each step also does the previous ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Step {
+    Warn,
+    Timeout,
+    NotifyMods,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Offense {
+    pub count: u32,
+    /// Unix time of the last counted offense, in seconds.
+    pub last: u64,
+}
+
+/// Offense counters of every member, saved as JSON.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Offenses {
+    #[serde(default)]
+    records: HashMap<Id<UserMarker>, Offense>,
+    #[serde(skip)]
+    source_file: Option<String>,
+}
+
+impl Offenses {
+    /// Loads the counters at `path`, or starts from scratch when the file
+    /// doesn't exist yet.
+    pub fn from_file(path: &str) -> anyhow::Result<Offenses> {
+        let mut offenses: Offenses = match std::fs::File::open(path) {
+            Ok(file) => serde_json::from_reader(&file)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Offenses::default(),
+            Err(err) => return Err(err.into()),
+        };
+        offenses.source_file = Some(path.to_owned());
+        Ok(offenses)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(file_name) = &self.source_file else {
+            anyhow::bail!("offenses were not loaded from a file and cannot be saved");
+        };
+        let mut file = std::fs::File::create(file_name)?;
+        let to_write = serde_json::to_string_pretty(&self)?;
+        writeln!(&mut file, "{}", to_write)?;
+        Ok(())
+    }
+
+    /// Counts an offense by `user_id` at `now` and returns what to do about
+    /// it, or `None` while the member is in cooldown.
+    pub fn record(
+        &mut self,
+        user_id: Id<UserMarker>,
+        now: u64,
+        config: &GuardConfig,
+    ) -> Option<(Step, u32)> {
+        let offense = self
+            .records
+            .entry(user_id)
+            .or_insert(Offense { count: 0, last: 0 });
+        let elapsed = now.saturating_sub(offense.last);
+        if offense.count > 0 && elapsed < config.cooldown_secs {
+            return None;
+        }
+        if elapsed >= config.forget_after_secs {
+            offense.count = 0;
+        }
+        offense.count += 1;
+        offense.last = now;
+
+        let reached = |threshold: u32| threshold != 0 && offense.count >= threshold;
+        let step = if reached(config.notify_after) {
+            Step::NotifyMods
+        } else if reached(config.timeout_after) {
+            Step::Timeout
+        } else {
+            Step::Warn
+        };
+        Some((step, offense.count))
+    }
+
+    /// Offenses counted for the user.
+    pub fn count(&self, user_id: Id<UserMarker>) -> u32 {
+        self.records
+            .get(&user_id)
+            .map_or(0, |offense| offense.count)
+    }
+
+    pub fn forgive(&mut self, user_id: Id<UserMarker>) -> bool {
+        self.records.remove(&user_id).is_some()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// The protected user `msg` pings, if any.
+///
+/// Replies only ping the author of the replied message when the mention is
+/// left on, otherwise they aren't listed in the mentions and the reply goes
+/// through.
+pub fn mentioned(msg: &MessageCreate, state: &BotState) -> Option<ProtectedUser> {
+    let config = state.config.lock().ok()?;
+    config
+        .guard
+        .protected
+        .iter()
+        .find(|protected| {
+            msg.mentions
+                .iter()
+                .any(|mention| mention.id == protected.user_id)
+        })
+        .cloned()
+}
+
+const BAN_LINE: &str = "<:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871><:ban:519852990119673871>";
+
+pub async fn on_mention(
+    msg: &MessageCreate,
+    protected: &ProtectedUser,
+    http: &dyn Discord,
+    state: &BotState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let emoji = RequestReactionType::Custom {
+        id: super::BAN_EMOJI_ID,
+        name: Some("ban"),
+    };
+    http.create_reaction(msg.channel_id, msg.id, &emoji).await?;
+
+    let (config, mod_log_channel) = {
+        let config = state.config.lock().map_err(CommandError::from)?;
+        (config.guard.clone(), config.mod_log_channel)
+    };
+    let recorded = {
+        let mut offenses = state.offenses.lock().map_err(CommandError::from)?;
+        let recorded = offenses.record(msg.author.id, now(), &config);
+        if let Err(err) = offenses.save() {
+            tracing::warn!(%err, "failed to save offenses");
+        }
+        recorded
+    };
+    let Some((step, count)) = recorded else {
+        tracing::info!(offender = %msg.author.id, "mention in cooldown");
+        return Ok(());
+    };
+    let record = AuditRecord::new(
+        Action::MentionGuard,
+        Some(msg.author.id),
+        format!("<@{}>", protected.user_id),
+    )
+    .after(format!("{:?} ({})", step, count).to_lowercase());
+    audit::log(http, state, record).await;
+
+    let locale = state.locale(msg.guild_id);
+    let mut warning = format!(
+        "{}\n{}\n{}",
+        BAN_LINE,
+        protected.warning(msg.author.id, locale),
+        BAN_LINE
+    );
+
+    if step >= Step::Timeout {
+        if let Some(guild_id) = msg.guild_id {
+            let until = Timestamp::from_secs((now() + 60 * config.timeout_minutes as u64) as i64)?;
+            match http.time_out(guild_id, msg.author.id, Some(until)).await {
+                Ok(_) => {
+                    warning.push('\n');
+                    warning.push_str(
+                        &Message::new("guard-timeout")
+                            .arg("minutes", config.timeout_minutes as usize)
+                            .render(locale),
+                    );
+                }
+                Err(err) => tracing::warn!(%err, offender = %msg.author.id, "failed to time out"),
+            }
+        }
+    }
+
+    Outgoing::new(msg.channel_id)
+        .reply(msg.id)
+        .content(warning)
+        .send(http)
+        .await?;
+
+    if step >= Step::NotifyMods {
+        match config.notify_channel.or(mod_log_channel) {
+            Some(channel_id) => {
+                let link = match msg.guild_id {
+                    Some(guild_id) => format!(
+                        "https://discord.com/channels/{}/{}/{}",
+                        guild_id, msg.channel_id, msg.id
+                    ),
+                    None => String::new(),
+                };
+                let notice = Message::new("guard-notify")
+                    .arg("offender", format!("<@{}>", msg.author.id))
+                    .arg("name", protected.name.as_str())
+                    .arg("count", count as usize)
+                    .arg("link", link)
+                    .render(locale);
+                Outgoing::new(channel_id)
+                    .allowed_mentions(AllowedMentions::default())
+                    .content(notice)
+                    .send(http)
+                    .await?;
+            }
+            None => tracing::warn!("no channel to notify moderators in"),
+        }
+    }
+    Ok(())
+}
+
+fn parse_user(inv: &Invocation, arg: &str) -> Result<Id<UserMarker>, CommandError> {
+    registry::parse_user(arg).ok_or_else(|| inv.bad_usage())
+}
+
+pub async fn list(inv: Invocation) -> CommandResult {
+    let config = inv.state.config.lock()?.guard.clone();
+    if config.protected.is_empty() {
+        let response = Message::new("guard-list-empty").render(inv.locale);
+        return reply_in_chann(&*inv.http, &inv.msg, &response).await;
+    }
+    let mut response = Message::new("guard-list-header").render(inv.locale);
+    for protected in &config.protected {
+        response.push_str(&format!(
+            "\n- <@{}> ({})",
+            protected.user_id, protected.name
+        ));
+    }
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn protect(inv: Invocation) -> CommandResult {
+    let user_id = parse_user(&inv, &inv.args[0])?;
+    let protected = ProtectedUser {
+        user_id,
+        name: inv.args[1].clone(),
+        warning: inv.args.get(2).cloned(),
+    };
+    let after = protected.describe();
+    let before = inv.state.update_config(|config| {
+        let users = &mut config.guard.protected;
+        let before = users
+            .iter()
+            .find(|user| user.user_id == user_id)
+            .map(ProtectedUser::describe);
+        users.retain(|user| user.user_id != user_id);
+        users.push(protected);
+        before
+    })?;
+    let mut record = AuditRecord::new(
+        Action::GuardSettings,
+        Some(inv.msg.author.id),
+        format!("<@{}>", user_id),
+    )
+    .after(after);
+    if let Some(before) = before {
+        record = record.before(before);
+    }
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let response = Message::new("guard-protected").arg("user", format!("<@{}>", user_id));
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+pub async fn unprotect(inv: Invocation) -> CommandResult {
+    let user_id = parse_user(&inv, &inv.args[0])?;
+    let removed = inv.state.update_config(|config| {
+        let users = &mut config.guard.protected;
+        let removed = users
+            .iter()
+            .find(|user| user.user_id == user_id)
+            .map(ProtectedUser::describe);
+        users.retain(|user| user.user_id != user_id);
+        removed
+    })?;
+    if let Some(before) = &removed {
+        let record = AuditRecord::new(
+            Action::GuardSettings,
+            Some(inv.msg.author.id),
+            format!("<@{}>", user_id),
+        )
+        .before(before.clone());
+        audit::log(&*inv.http, &inv.state, record).await;
+    }
+    let key = if removed.is_some() {
+        "guard-unprotected"
+    } else {
+        "guard-not-protected"
+    };
+    let response = Message::new(key).arg("user", format!("<@{}>", user_id));
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+pub async fn forgive(inv: Invocation) -> CommandResult {
+    let user_id = parse_user(&inv, &inv.args[0])?;
+    let count = {
+        let mut offenses = inv.state.offenses.lock()?;
+        let count = offenses.count(user_id);
+        offenses.forgive(user_id);
+        offenses
+            .save()
+            .map_err(|err| CommandError::Storage(err.to_string()))?;
+        count
+    };
+    let record = AuditRecord::new(
+        Action::GuardSettings,
+        Some(inv.msg.author.id),
+        format!("<@{}>", user_id),
+    )
+    .before(format!("offenses: {}", count))
+    .after("offenses: 0");
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("guard-forgiven").arg("user", format!("<@{}>", user_id));
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> GuardConfig {
+        GuardConfig {
+            cooldown_secs: 60,
+            forget_after_secs: 1000,
+            timeout_after: 2,
+            notify_after: 3,
+            ..GuardConfig::default()
+        }
+    }
+
+    #[test]
+    fn escalates_past_cooldown() {
+        let config = config();
+        let mut offenses = Offenses::default();
+        let user = Id::new(1);
+        assert_eq!(offenses.record(user, 100, &config), Some((Step::Warn, 1)));
+        assert_eq!(offenses.record(user, 130, &config), None);
+        assert_eq!(
+            offenses.record(user, 160, &config),
+            Some((Step::Timeout, 2))
+        );
+        assert_eq!(
+            offenses.record(user, 220, &config),
+            Some((Step::NotifyMods, 3))
+        );
+        assert_eq!(
+            offenses.record(Id::new(2), 220, &config),
+            Some((Step::Warn, 1))
+        );
+    }
+
+    #[test]
+    fn old_offenses_are_forgotten() {
+        let config = config();
+        let mut offenses = Offenses::default();
+        let user = Id::new(1);
+        offenses.record(user, 100, &config);
+        offenses.record(user, 200, &config);
+        assert_eq!(offenses.record(user, 2000, &config), Some((Step::Warn, 1)));
+        assert_eq!(offenses.count(user), 1);
+        assert!(offenses.forgive(user));
+        assert_eq!(offenses.count(user), 0);
+        assert!(!offenses.forgive(user));
+    }
+
+    #[test]
+    fn disabled_steps_are_skipped() {
+        let config = GuardConfig {
+            cooldown_secs: 0,
+            timeout_after: 0,
+            notify_after: 0,
+            ..GuardConfig::default()
+        };
+        let mut offenses = Offenses::default();
+        for now in 0..5 {
+            assert_eq!(
+                offenses
+                    .record(Id::new(1), now, &config)
+                    .map(|(step, _)| step),
+                Some(Step::Warn)
+            );
+        }
+    }
+
+    #[test]
+    fn warning_templates() {
+        let mut protected = ProtectedUser {
+            user_id: Id::new(1),
+            name: "Julia".to_owned(),
+            warning: None,
+        };
+        assert!(protected.warning(Id::new(2), Locale::Fr).contains("Julia"));
+        protected.warning = Some("{offender}, laisse {name} tranquille".to_owned());
+        assert_eq!(
+            protected.warning(Id::new(2), Locale::Fr),
+            "<@2>, laisse Julia tranquille"
+        );
+    }
+}
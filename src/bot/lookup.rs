@@ -0,0 +1,179 @@
+//! Member, role and channel lookups.
+//!
+//! They are answered from the in-memory cache when it holds what is needed
+//! and fall back to the HTTP API otherwise. The cache only keeps the
+//! resources listed in [`RESOURCE_TYPES`].
+
+use twilight_cache_inmemory::{InMemoryCache, ResourceType};
+use twilight_model::guild::Member;
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, MessageMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use super::discord::{self, Discord, RoleNames};
+use super::error::CommandError;
+use super::BotState;
+
+/// Resources kept in the cache: guilds for their owner and member count,
+/// roles for their names and permissions, members and users for nicknames
+/// and roles, and channels.
+pub const RESOURCE_TYPES: ResourceType = ResourceType::GUILD
+    .union(ResourceType::ROLE)
+    .union(ResourceType::MEMBER)
+    .union(ResourceType::USER)
+    .union(ResourceType::CHANNEL);
+
+/// What the bot needs to know about a member, wherever it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberInfo {
+    pub user_id: Id<UserMarker>,
+    pub name: String,
+    pub bot: bool,
+    pub nick: Option<String>,
+    pub roles: Vec<Id<RoleMarker>>,
+}
+
+impl From<Member> for MemberInfo {
+    fn from(member: Member) -> Self {
+        Self {
+            user_id: member.user.id,
+            name: member.user.name,
+            bot: member.user.bot,
+            nick: member.nick,
+            roles: member.roles,
+        }
+    }
+}
+
+/// Every member of the guild, as long as the cache holds all of them.
+fn cached_members(cache: &InMemoryCache, guild_id: Id<GuildMarker>) -> Option<Vec<MemberInfo>> {
+    let expected = cache.guild(guild_id)?.member_count()?;
+    let user_ids: Vec<Id<UserMarker>> = cache.guild_members(guild_id)?.iter().copied().collect();
+    if (user_ids.len() as u64) < expected {
+        return None;
+    }
+    user_ids
+        .into_iter()
+        .map(|user_id| {
+            let member = cache.member(guild_id, user_id)?;
+            let user = cache.user(user_id)?;
+            Some(MemberInfo {
+                user_id,
+                name: user.name.clone(),
+                bot: user.bot,
+                nick: member.nick().map(str::to_owned),
+                roles: member.roles().to_vec(),
+            })
+        })
+        .collect()
+}
+
+const MEMBERS_PAGE: u16 = 1000;
+
+/// Fetches every member of the guild, one page at a time.
+async fn fetch_all_members(
+    http: &dyn Discord,
+    guild_id: Id<GuildMarker>,
+) -> Result<Vec<MemberInfo>, CommandError> {
+    let mut members = Vec::new();
+    let mut after = None;
+    loop {
+        let page = http.guild_members(guild_id, after, MEMBERS_PAGE).await?;
+        let page_len = page.len();
+        after = page.last().map(|member| member.user_id);
+        members.extend(page);
+        if page_len < MEMBERS_PAGE as usize {
+            return Ok(members);
+        }
+    }
+}
+
+pub async fn members(
+    http: &dyn Discord,
+    state: &BotState,
+    guild_id: Id<GuildMarker>,
+) -> Result<Vec<MemberInfo>, CommandError> {
+    if let Some(members) = cached_members(&state.cache, guild_id) {
+        return Ok(members);
+    }
+    tracing::debug!(%guild_id, "members not all cached, fetching them");
+    fetch_all_members(http, guild_id).await
+}
+
+pub async fn role_names(
+    http: &dyn Discord,
+    state: &BotState,
+    guild_id: Id<GuildMarker>,
+) -> Result<RoleNames, CommandError> {
+    let cached: Option<RoleNames> = state.cache.guild_roles(guild_id).map(|role_ids| {
+        role_ids
+            .iter()
+            .filter_map(|role_id| {
+                let role = state.cache.role(*role_id)?;
+                Some((*role_id, role.resource().name.clone()))
+            })
+            .collect()
+    });
+    if let Some(names) = cached.filter(|names| !names.is_empty()) {
+        return Ok(names);
+    }
+    http.roles(guild_id).await
+}
+
+/// Guild of a channel, none when the channel doesn't exist or belongs to no
+/// guild.
+pub async fn channel_guild(
+    http: &dyn Discord,
+    state: &BotState,
+    channel_id: Id<ChannelMarker>,
+) -> Result<Option<Id<GuildMarker>>, CommandError> {
+    if let Some(channel) = state.cache.channel(channel_id) {
+        return Ok(channel.guild_id);
+    }
+    http.channel_guild(channel_id).await
+}
+
+/// Channels of the guild that hold messages.
+async fn text_channels(
+    http: &dyn Discord,
+    state: &BotState,
+    guild_id: Id<GuildMarker>,
+) -> Result<Vec<Id<ChannelMarker>>, CommandError> {
+    let cached: Option<Vec<Id<ChannelMarker>>> =
+        state.cache.guild_channels(guild_id).map(|channel_ids| {
+            channel_ids
+                .iter()
+                .copied()
+                .filter(|channel_id| {
+                    state
+                        .cache
+                        .channel(*channel_id)
+                        .is_some_and(|channel| discord::is_text(channel.kind))
+                })
+                .collect()
+        });
+    if let Some(channels) = cached.filter(|channels| !channels.is_empty()) {
+        return Ok(channels);
+    }
+    http.text_channels(guild_id).await
+}
+
+/// Channel holding a message of the guild, found by looking for it in every
+/// channel. None when no channel has it.
+pub async fn message_channel(
+    http: &dyn Discord,
+    state: &BotState,
+    guild_id: Id<GuildMarker>,
+    message_id: Id<MessageMarker>,
+) -> Result<Option<Id<ChannelMarker>>, CommandError> {
+    for channel_id in text_channels(http, state, guild_id).await? {
+        match http.message_exists(channel_id, message_id).await {
+            Ok(true) => return Ok(Some(channel_id)),
+            Ok(false) => {}
+            // Channels the bot can't read.
+            Err(err) => tracing::debug!(%err, channel = %channel_id, "skipped channel"),
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,447 @@
+//! Nickname moderation.
+//!
+//! `reset_nick` takes patterns rather than exact nicknames: globs with `*`
+//! and `?`, `contains:` substrings or `re:` regular expressions. It can
+//! preview who would be affected, and asks for a confirmation before
+//! resetting many nicknames at once.
+//!
+//! The nickname policy is enforced on members who join or change their
+//! nickname: banned words get the nickname reset, stray Unicode, hoisting
+//! characters and extra length are cleaned up.
+
+use std::time::Instant;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{
+    marker::{GuildMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::confirm::{self, Pending};
+use super::discord::Discord;
+use super::error::CommandError;
+use super::lookup::{self, MemberInfo};
+use super::permissions;
+use super::registry::{self, CommandResult, Invocation, Permission};
+use super::BotState;
+use crate::i18n::Message;
+
+/// Only lists who would be affected.
+const DRY_RUN: &str = "--dry-run";
+
+/// Resetting more nicknames than this needs a confirmation.
+const CONFIRM_THRESHOLD: usize = 5;
+
+/// Members listed in a preview, the others are only counted.
+const PREVIEW_LIMIT: usize = 20;
+
+#[derive(Debug)]
+pub enum Pattern {
+    Exact(String),
+    /// Lowercase substring, matched case-insensitively.
+    Contains(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn parse(arg: &str) -> Result<Self, regex::Error> {
+        if let Some(re) = arg
+            .strip_prefix("re:")
+            .or_else(|| arg.strip_prefix("regex:"))
+        {
+            return Ok(Self::Regex(Regex::new(re)?));
+        }
+        if let Some(text) = arg
+            .strip_prefix("contains:")
+            .or_else(|| arg.strip_prefix("contient:"))
+        {
+            return Ok(Self::Contains(text.to_lowercase()));
+        }
+        if arg.contains(['*', '?']) {
+            return Ok(Self::Regex(glob_to_regex(arg)?));
+        }
+        Ok(Self::Exact(arg.to_owned()))
+    }
+
+    pub fn matches(&self, nick: &str) -> bool {
+        match self {
+            Self::Exact(expected) => nick == expected,
+            Self::Contains(text) => nick.to_lowercase().contains(text),
+            Self::Regex(re) => re.is_match(nick),
+        }
+    }
+}
+
+/// Globs match the whole nickname, regardless of case.
+fn glob_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    RegexBuilder::new(&re).case_insensitive(true).build()
+}
+
+/// Most combining marks kept on a single character, more is zalgo.
+const MAX_COMBINING: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NicknamePolicy {
+    pub enabled: bool,
+    /// Nicknames containing one of these words are reset, regardless of
+    /// case and accents.
+    pub banned_words: Vec<String>,
+    /// Removes invisible characters and stacked combining marks.
+    pub strip_unicode: bool,
+    /// Removed from the start of nicknames, so that they don't get sorted at
+    /// the top of the member list.
+    pub hoisting_chars: String,
+    /// Longer nicknames are cut.
+    pub max_length: Option<usize>,
+    /// Members from this level on are left alone.
+    pub exempt: Permission,
+}
+
+impl Default for NicknamePolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            banned_words: Vec::new(),
+            strip_unicode: true,
+            hoisting_chars: "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~".to_owned(),
+            max_length: None,
+            exempt: Permission::Moderator,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Allowed,
+    /// The nickname is replaced by a cleaned up one.
+    Normalize(String),
+    Reset,
+}
+
+impl NicknamePolicy {
+    pub fn check(&self, nick: &str) -> Verdict {
+        let mut fixed = if self.strip_unicode {
+            strip_unicode(nick)
+        } else {
+            nick.to_owned()
+        };
+        fixed = fixed
+            .trim_start_matches(|c: char| c.is_whitespace() || self.hoisting_chars.contains(c))
+            .to_owned();
+        let folded = deunicode::deunicode(&fixed).to_lowercase();
+        let banned = self
+            .banned_words
+            .iter()
+            .map(|word| deunicode::deunicode(word).to_lowercase())
+            .any(|word| !word.is_empty() && folded.contains(&word));
+        if let Some(max_length) = self.max_length {
+            if fixed.chars().count() > max_length {
+                fixed = fixed.chars().take(max_length).collect::<String>();
+                fixed.truncate(fixed.trim_end().len());
+            }
+        }
+        if banned || fixed.trim().is_empty() {
+            Verdict::Reset
+        } else if fixed == nick {
+            Verdict::Allowed
+        } else {
+            Verdict::Normalize(fixed)
+        }
+    }
+}
+
+fn strip_unicode(nick: &str) -> String {
+    let mut stripped = String::with_capacity(nick.len());
+    let mut combining = 0;
+    for c in nick.chars() {
+        if is_invisible(c) {
+            continue;
+        }
+        if is_combining(c) {
+            combining += 1;
+            if combining > MAX_COMBINING {
+                continue;
+            }
+        } else {
+            combining = 0;
+        }
+        stripped.push(c);
+    }
+    stripped
+}
+
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{115F}'
+            | '\u{1160}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{3164}'
+            | '\u{FEFF}'
+            | '\u{FFA0}'
+    )
+}
+
+fn is_combining(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Resets the nickname of the member, or replaces it with `nick`.
+async fn member_reset_nickname(
+    http: &dyn Discord,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    nick: Option<&str>,
+) -> CommandResult {
+    http.set_nickname(guild_id, user_id, nick).await
+}
+
+/// Lists members as `name (nick)`, up to [`PREVIEW_LIMIT`] of them.
+fn preview(inv: &Invocation, members: &[MemberInfo]) -> String {
+    let mut lines: Vec<String> = members
+        .iter()
+        .take(PREVIEW_LIMIT)
+        .map(|member| {
+            format!(
+                "- {} ({})",
+                member.name,
+                member.nick.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+    if members.len() > PREVIEW_LIMIT {
+        lines.push(
+            Message::new("nick-preview-more")
+                .arg("count", members.len() - PREVIEW_LIMIT)
+                .render(inv.locale),
+        );
+    }
+    lines.join("\n")
+}
+
+pub async fn reset_nick(inv: Invocation) -> CommandResult {
+    let Some(guild_id) = inv.msg.guild_id else {
+        return Err(CommandError::NotFound(Message::new("error-no-guild")));
+    };
+    let dry_run = inv.args.iter().any(|arg| arg == DRY_RUN);
+    let patterns = inv
+        .args
+        .iter()
+        .filter(|arg| *arg != DRY_RUN)
+        .map(|arg| {
+            Pattern::parse(arg).map_err(|_| {
+                CommandError::NotFound(
+                    Message::new("nick-bad-pattern").arg("pattern", arg.as_str()),
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if patterns.is_empty() {
+        return Err(inv.bad_usage());
+    }
+
+    let members: Vec<MemberInfo> = lookup::members(&*inv.http, &inv.state, guild_id)
+        .await?
+        .into_iter()
+        .filter(|member| {
+            matches!(&member.nick, Some(nick) if patterns.iter().any(|pattern| pattern.matches(nick)))
+        })
+        .collect();
+
+    if members.is_empty() {
+        let response = Message::new("nick-none-matched").render(inv.locale);
+        return reply_in_chann(&*inv.http, &inv.msg, &response).await;
+    }
+    if dry_run {
+        let response = format!(
+            "{}\n{}",
+            Message::new("nick-dry-run")
+                .arg("count", members.len())
+                .render(inv.locale),
+            preview(&inv, &members)
+        );
+        return reply_in_chann(&*inv.http, &inv.msg, &response).await;
+    }
+    if members.len() > CONFIRM_THRESHOLD {
+        let response = format!(
+            "{}\n{}",
+            Message::new("nick-confirm")
+                .arg("count", members.len())
+                .arg("confirm", format!("{}confirm", registry::PREFIX))
+                .arg("cancel", format!("{}cancel", registry::PREFIX))
+                .arg("minutes", (confirm::TIMEOUT.as_secs() / 60) as usize)
+                .render(inv.locale),
+            preview(&inv, &members)
+        );
+        inv.state.confirmations.lock()?.ask(
+            inv.msg.author.id,
+            Pending::ResetNick { guild_id, members },
+            Instant::now(),
+        );
+        return reply_in_chann(&*inv.http, &inv.msg, &response).await;
+    }
+    apply_reset(&inv, guild_id, &members).await
+}
+
+/// Resets the nickname of `members` and reports which ones changed.
+pub async fn apply_reset(
+    inv: &Invocation,
+    guild_id: Id<GuildMarker>,
+    members: &[MemberInfo],
+) -> CommandResult {
+    let (msg, http) = (&inv.msg, &*inv.http);
+    let mut changed_str: String = String::new();
+    let mut failed_str: String = String::new();
+    for member in members {
+        match member_reset_nickname(http, guild_id, member.user_id, None).await {
+            Ok(_) => {
+                changed_str.push_str(&format!(" {}", member.name));
+                let mut record = AuditRecord::new(
+                    Action::NicknameReset,
+                    Some(msg.author.id),
+                    format!("<@{}>", member.user_id),
+                );
+                record.before = member.nick.clone();
+                audit::log(http, &inv.state, record).await;
+            }
+            Err(err) => {
+                failed_str.push_str(&format!(" {}", member.name));
+                tracing::warn!(%err, target_user = %member.user_id, "failed to change nickname");
+            }
+        }
+    }
+    let mut response = Message::new("nick-reset-done")
+        .arg("changed", changed_str)
+        .render(inv.locale);
+    if !failed_str.is_empty() {
+        response.push('\n');
+        response.push_str(
+            &Message::new("nick-reset-failed")
+                .arg("failed", failed_str)
+                .render(inv.locale),
+        );
+    }
+
+    reply_in_chann(http, msg, &response).await
+}
+
+/// Enforces the nickname policy on a member who joined or was updated.
+pub async fn on_member(
+    http: &dyn Discord,
+    state: &BotState,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    nick: Option<&str>,
+    roles: &[Id<RoleMarker>],
+) -> CommandResult {
+    let Some(nick) = nick else {
+        return Ok(());
+    };
+    let policy = state.config.lock()?.nickname_policy.clone();
+    if !policy.enabled || permissions::level(state, guild_id, user_id, roles) >= policy.exempt {
+        return Ok(());
+    }
+    let new_nick = match policy.check(nick) {
+        Verdict::Allowed => return Ok(()),
+        Verdict::Normalize(fixed) => Some(fixed),
+        Verdict::Reset => None,
+    };
+    tracing::info!(%user_id, nick, ?new_nick, "nickname breaks the policy");
+    member_reset_nickname(http, guild_id, user_id, new_nick.as_deref()).await?;
+    let mut record =
+        AuditRecord::new(Action::NicknameReset, None, format!("<@{}>", user_id)).before(nick);
+    record.after = new_nick;
+    audit::log(http, state, record).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_contains_and_regex() {
+        let exact = Pattern::parse("Bob").unwrap();
+        assert!(exact.matches("Bob"));
+        assert!(!exact.matches("bob"));
+        assert!(!exact.matches("Bobby"));
+
+        let contains = Pattern::parse("contains:BoB").unwrap();
+        assert!(contains.matches("le gros bob"));
+        assert!(!contains.matches("bo b"));
+
+        let re = Pattern::parse("re:^[0-9]+$").unwrap();
+        assert!(re.matches("1234"));
+        assert!(!re.matches("a1234"));
+
+        assert!(Pattern::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn glob_matches_whole_nickname() {
+        let glob = Pattern::parse("bob*").unwrap();
+        assert!(glob.matches("Bobby"));
+        assert!(glob.matches("bob"));
+        assert!(!glob.matches("le bob"));
+
+        let glob = Pattern::parse("b?b.").unwrap();
+        assert!(glob.matches("bob."));
+        assert!(!glob.matches("bobx"));
+    }
+
+    #[test]
+    fn policy_cleans_up_nicknames() {
+        let policy = NicknamePolicy {
+            enabled: true,
+            banned_words: vec!["Vilain".to_owned()],
+            max_length: Some(8),
+            ..NicknamePolicy::default()
+        };
+        assert_eq!(policy.check("Julia"), Verdict::Allowed);
+        assert_eq!(
+            policy.check("!!  Julia"),
+            Verdict::Normalize("Julia".to_owned())
+        );
+        assert_eq!(
+            policy.check("Ju\u{200B}lia"),
+            Verdict::Normalize("Julia".to_owned())
+        );
+        assert_eq!(
+            policy.check("Z\u{0301}\u{0302}\u{0303}\u{0304}a"),
+            Verdict::Normalize("Z\u{0301}\u{0302}a".to_owned())
+        );
+        assert_eq!(
+            policy.check("Julia la grande"),
+            Verdict::Normalize("Julia la".to_owned())
+        );
+        assert_eq!(policy.check("le vïlain"), Verdict::Reset);
+        assert_eq!(policy.check("!!!"), Verdict::Reset);
+        assert_eq!(policy.check("\u{3164}"), Verdict::Reset);
+    }
+}
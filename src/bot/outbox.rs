@@ -0,0 +1,196 @@
+//! Outgoing messages.
+//!
+//! Messages to a channel are sent one after the other, in the order they
+//! were queued. Requests Discord rate limits are retried once the delay it
+//! asks for has passed, server errors and connection failures after an
+//! exponential backoff. Other failures are logged with the message they
+//! were about, then returned to the caller.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use twilight_http::api_error::ApiError;
+use twilight_http::error::ErrorType;
+use twilight_model::channel::message::{AllowedMentions, Embed};
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker},
+    Id,
+};
+
+use super::discord::Discord;
+use super::error::CommandError;
+
+/// Attempts made before giving up on a message.
+const MAX_ATTEMPTS: u32 = 5;
+/// First backoff delay, doubled at each attempt.
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+/// Longer rate limits aren't waited for.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Idle channel queues are dropped from this many on.
+const PRUNE_AT: usize = 100;
+
+/// How a failed attempt is dealt with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Failure {
+    /// Discord asked to wait this long.
+    RateLimited(Duration),
+    /// Server error or connection failure.
+    Transient,
+    Permanent,
+}
+
+fn classify(err: &CommandError) -> Failure {
+    let CommandError::Discord(source) = err else {
+        return Failure::Permanent;
+    };
+    let Some(err) = source.downcast_ref::<twilight_http::Error>() else {
+        return Failure::Permanent;
+    };
+    match err.kind() {
+        ErrorType::Response {
+            error: ApiError::Ratelimited(ratelimited),
+            ..
+        } => Failure::RateLimited(Duration::from_secs_f64(ratelimited.retry_after.max(0.0))),
+        ErrorType::Response { status, .. } if status.is_server_error() => Failure::Transient,
+        // Error responses whose body isn't JSON come from proxies in front of
+        // Discord, not from the API itself.
+        ErrorType::ServiceUnavailable { .. } | ErrorType::Parsing { .. } => Failure::Transient,
+        // Only connection failures: requests that timed out may have been
+        // delivered, retrying them could post the message twice.
+        ErrorType::RequestError => Failure::Transient,
+        _ => Failure::Permanent,
+    }
+}
+
+/// Delay before attempt `attempt + 1`, none to give up.
+fn retry_delay(failure: Failure, attempt: u32) -> Option<Duration> {
+    if attempt >= MAX_ATTEMPTS {
+        return None;
+    }
+    match failure {
+        Failure::RateLimited(delay) if delay <= MAX_RETRY_AFTER => Some(delay),
+        Failure::RateLimited(_) | Failure::Permanent => None,
+        Failure::Transient => Some(BACKOFF_BASE * 2u32.pow(attempt - 1)),
+    }
+}
+
+/// Whoever holds the lock of a channel queue is the one sending to it.
+type Queue = Arc<tokio::sync::Mutex<()>>;
+
+fn channel_queue(channel_id: Id<ChannelMarker>) -> Queue {
+    static QUEUES: OnceLock<Mutex<HashMap<Id<ChannelMarker>, Queue>>> = OnceLock::new();
+    let mut queues = match QUEUES.get_or_init(Mutex::default).lock() {
+        Ok(queues) => queues,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if queues.len() >= PRUNE_AT {
+        queues.retain(|_, queue| Arc::strong_count(queue) > 1);
+    }
+    Arc::clone(queues.entry(channel_id).or_default())
+}
+
+/// A message waiting to be sent.
+#[derive(Debug, Clone)]
+pub struct Outgoing {
+    pub(super) channel_id: Id<ChannelMarker>,
+    pub(super) content: Option<String>,
+    pub(super) embeds: Vec<Embed>,
+    pub(super) reply_to: Option<Id<MessageMarker>>,
+    pub(super) allowed_mentions: Option<AllowedMentions>,
+}
+
+impl Outgoing {
+    pub fn new(channel_id: Id<ChannelMarker>) -> Self {
+        Self {
+            channel_id,
+            content: None,
+            embeds: Vec::new(),
+            reply_to: None,
+            allowed_mentions: None,
+        }
+    }
+
+    pub fn content(mut self, content: impl Into<String>) -> Self {
+        self.content = Some(content.into());
+        self
+    }
+
+    pub fn embeds(mut self, embeds: Vec<Embed>) -> Self {
+        self.embeds = embeds;
+        self
+    }
+
+    pub fn reply(mut self, message_id: Id<MessageMarker>) -> Self {
+        self.reply_to = Some(message_id);
+        self
+    }
+
+    pub fn allowed_mentions(mut self, mentions: AllowedMentions) -> Self {
+        self.allowed_mentions = Some(mentions);
+        self
+    }
+
+    /// Waits for the messages queued before in the channel, then sends this
+    /// one. Returns the id of the posted message.
+    pub async fn send(self, http: &dyn Discord) -> Result<Id<MessageMarker>, CommandError> {
+        let queue = channel_queue(self.channel_id);
+        let _turn = queue.lock().await;
+        let mut attempt = 1;
+        loop {
+            let err = match http.create_message(&self).await {
+                Ok(message) => return Ok(message),
+                Err(err) => err,
+            };
+            let failure = classify(&err);
+            match retry_delay(failure, attempt) {
+                Some(delay) => {
+                    tracing::debug!(%err, channel = %self.channel_id, attempt, ?delay, "retrying message");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => {
+                    tracing::error!(
+                        %err,
+                        channel = %self.channel_id,
+                        reply_to = ?self.reply_to,
+                        attempt,
+                        ?failure,
+                        "failed to send message"
+                    );
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_until_attempts_run_out() {
+        assert_eq!(retry_delay(Failure::Transient, 1), Some(BACKOFF_BASE));
+        assert_eq!(retry_delay(Failure::Transient, 3), Some(BACKOFF_BASE * 4));
+        assert_eq!(retry_delay(Failure::Transient, MAX_ATTEMPTS), None);
+
+        let wait = Duration::from_millis(1500);
+        assert_eq!(retry_delay(Failure::RateLimited(wait), 2), Some(wait));
+        assert_eq!(
+            retry_delay(Failure::RateLimited(MAX_RETRY_AFTER * 2), 1),
+            None
+        );
+        assert_eq!(retry_delay(Failure::Permanent, 1), None);
+    }
+
+    #[test]
+    fn only_discord_errors_are_retried() {
+        assert_eq!(
+            classify(&CommandError::Storage("disk full".to_owned())),
+            Failure::Permanent
+        );
+        let invalid = twilight_validate::message::content("a".repeat(3000)).unwrap_err();
+        assert_eq!(classify(&invalid.into()), Failure::Permanent);
+    }
+}
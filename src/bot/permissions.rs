@@ -0,0 +1,291 @@
+//! Permission levels.
+//!
+//! Every command requires a level, its default one from the registry unless
+//! it is overridden in the configuration. Members get the highest level of
+//! their configured roles and of their own user. The guild owner and roles
+//! with the Administrator permission are always admins: role permissions are
+//! read from the cache, which follows role creations and updates from the
+//! gateway.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use twilight_model::gateway::payload::incoming::MessageCreate;
+use twilight_model::guild::Permissions;
+use twilight_model::id::{
+    marker::{GuildMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::error::CommandError;
+use super::registry::{self, CommandResult, Invocation, Permission};
+use super::BotState;
+use crate::i18n::{Locale, Message};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionConfig {
+    pub roles: HashMap<Id<RoleMarker>, Permission>,
+    pub users: HashMap<Id<UserMarker>, Permission>,
+    /// Overrides the level of commands, by full name such as `rr add`.
+    pub commands: HashMap<String, Permission>,
+}
+
+impl PermissionConfig {
+    /// Level of a member holding `roles`, `admin` tells whether they are an
+    /// admin on Discord's side.
+    pub fn level_of(
+        &self,
+        user_id: Id<UserMarker>,
+        roles: &[Id<RoleMarker>],
+        admin: bool,
+    ) -> Permission {
+        if admin {
+            return Permission::Admin;
+        }
+        roles
+            .iter()
+            .filter_map(|role_id| self.roles.get(role_id))
+            .chain(self.users.get(&user_id))
+            .copied()
+            .max()
+            .unwrap_or(Permission::Everyone)
+    }
+
+    /// Level required to run the command at `path`, whose default is
+    /// `default`.
+    pub fn required(&self, path: &str, default: Permission) -> Permission {
+        if is_locked(path) {
+            return default;
+        }
+        self.commands.get(path).copied().unwrap_or(default)
+    }
+}
+
+/// Managing permissions stays at its default level so that it can't be
+/// handed out by mistake.
+fn is_locked(path: &str) -> bool {
+    path == "perm" || path.starts_with("perm ")
+}
+
+/// Whether Discord makes the member an admin of the guild: they own it or
+/// hold a role with the Administrator permission.
+fn is_discord_admin(
+    state: &BotState,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    roles: &[Id<RoleMarker>],
+) -> bool {
+    if matches!(state.cache.guild(guild_id), Some(guild) if guild.owner_id() == user_id) {
+        return true;
+    }
+    roles.iter().any(|role_id| {
+        state.cache.role(*role_id).is_some_and(|role| {
+            role.resource()
+                .permissions
+                .contains(Permissions::ADMINISTRATOR)
+        })
+    })
+}
+
+pub fn level(
+    state: &BotState,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    roles: &[Id<RoleMarker>],
+) -> Permission {
+    let admin = is_discord_admin(state, guild_id, user_id, roles);
+    match state.config.lock() {
+        Ok(config) => config.permissions.level_of(user_id, roles, admin),
+        Err(_) if admin => Permission::Admin,
+        Err(_) => Permission::Everyone,
+    }
+}
+
+/// Level of the author of `msg`. Direct messages only get the lowest level.
+pub fn of_message(msg: &MessageCreate, state: &BotState) -> Permission {
+    match (msg.guild_id, &msg.member) {
+        (Some(guild_id), Some(member)) => level(state, guild_id, msg.author.id, &member.roles),
+        _ => Permission::Everyone,
+    }
+}
+
+pub fn required(state: &BotState, path: &str, default: Permission) -> Permission {
+    match state.config.lock() {
+        Ok(config) => config.permissions.required(path, default),
+        Err(_) => default,
+    }
+}
+
+/// `none` removes a level, any other argument must name one.
+fn parse_level(inv: &Invocation, arg: &str) -> Result<Option<Permission>, CommandError> {
+    match arg {
+        "none" | "aucun" => Ok(None),
+        arg => Permission::parse(arg)
+            .map(Some)
+            .ok_or_else(|| inv.bad_usage()),
+    }
+}
+
+fn level_name(level: Option<Permission>, locale: Locale) -> String {
+    match level {
+        Some(level) => level.label(locale),
+        None => "-".to_owned(),
+    }
+}
+
+async fn audit_change(
+    inv: &Invocation,
+    target: String,
+    before: Option<Permission>,
+    after: Option<Permission>,
+) {
+    let record = AuditRecord::new(Action::Permissions, Some(inv.msg.author.id), target)
+        .before(level_name(before, inv.locale))
+        .after(level_name(after, inv.locale));
+    audit::log(&*inv.http, &inv.state, record).await;
+}
+
+async fn reply_level(inv: &Invocation, target: String, level: Option<Permission>) -> CommandResult {
+    let response = match level {
+        Some(level) => Message::new("perm-set")
+            .arg("target", target)
+            .arg("level", level.label(inv.locale)),
+        None => Message::new("perm-unset").arg("target", target),
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+pub async fn list(inv: Invocation) -> CommandResult {
+    let permissions = inv.state.config.lock()?.permissions.clone();
+    let mut lines = Vec::new();
+    for (role_id, level) in &permissions.roles {
+        lines.push(format!("- <@&{}> : {}", role_id, level.label(inv.locale)));
+    }
+    for (user_id, level) in &permissions.users {
+        lines.push(format!("- <@{}> : {}", user_id, level.label(inv.locale)));
+    }
+    for (path, level) in &permissions.commands {
+        lines.push(format!(
+            "- `{}{}` : {}",
+            registry::PREFIX,
+            path,
+            level.label(inv.locale)
+        ));
+    }
+    lines.sort();
+    let response = if lines.is_empty() {
+        Message::new("perm-list-empty").render(inv.locale)
+    } else {
+        format!(
+            "{}\n{}",
+            Message::new("perm-list-header").render(inv.locale),
+            lines.join("\n")
+        )
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn set_role(inv: Invocation) -> CommandResult {
+    let Some(role_id) = registry::parse_role(&inv.args[0]) else {
+        return Err(inv.bad_usage());
+    };
+    let level = parse_level(&inv, &inv.args[1])?;
+    let before = inv.state.update_config(|config| match level {
+        Some(level) => config.permissions.roles.insert(role_id, level),
+        None => config.permissions.roles.remove(&role_id),
+    })?;
+    let target = format!("<@&{}>", role_id);
+    audit_change(&inv, target.clone(), before, level).await;
+    reply_level(&inv, target, level).await
+}
+
+pub async fn set_user(inv: Invocation) -> CommandResult {
+    let Some(user_id) = registry::parse_user(&inv.args[0]) else {
+        return Err(inv.bad_usage());
+    };
+    let level = parse_level(&inv, &inv.args[1])?;
+    let before = inv.state.update_config(|config| match level {
+        Some(level) => config.permissions.users.insert(user_id, level),
+        None => config.permissions.users.remove(&user_id),
+    })?;
+    let target = format!("<@{}>", user_id);
+    audit_change(&inv, target.clone(), before, level).await;
+    reply_level(&inv, target, level).await
+}
+
+pub async fn set_command(inv: Invocation) -> CommandResult {
+    let path = registry::command_path(&inv, &inv.args[0])?;
+    if is_locked(&path) {
+        return Err(CommandError::NotFound(
+            Message::new("perm-locked").arg("command", format!("{}{}", registry::PREFIX, path)),
+        ));
+    }
+    let level = parse_level(&inv, &inv.args[1])?;
+    let before = inv.state.update_config(|config| match level {
+        Some(level) => config.permissions.commands.insert(path.clone(), level),
+        None => config.permissions.commands.remove(&path),
+    })?;
+    let target = format!("`{}{}`", registry::PREFIX, path);
+    audit_change(&inv, target.clone(), before, level).await;
+    reply_level(&inv, target, level).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_is_highest_grant() {
+        let config = PermissionConfig {
+            roles: HashMap::from([
+                (Id::new(10), Permission::Curator),
+                (Id::new(11), Permission::Moderator),
+            ]),
+            users: HashMap::from([(Id::new(1), Permission::Admin)]),
+            commands: HashMap::new(),
+        };
+        assert_eq!(
+            config.level_of(Id::new(2), &[Id::new(10)], false),
+            Permission::Curator
+        );
+        assert_eq!(
+            config.level_of(Id::new(2), &[Id::new(10), Id::new(11)], false),
+            Permission::Moderator
+        );
+        assert_eq!(
+            config.level_of(Id::new(1), &[Id::new(10)], false),
+            Permission::Admin
+        );
+        assert_eq!(
+            config.level_of(Id::new(2), &[Id::new(50)], false),
+            Permission::Everyone
+        );
+        assert_eq!(config.level_of(Id::new(2), &[], true), Permission::Admin);
+    }
+
+    #[test]
+    fn command_overrides() {
+        let config = PermissionConfig {
+            commands: HashMap::from([
+                ("rr add".to_owned(), Permission::Moderator),
+                ("perm role".to_owned(), Permission::Everyone),
+            ]),
+            ..PermissionConfig::default()
+        };
+        assert_eq!(
+            config.required("rr add", Permission::Admin),
+            Permission::Moderator
+        );
+        assert_eq!(
+            config.required("rr remove", Permission::Admin),
+            Permission::Admin
+        );
+        assert_eq!(
+            config.required("perm role", Permission::Admin),
+            Permission::Admin
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Rate limits of picture commands.
+//!
+//! Every picture sent takes a token from the bucket of its author, of its
+//! channel and from the global one. Buckets refill continuously, and a
+//! picture is only sent when all of them have a token left.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{
+    marker::{ChannelMarker, UserMarker},
+    Id,
+};
+
+use super::registry::Permission;
+
+/// Buckets are pruned from this many on.
+const PRUNE_AT: usize = 1000;
+
+/// Allows `count` pictures every `per_secs` seconds, in bursts of up to
+/// `count`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    pub count: u32,
+    pub per_secs: u64,
+}
+
+impl Quota {
+    /// Time it takes to get a token back.
+    fn refill_time(&self) -> Duration {
+        Duration::from_secs(self.per_secs) / self.count.max(1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Unset quotas don't limit anything.
+    pub user: Option<Quota>,
+    pub channel: Option<Quota>,
+    pub global: Option<Quota>,
+    /// Replies once to members who go over their quota, rather than
+    /// silently ignoring them.
+    pub warn: bool,
+    /// Members from this level on aren't limited.
+    pub exempt: Permission,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            user: Some(Quota {
+                count: 5,
+                per_secs: 60,
+            }),
+            channel: Some(Quota {
+                count: 10,
+                per_secs: 60,
+            }),
+            global: Some(Quota {
+                count: 30,
+                per_secs: 60,
+            }),
+            warn: true,
+            exempt: Permission::Admin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allowed,
+    /// Over the quota, and the member has to be told so.
+    Warn,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(quota: &Quota, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(quota.count),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, quota: &Quota, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        let refilled = elapsed.as_secs_f64() / quota.refill_time().as_secs_f64();
+        self.tokens = (self.tokens + refilled).min(f64::from(quota.count));
+        self.updated = now;
+    }
+}
+
+/// Refills the bucket of `key` and tells whether it has a token left.
+fn has_token<K: Eq + Hash>(
+    buckets: &mut HashMap<K, Bucket>,
+    key: K,
+    quota: &Quota,
+    now: Instant,
+) -> bool {
+    if buckets.len() >= PRUNE_AT {
+        buckets.retain(|_, bucket| {
+            bucket.refill(quota, now);
+            bucket.tokens < f64::from(quota.count)
+        });
+    }
+    let bucket = buckets
+        .entry(key)
+        .or_insert_with(|| Bucket::full(quota, now));
+    bucket.refill(quota, now);
+    bucket.tokens >= 1.0
+}
+
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    users: HashMap<Id<UserMarker>, Bucket>,
+    channels: HashMap<Id<ChannelMarker>, Bucket>,
+    global: HashMap<(), Bucket>,
+    /// Members who were told to slow down since their last picture.
+    warned: HashSet<Id<UserMarker>>,
+}
+
+impl RateLimiter {
+    /// Takes a token for a picture sent by `user_id` in `channel_id`, if
+    /// every bucket has one.
+    pub fn check(
+        &mut self,
+        config: &RateLimitConfig,
+        user_id: Id<UserMarker>,
+        channel_id: Id<ChannelMarker>,
+        now: Instant,
+    ) -> Decision {
+        let mut allowed = true;
+        if let Some(quota) = &config.user {
+            allowed &= has_token(&mut self.users, user_id, quota, now);
+        }
+        if let Some(quota) = &config.channel {
+            allowed &= has_token(&mut self.channels, channel_id, quota, now);
+        }
+        if let Some(quota) = &config.global {
+            allowed &= has_token(&mut self.global, (), quota, now);
+        }
+
+        if !allowed {
+            return if config.warn && self.warned.insert(user_id) {
+                Decision::Warn
+            } else {
+                Decision::Drop
+            };
+        }
+        self.warned.remove(&user_id);
+        let buckets = [
+            config.user.and(self.users.get_mut(&user_id)),
+            config.channel.and(self.channels.get_mut(&channel_id)),
+            config.global.and(self.global.get_mut(&())),
+        ];
+        for bucket in buckets.into_iter().flatten() {
+            bucket.tokens -= 1.0;
+        }
+        Decision::Allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(user: u32, channel: u32, global: u32) -> RateLimitConfig {
+        let quota = |count| {
+            Some(Quota {
+                count,
+                per_secs: 60,
+            })
+        };
+        RateLimitConfig {
+            user: quota(user),
+            channel: quota(channel),
+            global: quota(global),
+            ..RateLimitConfig::default()
+        }
+    }
+
+    #[test]
+    fn user_bucket_refills() {
+        let config = config(2, 100, 100);
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+        let (user, channel) = (Id::new(1), Id::new(10));
+        assert_eq!(
+            limiter.check(&config, user, channel, now),
+            Decision::Allowed
+        );
+        assert_eq!(
+            limiter.check(&config, user, channel, now),
+            Decision::Allowed
+        );
+        assert_eq!(limiter.check(&config, user, channel, now), Decision::Warn);
+        assert_eq!(limiter.check(&config, user, channel, now), Decision::Drop);
+        assert_eq!(
+            limiter.check(&config, Id::new(2), channel, now),
+            Decision::Allowed
+        );
+
+        let later = now + Duration::from_secs(30);
+        assert_eq!(
+            limiter.check(&config, user, channel, later),
+            Decision::Allowed
+        );
+        assert_eq!(limiter.check(&config, user, channel, later), Decision::Warn);
+    }
+
+    #[test]
+    fn channel_and_global_buckets() {
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+        let config = config(100, 1, 100);
+        assert_eq!(
+            limiter.check(&config, Id::new(1), Id::new(10), now),
+            Decision::Allowed
+        );
+        assert_eq!(
+            limiter.check(&config, Id::new(2), Id::new(10), now),
+            Decision::Warn
+        );
+        assert_eq!(
+            limiter.check(&config, Id::new(2), Id::new(11), now),
+            Decision::Allowed
+        );
+
+        let mut limiter = RateLimiter::default();
+        let config = RateLimitConfig {
+            warn: false,
+            ..self::config(100, 100, 1)
+        };
+        assert_eq!(
+            limiter.check(&config, Id::new(1), Id::new(10), now),
+            Decision::Allowed
+        );
+        assert_eq!(
+            limiter.check(&config, Id::new(2), Id::new(11), now),
+            Decision::Drop
+        );
+    }
+}
@@ -0,0 +1,947 @@
+//! Reaction roles: members react to a message with an emoji to get the role
+//! bound to it, and lose the role when they remove their reaction.
+//!
+//! Messages and their emoji to role mappings are managed with `!rr` and
+//! persisted in the configuration. Messages can also show buttons or a select
+//! menu instead, see [`role_picker`].
+
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::channel::message::{AllowedMentions, ReactionType};
+use twilight_model::gateway::GatewayReaction;
+use twilight_model::id::{
+    marker::{ChannelMarker, EmojiMarker, GuildMarker, MessageMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::lookup;
+use super::outbox::Outgoing;
+use super::registry::{self, CommandResult, Invocation};
+use super::role_picker::{self, Picker};
+use super::BotState;
+use crate::i18n::Message;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleEmoji {
+    Unicode(String),
+    Custom {
+        id: Id<EmojiMarker>,
+        name: String,
+        #[serde(default)]
+        animated: bool,
+    },
+}
+
+impl RoleEmoji {
+    /// Parses an emoji as typed in a message: either a Unicode emoji or a
+    /// custom one, which Discord sends as `<:name:id>` or `<a:name:id>`.
+    pub fn parse(arg: &str) -> Option<RoleEmoji> {
+        if let Some(inner) = arg.strip_prefix('<').and_then(|arg| arg.strip_suffix('>')) {
+            let mut parts = inner.split(':');
+            let animated = match parts.next()? {
+                "" => false,
+                "a" => true,
+                _ => return None,
+            };
+            let name = parts.next()?.to_owned();
+            let id = parts.next()?.parse().ok()?;
+            if parts.next().is_some() {
+                return None;
+            }
+            return Some(RoleEmoji::Custom { id, name, animated });
+        }
+        if arg.is_empty() || arg.chars().any(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(RoleEmoji::Unicode(arg.to_owned()))
+    }
+
+    pub fn matches(&self, reaction: &ReactionType) -> bool {
+        match (self, reaction) {
+            (Self::Unicode(name), ReactionType::Unicode { name: other }) => name == other,
+            (Self::Custom { id, .. }, ReactionType::Custom { id: other, .. }) => id == other,
+            _ => false,
+        }
+    }
+
+    pub fn reaction_type(&self) -> ReactionType {
+        match self {
+            Self::Unicode(name) => ReactionType::Unicode { name: name.clone() },
+            Self::Custom { id, name, animated } => ReactionType::Custom {
+                animated: *animated,
+                id: *id,
+                name: Some(name.clone()),
+            },
+        }
+    }
+
+    pub fn request(&self) -> RequestReactionType<'_> {
+        match self {
+            Self::Unicode(name) => RequestReactionType::Unicode { name },
+            Self::Custom { id, name, .. } => RequestReactionType::Custom {
+                id: *id,
+                name: Some(name),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for RoleEmoji {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unicode(name) => write!(f, "{}", name),
+            Self::Custom {
+                id,
+                name,
+                animated: false,
+            } => write!(f, "<:{}:{}>", name, id),
+            Self::Custom {
+                id,
+                name,
+                animated: true,
+            } => write!(f, "<a:{}:{}>", name, id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionRole {
+    pub emoji: RoleEmoji,
+    pub role_id: Id<RoleMarker>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionRoleMessage {
+    pub guild_id: Id<GuildMarker>,
+    /// Unknown for messages configured before channels were recorded, it is
+    /// filled in on the first reaction or looked up by the next resync.
+    pub channel_id: Option<Id<ChannelMarker>>,
+    pub message_id: Id<MessageMarker>,
+    /// Members can only hold one of the roles of an exclusive message.
+    #[serde(default)]
+    pub exclusive: bool,
+    #[serde(default)]
+    pub picker: Picker,
+    pub roles: Vec<ReactionRole>,
+}
+
+impl ReactionRoleMessage {
+    pub fn role_for(&self, reaction: &ReactionType) -> Option<&ReactionRole> {
+        self.roles.iter().find(|role| role.emoji.matches(reaction))
+    }
+}
+
+/// Pronoun roles, as they were set up before reaction roles could be
+/// configured.
+pub fn legacy() -> Vec<ReactionRoleMessage> {
+    let role = |emoji: &str, role_id| ReactionRole {
+        emoji: RoleEmoji::Unicode(emoji.to_owned()),
+        role_id: Id::new(role_id),
+    };
+    vec![ReactionRoleMessage {
+        guild_id: super::GUILD_ID,
+        channel_id: None,
+        message_id: Id::new(606807344759963688),
+        exclusive: false,
+        picker: Picker::Reactions,
+        roles: vec![
+            role("🌻", 606807806938447872),
+            role("🌸", 606807957052588042),
+            role("🍀", 606808023108943872),
+            role("🌼", 606808071834173451),
+        ],
+    }]
+}
+
+/// Finds the message members pick roles on by reacting.
+fn find_message(state: &BotState, message_id: Id<MessageMarker>) -> Option<ReactionRoleMessage> {
+    let config = state.config.lock().ok()?;
+    config
+        .reaction_roles
+        .iter()
+        .find(|rr| rr.message_id == message_id && rr.picker == Picker::Reactions)
+        .cloned()
+}
+
+/// Records the channel of messages that were configured without one.
+fn remember_channel(state: &BotState, rr: &ReactionRoleMessage, channel_id: Id<ChannelMarker>) {
+    if rr.channel_id.is_some() {
+        return;
+    }
+    let result = state.update_config(|config| {
+        for entry in &mut config.reaction_roles {
+            if entry.message_id == rr.message_id {
+                entry.channel_id = Some(channel_id);
+            }
+        }
+    });
+    if let Err(err) = result {
+        tracing::warn!(%err, "failed to record reaction role channel");
+    }
+}
+
+pub async fn on_reaction_add(
+    reaction: &GatewayReaction,
+    http: &dyn Discord,
+    state: &BotState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if matches!(&reaction.member, Some(member) if member.user.bot) {
+        return Ok(());
+    }
+    let Some(rr) = find_message(state, reaction.message_id) else {
+        return Ok(());
+    };
+    remember_channel(state, &rr, reaction.channel_id);
+    let Some(picked) = rr.role_for(&reaction.emoji) else {
+        return Ok(());
+    };
+
+    http.add_role(rr.guild_id, reaction.user_id, picked.role_id)
+        .await?;
+    tracing::info!(user = %reaction.user_id, role = %picked.role_id, "added reaction role");
+
+    if rr.exclusive {
+        let held = reaction
+            .member
+            .as_ref()
+            .map(|member| member.roles.clone())
+            .unwrap_or_default();
+        for other in rr
+            .roles
+            .iter()
+            .filter(|other| other.role_id != picked.role_id)
+        {
+            if !held.contains(&other.role_id) {
+                continue;
+            }
+            http.remove_role(rr.guild_id, reaction.user_id, other.role_id)
+                .await?;
+            let emoji = other.emoji.request();
+            http.delete_reaction(
+                reaction.channel_id,
+                reaction.message_id,
+                &emoji,
+                reaction.user_id,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn on_reaction_remove(
+    reaction: &GatewayReaction,
+    http: &dyn Discord,
+    state: &BotState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(rr) = find_message(state, reaction.message_id) else {
+        return Ok(());
+    };
+    remember_channel(state, &rr, reaction.channel_id);
+    let Some(picked) = rr.role_for(&reaction.emoji) else {
+        return Ok(());
+    };
+    http.remove_role(rr.guild_id, reaction.user_id, picked.role_id)
+        .await?;
+    tracing::info!(user = %reaction.user_id, role = %picked.role_id, "removed reaction role");
+    Ok(())
+}
+
+/// What to do with reaction roles that went out of sync while the bot was
+/// offline, when it starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResyncMode {
+    Off,
+    /// Only log what would change.
+    #[default]
+    DryRun,
+    Apply,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleChange {
+    pub user_id: Id<UserMarker>,
+    pub role_id: Id<RoleMarker>,
+    pub add: bool,
+}
+
+impl RoleChange {
+    pub fn audit_record(&self, actor: Option<Id<UserMarker>>) -> AuditRecord {
+        let record = AuditRecord::new(Action::RoleChanged, actor, format!("<@{}>", self.user_id));
+        let role = format!("<@&{}>", self.role_id);
+        if self.add {
+            record.after(role)
+        } else {
+            record.before(role)
+        }
+    }
+}
+
+impl std::fmt::Display for RoleChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let sign = if self.add { '+' } else { '-' };
+        write!(f, "{} <@{}> <@&{}>", sign, self.user_id, self.role_id)
+    }
+}
+
+/// Computes the role changes that make members' roles match their reactions
+/// on `rr`. `reactors` holds the users who reacted for each role. Roles in
+/// `shared` are also given by other messages, so they are never removed. On
+/// exclusive messages, members keep at most one role.
+pub fn plan_changes(
+    rr: &ReactionRoleMessage,
+    reactors: &HashMap<Id<RoleMarker>, HashSet<Id<UserMarker>>>,
+    members: &[(Id<UserMarker>, Vec<Id<RoleMarker>>)],
+    shared: &HashSet<Id<RoleMarker>>,
+) -> Vec<RoleChange> {
+    let mut changes = Vec::new();
+    for (user_id, roles) in members {
+        let mut wanted: Vec<Id<RoleMarker>> = rr
+            .roles
+            .iter()
+            .map(|role| role.role_id)
+            .filter(|role_id| {
+                reactors
+                    .get(role_id)
+                    .is_some_and(|users| users.contains(user_id))
+            })
+            .collect();
+        if rr.exclusive && wanted.len() > 1 {
+            // Prefer a role the member already holds, to change as little as
+            // possible.
+            let kept = wanted
+                .iter()
+                .copied()
+                .find(|role_id| roles.contains(role_id))
+                .unwrap_or(wanted[0]);
+            wanted = vec![kept];
+        }
+        for role in &rr.roles {
+            let reacted = wanted.contains(&role.role_id);
+            let held = roles.contains(&role.role_id);
+            if reacted == held || (held && shared.contains(&role.role_id)) {
+                continue;
+            }
+            changes.push(RoleChange {
+                user_id: *user_id,
+                role_id: role.role_id,
+                add: reacted,
+            });
+        }
+    }
+    changes
+}
+
+/// Roles of `rr` that another reaction role message or picker also gives.
+fn shared_roles(
+    rr: &ReactionRoleMessage,
+    messages: &[ReactionRoleMessage],
+) -> HashSet<Id<RoleMarker>> {
+    messages
+        .iter()
+        .filter(|other| other.message_id != rr.message_id && other.guild_id == rr.guild_id)
+        .flat_map(|other| other.roles.iter().map(|role| role.role_id))
+        .filter(|role_id| rr.roles.iter().any(|role| role.role_id == *role_id))
+        .collect()
+}
+
+const REACTIONS_PAGE: u16 = 100;
+
+async fn fetch_reactors(
+    http: &dyn Discord,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    emoji: &RoleEmoji,
+) -> Result<HashSet<Id<UserMarker>>, CommandError> {
+    let request_emoji = emoji.request();
+    let mut users = HashSet::new();
+    let mut after = None;
+    loop {
+        let page = http
+            .reactions(
+                channel_id,
+                message_id,
+                &request_emoji,
+                after,
+                REACTIONS_PAGE,
+            )
+            .await?;
+        let page_len = page.len();
+        after = page.last().map(|reactor| reactor.user_id);
+        users.extend(
+            page.into_iter()
+                .filter(|reactor| !reactor.bot)
+                .map(|reactor| reactor.user_id),
+        );
+        if page_len < REACTIONS_PAGE as usize {
+            return Ok(users);
+        }
+    }
+}
+
+async fn plan_message(
+    http: &dyn Discord,
+    state: &BotState,
+    rr: &ReactionRoleMessage,
+    members: &[(Id<UserMarker>, Vec<Id<RoleMarker>>)],
+    shared: &HashSet<Id<RoleMarker>>,
+) -> Result<Vec<RoleChange>, CommandError> {
+    if rr.picker != Picker::Reactions {
+        return Ok(Vec::new());
+    }
+    let channel_id = match rr.channel_id {
+        Some(channel_id) => channel_id,
+        None => match lookup::message_channel(http, state, rr.guild_id, rr.message_id).await? {
+            Some(channel_id) => {
+                remember_channel(state, rr, channel_id);
+                channel_id
+            }
+            None => {
+                tracing::warn!(message = %rr.message_id, "message not found, cannot resync reaction roles");
+                return Ok(Vec::new());
+            }
+        },
+    };
+    let mut reactors = HashMap::new();
+    for role in &rr.roles {
+        let users = fetch_reactors(http, channel_id, rr.message_id, &role.emoji).await?;
+        reactors.insert(role.role_id, users);
+    }
+    Ok(plan_changes(rr, &reactors, members, shared))
+}
+
+/// Brings members' roles back in line with the reactions on every reaction
+/// role message. With `dry_run`, nothing is changed and the changes that
+/// would have been made are returned. Applied changes are audited as made by
+/// `actor`.
+pub async fn resync(
+    http: &dyn Discord,
+    state: &BotState,
+    dry_run: bool,
+    actor: Option<Id<UserMarker>>,
+) -> Result<Vec<RoleChange>, CommandError> {
+    let messages = state.config.lock()?.reaction_roles.clone();
+    let mut members_by_guild = HashMap::new();
+    let mut all_changes = Vec::new();
+
+    for rr in &messages {
+        if let Entry::Vacant(entry) = members_by_guild.entry(rr.guild_id) {
+            let members: Vec<(Id<UserMarker>, Vec<Id<RoleMarker>>)> =
+                lookup::members(http, state, rr.guild_id)
+                    .await?
+                    .into_iter()
+                    .filter(|member| !member.bot)
+                    .map(|member| (member.user_id, member.roles))
+                    .collect();
+            entry.insert(members);
+        }
+        let members = &members_by_guild[&rr.guild_id];
+
+        let shared = shared_roles(rr, &messages);
+        let changes = match plan_message(http, state, rr, members, &shared).await {
+            Ok(changes) => changes,
+            Err(err) => {
+                tracing::warn!(message = %rr.message_id, %err, "failed to resync reaction roles");
+                continue;
+            }
+        };
+        for change in changes {
+            if !dry_run {
+                let result = if change.add {
+                    http.add_role(rr.guild_id, change.user_id, change.role_id)
+                        .await
+                } else {
+                    http.remove_role(rr.guild_id, change.user_id, change.role_id)
+                        .await
+                };
+                if let Err(err) = result {
+                    tracing::warn!(%change, %err, "failed to apply reaction role change");
+                    continue;
+                }
+                audit::log(http, state, change.audit_record(actor)).await;
+            }
+            tracing::info!(%change, dry_run, "reaction role resync");
+            all_changes.push(change);
+        }
+    }
+    Ok(all_changes)
+}
+
+/// Most changes listed in a report, to stay under Discord's message length.
+const REPORT_MAX_LINES: usize = 20;
+
+pub async fn sync(inv: Invocation) -> CommandResult {
+    let dry_run = match inv.args.first().map(String::as_str) {
+        None => false,
+        Some("dry-run" | "test") => true,
+        Some(_) => return Err(inv.bad_usage()),
+    };
+    let changes = resync(&*inv.http, &inv.state, dry_run, Some(inv.msg.author.id)).await?;
+
+    let key = if dry_run {
+        "rr-sync-dry-run"
+    } else {
+        "rr-sync-done"
+    };
+    let added = changes.iter().filter(|change| change.add).count();
+    let mut response = Message::new(key)
+        .arg("added", added)
+        .arg("removed", changes.len() - added)
+        .render(inv.locale);
+    for change in changes.iter().take(REPORT_MAX_LINES) {
+        response.push_str(&format!("\n{}", change));
+    }
+    if changes.len() > REPORT_MAX_LINES {
+        response.push_str("\n…");
+    }
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+fn parse_message_arg(inv: &Invocation, arg: &str) -> Result<Id<MessageMarker>, CommandError> {
+    arg.parse().map_err(|_| inv.bad_usage())
+}
+
+fn unknown_message(message_id: Id<MessageMarker>) -> CommandError {
+    CommandError::NotFound(
+        Message::new("rr-unknown-message").arg("message", message_id.to_string()),
+    )
+}
+
+pub async fn list(inv: Invocation) -> CommandResult {
+    let messages = inv.state.config.lock()?.reaction_roles.clone();
+    if messages.is_empty() {
+        let response = Message::new("rr-list-empty").render(inv.locale);
+        return reply_in_chann(&*inv.http, &inv.msg, &response).await;
+    }
+
+    let mut response = Message::new("rr-list-header").render(inv.locale);
+    for rr in messages {
+        let roles: Vec<String> = rr
+            .roles
+            .iter()
+            .map(|role| format!("{} → <@&{}>", role.emoji, role.role_id))
+            .collect();
+        let channel = match rr.channel_id {
+            Some(channel_id) => format!("<#{}>", channel_id),
+            None => "?".to_owned(),
+        };
+        let key = if rr.exclusive {
+            "rr-list-entry-exclusive"
+        } else {
+            "rr-list-entry"
+        };
+        response.push('\n');
+        response.push_str(
+            &Message::new(key)
+                .arg("message", rr.message_id.to_string())
+                .arg("channel", channel)
+                .arg("roles", roles.join(", "))
+                .render(inv.locale),
+        );
+    }
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn create(inv: Invocation) -> CommandResult {
+    let Some(guild_id) = inv.msg.guild_id else {
+        return Err(CommandError::NotFound(Message::new("error-no-guild")));
+    };
+    let exclusive = match inv.args.get(1).map(String::as_str) {
+        None => false,
+        Some("exclusif" | "exclusive") => true,
+        Some(_) => return Err(inv.bad_usage()),
+    };
+
+    let posted = Outgoing::new(inv.msg.channel_id)
+        .allowed_mentions(AllowedMentions::default())
+        .content(inv.args[0].as_str())
+        .send(&*inv.http)
+        .await?;
+
+    inv.state.update_config(|config| {
+        config.reaction_roles.push(ReactionRoleMessage {
+            guild_id,
+            channel_id: Some(inv.msg.channel_id),
+            message_id: posted,
+            exclusive,
+            picker: Picker::Reactions,
+            roles: Vec::new(),
+        })
+    })?;
+    let record = AuditRecord::new(
+        Action::ReactionRoles,
+        Some(inv.msg.author.id),
+        posted.to_string(),
+    )
+    .after(inv.args[0].as_str());
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let response = Message::new("rr-created")
+        .arg("message", posted.to_string())
+        .arg(
+            "add",
+            format!("{}rr add {} <emoji> <role>", registry::PREFIX, posted),
+        );
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+pub async fn add(inv: Invocation) -> CommandResult {
+    let message_id = parse_message_arg(&inv, &inv.args[0])?;
+    let Some(emoji) = RoleEmoji::parse(&inv.args[1]) else {
+        return Err(inv.bad_usage());
+    };
+    let Some(role_id) = registry::parse_role(&inv.args[2]) else {
+        return Err(inv.bad_usage());
+    };
+
+    let rr = inv.state.update_config(|config| {
+        let rr = config
+            .reaction_roles
+            .iter_mut()
+            .find(|rr| rr.message_id == message_id)?;
+        rr.roles.retain(|role| role.emoji != emoji);
+        rr.roles.push(ReactionRole {
+            emoji: emoji.clone(),
+            role_id,
+        });
+        Some(rr.clone())
+    })?;
+    let Some(rr) = rr else {
+        return Err(unknown_message(message_id));
+    };
+
+    match rr.channel_id {
+        Some(channel_id) if rr.picker == Picker::Reactions => {
+            let request = emoji.request();
+            inv.http
+                .create_reaction(channel_id, message_id, &request)
+                .await?;
+        }
+        Some(channel_id) => {
+            role_picker::render(&*inv.http, &inv.state, &rr, channel_id, inv.locale).await?
+        }
+        None => {}
+    }
+
+    let record = AuditRecord::new(
+        Action::ReactionRoles,
+        Some(inv.msg.author.id),
+        message_id.to_string(),
+    )
+    .after(format!("{} → <@&{}>", emoji, role_id));
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let response = Message::new("rr-role-added")
+        .arg("emoji", emoji.to_string())
+        .arg("role", format!("<@&{}>", role_id));
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+pub async fn remove(inv: Invocation) -> CommandResult {
+    let message_id = parse_message_arg(&inv, &inv.args[0])?;
+    let Some(emoji) = RoleEmoji::parse(&inv.args[1]) else {
+        return Err(inv.bad_usage());
+    };
+
+    let rr = inv.state.update_config(|config| {
+        let rr = config
+            .reaction_roles
+            .iter_mut()
+            .find(|rr| rr.message_id == message_id)?;
+        let before = rr.roles.len();
+        rr.roles.retain(|role| role.emoji != emoji);
+        Some((rr.clone(), before != rr.roles.len()))
+    })?;
+    let (rr, removed) = rr.ok_or_else(|| unknown_message(message_id))?;
+    if !removed {
+        return Err(CommandError::NotFound(
+            Message::new("rr-unknown-emoji").arg("emoji", emoji.to_string()),
+        ));
+    }
+
+    match rr.channel_id {
+        Some(channel_id) if rr.picker == Picker::Reactions => {
+            let request = emoji.request();
+            inv.http
+                .delete_own_reaction(channel_id, message_id, &request)
+                .await?;
+        }
+        Some(channel_id) => {
+            role_picker::render(&*inv.http, &inv.state, &rr, channel_id, inv.locale).await?
+        }
+        None => {}
+    }
+
+    let record = AuditRecord::new(
+        Action::ReactionRoles,
+        Some(inv.msg.author.id),
+        message_id.to_string(),
+    )
+    .before(emoji.to_string());
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let response = Message::new("rr-role-removed").arg("emoji", emoji.to_string());
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+pub async fn set_exclusive(inv: Invocation) -> CommandResult {
+    let message_id = parse_message_arg(&inv, &inv.args[0])?;
+    let Some(exclusive) = registry::parse_switch(&inv.args[1]) else {
+        return Err(inv.bad_usage());
+    };
+
+    let rr = inv.state.update_config(|config| {
+        let rr = config
+            .reaction_roles
+            .iter_mut()
+            .find(|rr| rr.message_id == message_id)?;
+        rr.exclusive = exclusive;
+        Some(rr.clone())
+    })?;
+    let rr = rr.ok_or_else(|| unknown_message(message_id))?;
+    // The select menu limits how many roles can be picked.
+    if let (Picker::Select, Some(channel_id)) = (rr.picker, rr.channel_id) {
+        role_picker::render(&*inv.http, &inv.state, &rr, channel_id, inv.locale).await?;
+    }
+
+    let record = AuditRecord::new(
+        Action::ReactionRoles,
+        Some(inv.msg.author.id),
+        message_id.to_string(),
+    )
+    .before(format!("exclusive: {}", !exclusive))
+    .after(format!("exclusive: {}", exclusive));
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let key = if exclusive {
+        "rr-exclusive-on"
+    } else {
+        "rr-exclusive-off"
+    };
+    let response = Message::new(key).arg("message", message_id.to_string());
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+pub async fn delete(inv: Invocation) -> CommandResult {
+    let message_id = parse_message_arg(&inv, &inv.args[0])?;
+
+    let deleted = inv.state.update_config(|config| {
+        let index = config
+            .reaction_roles
+            .iter()
+            .position(|rr| rr.message_id == message_id)?;
+        Some(config.reaction_roles.remove(index))
+    })?;
+    let deleted = deleted.ok_or_else(|| unknown_message(message_id))?;
+    let roles: Vec<String> = deleted
+        .roles
+        .iter()
+        .map(|role| format!("{} → <@&{}>", role.emoji, role.role_id))
+        .collect();
+    let record = AuditRecord::new(
+        Action::ReactionRoles,
+        Some(inv.msg.author.id),
+        message_id.to_string(),
+    )
+    .before(roles.join(", "));
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let response = Message::new("rr-deleted").arg("message", message_id.to_string());
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_emoji() {
+        assert_eq!(
+            RoleEmoji::parse("🌻"),
+            Some(RoleEmoji::Unicode("🌻".to_owned()))
+        );
+        assert_eq!(
+            RoleEmoji::parse("<:ban:519852990119673871>"),
+            Some(RoleEmoji::Custom {
+                id: Id::new(519852990119673871),
+                name: "ban".to_owned(),
+                animated: false,
+            })
+        );
+        assert_eq!(
+            RoleEmoji::parse("<a:dance:42>"),
+            Some(RoleEmoji::Custom {
+                id: Id::new(42),
+                name: "dance".to_owned(),
+                animated: true,
+            })
+        );
+        assert_eq!(RoleEmoji::parse("<:broken>"), None);
+        assert_eq!(RoleEmoji::parse("role"), None);
+        assert_eq!(RoleEmoji::parse(""), None);
+    }
+
+    #[test]
+    fn emoji_display_roundtrip() {
+        for text in ["🍀", "<:ban:519852990119673871>", "<a:dance:42>"] {
+            assert_eq!(RoleEmoji::parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn role_for_matches_custom_emoji_by_id() {
+        let rr = ReactionRoleMessage {
+            guild_id: Id::new(1),
+            channel_id: None,
+            message_id: Id::new(2),
+            exclusive: true,
+            picker: Picker::Reactions,
+            roles: vec![ReactionRole {
+                emoji: RoleEmoji::parse("<:ban:519852990119673871>").unwrap(),
+                role_id: Id::new(3),
+            }],
+        };
+        let renamed = ReactionType::Custom {
+            animated: false,
+            id: Id::new(519852990119673871),
+            name: Some("renamed".to_owned()),
+        };
+        assert_eq!(
+            rr.role_for(&renamed).map(|role| role.role_id),
+            Some(Id::new(3))
+        );
+        let unicode = ReactionType::Unicode {
+            name: "🌻".to_owned(),
+        };
+        assert!(rr.role_for(&unicode).is_none());
+    }
+
+    #[test]
+    fn plan_changes_matches_reactions() {
+        let rr = ReactionRoleMessage {
+            guild_id: Id::new(1),
+            channel_id: Some(Id::new(2)),
+            message_id: Id::new(3),
+            exclusive: false,
+            picker: Picker::Reactions,
+            roles: vec![
+                ReactionRole {
+                    emoji: RoleEmoji::Unicode("🌻".to_owned()),
+                    role_id: Id::new(10),
+                },
+                ReactionRole {
+                    emoji: RoleEmoji::Unicode("🌸".to_owned()),
+                    role_id: Id::new(11),
+                },
+            ],
+        };
+        let reactors = HashMap::from([
+            (Id::new(10), HashSet::from([Id::new(100), Id::new(101)])),
+            (Id::new(11), HashSet::from([Id::new(101)])),
+        ]);
+        let members = vec![
+            // Reacted while the bot was away.
+            (Id::new(100), vec![]),
+            // Already in sync.
+            (Id::new(101), vec![Id::new(10), Id::new(11)]),
+            // Removed a reaction while the bot was away, other roles are kept.
+            (Id::new(102), vec![Id::new(11), Id::new(50)]),
+        ];
+        assert_eq!(
+            plan_changes(&rr, &reactors, &members, &HashSet::new()),
+            vec![
+                RoleChange {
+                    user_id: Id::new(100),
+                    role_id: Id::new(10),
+                    add: true,
+                },
+                RoleChange {
+                    user_id: Id::new(102),
+                    role_id: Id::new(11),
+                    add: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_changes_keeps_shared_and_exclusive_roles() {
+        let mut rr = ReactionRoleMessage {
+            guild_id: Id::new(1),
+            channel_id: Some(Id::new(2)),
+            message_id: Id::new(3),
+            exclusive: true,
+            picker: Picker::Reactions,
+            roles: vec![
+                ReactionRole {
+                    emoji: RoleEmoji::Unicode("🌻".to_owned()),
+                    role_id: Id::new(10),
+                },
+                ReactionRole {
+                    emoji: RoleEmoji::Unicode("🌸".to_owned()),
+                    role_id: Id::new(11),
+                },
+            ],
+        };
+        let picker = ReactionRoleMessage {
+            message_id: Id::new(4),
+            exclusive: false,
+            picker: Picker::Select,
+            roles: vec![ReactionRole {
+                emoji: RoleEmoji::Unicode("🌼".to_owned()),
+                role_id: Id::new(11),
+            }],
+            ..rr.clone()
+        };
+        let shared = shared_roles(&rr, &[rr.clone(), picker]);
+        assert_eq!(shared, HashSet::from([Id::new(11)]));
+
+        let reactors = HashMap::from([
+            (Id::new(10), HashSet::from([Id::new(100), Id::new(101)])),
+            (Id::new(11), HashSet::from([Id::new(100), Id::new(101)])),
+        ]);
+        let members = vec![
+            // Reacted twice while the bot was away, only the first role is given.
+            (Id::new(100), vec![]),
+            // Reacted twice, keeps the role already held.
+            (Id::new(101), vec![Id::new(11)]),
+            // No reaction, but the role also comes from the picker.
+            (Id::new(102), vec![Id::new(11)]),
+        ];
+        assert_eq!(
+            plan_changes(&rr, &reactors, &members, &shared),
+            vec![RoleChange {
+                user_id: Id::new(100),
+                role_id: Id::new(10),
+                add: true,
+            }]
+        );
+
+        rr.exclusive = false;
+        assert_eq!(
+            plan_changes(&rr, &HashMap::new(), &members, &HashSet::new()).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn legacy_roles() {
+        let legacy = legacy();
+        let sunflower = ReactionType::Unicode {
+            name: "🌻".to_owned(),
+        };
+        assert_eq!(
+            legacy[0].role_for(&sunflower).map(|role| role.role_id),
+            Some(Id::new(606807806938447872))
+        );
+    }
+}
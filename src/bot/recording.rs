@@ -0,0 +1,392 @@
+//! Gateway recordings: the events the bot receives can be written to a file,
+//! one JSON payload per line, then fed again through the event handler to
+//! reproduce a bug.
+//!
+//! Secrets and personal details that handlers don't depend on are redacted
+//! before writing. Message contents, nicknames and ids are kept, replaying
+//! needs them.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use twilight_cache_inmemory::InMemoryCache;
+use twilight_gateway::{Event, EventTypeFlags};
+use twilight_http::request::channel::reaction::RequestReactionType;
+use twilight_model::channel::message::Component;
+use twilight_model::http::interaction::InteractionResponse;
+use twilight_model::id::{
+    marker::{
+        ApplicationMarker, ChannelMarker, GuildMarker, InteractionMarker, MessageMarker,
+        RoleMarker, UserMarker,
+    },
+    Id,
+};
+use twilight_model::util::Timestamp;
+
+use super::discord::{Discord, DiscordResult, Reactor, RoleNames};
+use super::lookup::MemberInfo;
+use super::outbox::Outgoing;
+use super::{audit, guard, handle_event, stats, BotState};
+use crate::album::Album;
+use crate::config::Config;
+
+/// Opcode of the payloads carrying events.
+const DISPATCH: u64 = 0;
+const REDACTED: &str = "[redacted]";
+/// Fields replaced by [`REDACTED`], wherever they are.
+const SECRETS: [&str; 7] = [
+    "token",
+    "session_id",
+    "resume_gateway_url",
+    "email",
+    "phone",
+    "username",
+    "global_name",
+];
+/// Image hashes, replaced by null.
+const IMAGES: [&str; 3] = ["avatar", "banner", "avatar_decoration"];
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                if SECRETS.contains(&key.as_str()) && field.is_string() {
+                    *field = Value::from(REDACTED);
+                } else if IMAGES.contains(&key.as_str()) {
+                    *field = Value::Null;
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Redacted payload of a gateway message, none if it isn't an event.
+fn redacted(text: &str) -> Option<String> {
+    let mut payload: Value = serde_json::from_str(text).ok()?;
+    if payload["op"].as_u64() != Some(DISPATCH) {
+        return None;
+    }
+    redact(&mut payload);
+    Some(payload.to_string())
+}
+
+/// Parses a gateway message like `Shard::next_event` does: events that
+/// aren't wanted or can't be read are skipped.
+pub fn parse(text: String, wanted: EventTypeFlags) -> Option<Event> {
+    match twilight_gateway::parse(text, wanted) {
+        Ok(event) => event.map(Event::from),
+        Err(err) => {
+            tracing::debug!(%err, "skipped gateway message");
+            None
+        }
+    }
+}
+
+/// Appends the events received to a recording.
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> anyhow::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Records `text` if it is an event. Failures are only traced.
+    pub fn record(&mut self, text: &str) {
+        let Some(line) = redacted(text) else {
+            return;
+        };
+        if let Err(err) = writeln!(&mut self.file, "{}", line) {
+            tracing::warn!(%err, "failed to record event");
+        }
+    }
+}
+
+/// Copies of the data files in a directory of their own, so that replaying
+/// doesn't change them. Removed once dropped.
+pub struct Scratch {
+    dir: PathBuf,
+}
+
+impl Scratch {
+    /// Copies the `files` that exist.
+    pub fn new(files: &[&str]) -> anyhow::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("oxytrouille-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let scratch = Self { dir };
+        for file in files {
+            match std::fs::copy(file, scratch.path(file)) {
+                Ok(_) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(scratch)
+    }
+
+    /// Where the copy of `file` is.
+    pub fn path(&self, file: &str) -> String {
+        let name = Path::new(file).file_name().unwrap_or(file.as_ref());
+        self.dir.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A [`Discord`] that prints what the bot asks for instead of doing it.
+/// Lookups find nothing: there is no guild to ask.
+#[derive(Default)]
+struct ReplayDiscord {
+    messages: AtomicU64,
+}
+
+impl ReplayDiscord {
+    fn done<'a, T: Send + 'a>(&self, call: String, result: T) -> BoxFuture<'a, DiscordResult<T>> {
+        println!("  {}", call);
+        Box::pin(async move { Ok(result) })
+    }
+}
+
+impl Discord for ReplayDiscord {
+    fn create_message<'a>(
+        &'a self,
+        message: &'a Outgoing,
+    ) -> BoxFuture<'a, DiscordResult<Id<MessageMarker>>> {
+        let count = self.messages.fetch_add(1, Ordering::Relaxed) + 1;
+        let call = format!(
+            "create message in {} (reply to {:?}, {} embeds): {}",
+            message.channel_id,
+            message.reply_to,
+            message.embeds.len(),
+            message.content.as_deref().unwrap_or("")
+        );
+        self.done(call, Id::new(1_000_000 + count))
+    }
+
+    fn update_components<'a>(
+        &'a self,
+        _channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        components: &'a [Component],
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        let call = format!(
+            "update components of {}: {} rows",
+            message_id,
+            components.len()
+        );
+        self.done(call, ())
+    }
+
+    fn create_reaction<'a>(
+        &'a self,
+        _channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        self.done(format!("react to {} with {}", message_id, emoji), ())
+    }
+
+    fn delete_own_reaction<'a>(
+        &'a self,
+        _channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        self.done(format!("remove own {} from {}", emoji, message_id), ())
+    }
+
+    fn delete_reaction<'a>(
+        &'a self,
+        _channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        emoji: &'a RequestReactionType<'a>,
+        user_id: Id<UserMarker>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        let call = format!("remove {} of {} from {}", emoji, user_id, message_id);
+        self.done(call, ())
+    }
+
+    fn reactions<'a>(
+        &'a self,
+        _channel_id: Id<ChannelMarker>,
+        _message_id: Id<MessageMarker>,
+        _emoji: &'a RequestReactionType<'a>,
+        _after: Option<Id<UserMarker>>,
+        _limit: u16,
+    ) -> BoxFuture<'a, DiscordResult<Vec<Reactor>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn add_role(
+        &self,
+        _guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        role_id: Id<RoleMarker>,
+    ) -> BoxFuture<'_, DiscordResult<()>> {
+        self.done(format!("add role {} to {}", role_id, user_id), ())
+    }
+
+    fn remove_role(
+        &self,
+        _guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        role_id: Id<RoleMarker>,
+    ) -> BoxFuture<'_, DiscordResult<()>> {
+        self.done(format!("remove role {} from {}", role_id, user_id), ())
+    }
+
+    fn set_nickname<'a>(
+        &'a self,
+        _guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        nick: Option<&'a str>,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        self.done(format!("set nickname of {} to {:?}", user_id, nick), ())
+    }
+
+    fn time_out(
+        &self,
+        _guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        until: Option<Timestamp>,
+    ) -> BoxFuture<'_, DiscordResult<()>> {
+        let until = until.map(|until| until.iso_8601().to_string());
+        self.done(format!("time out {} until {:?}", user_id, until), ())
+    }
+
+    fn guild_members(
+        &self,
+        _guild_id: Id<GuildMarker>,
+        _after: Option<Id<UserMarker>>,
+        _limit: u16,
+    ) -> BoxFuture<'_, DiscordResult<Vec<MemberInfo>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn roles(&self, _guild_id: Id<GuildMarker>) -> BoxFuture<'_, DiscordResult<RoleNames>> {
+        Box::pin(async { Ok(RoleNames::new()) })
+    }
+
+    fn channel_guild(
+        &self,
+        _channel_id: Id<ChannelMarker>,
+    ) -> BoxFuture<'_, DiscordResult<Option<Id<GuildMarker>>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    fn text_channels(
+        &self,
+        _guild_id: Id<GuildMarker>,
+    ) -> BoxFuture<'_, DiscordResult<Vec<Id<ChannelMarker>>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn message_exists(
+        &self,
+        _channel_id: Id<ChannelMarker>,
+        _message_id: Id<MessageMarker>,
+    ) -> BoxFuture<'_, DiscordResult<bool>> {
+        Box::pin(async { Ok(false) })
+    }
+
+    fn respond_to_interaction<'a>(
+        &'a self,
+        _application_id: Id<ApplicationMarker>,
+        interaction_id: Id<InteractionMarker>,
+        _token: &'a str,
+        response: &'a InteractionResponse,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        let content = response
+            .data
+            .as_ref()
+            .and_then(|data| data.content.as_deref());
+        let call = format!(
+            "respond to interaction {}: {}",
+            interaction_id,
+            content.unwrap_or("")
+        );
+        self.done(call, ())
+    }
+
+    fn update_interaction_response<'a>(
+        &'a self,
+        _application_id: Id<ApplicationMarker>,
+        _token: &'a str,
+        content: &'a str,
+    ) -> BoxFuture<'a, DiscordResult<()>> {
+        self.done(format!("update interaction response: {}", content), ())
+    }
+}
+
+/// Feeds the events of the recording at `path` through the event handler,
+/// one after the other, and prints what the bot would have done.
+pub async fn replay(
+    path: &str,
+    alb: Album,
+    config: Config,
+    offenses: guard::Offenses,
+    audit: audit::AuditLog,
+    usage: stats::UsageLog,
+) -> anyhow::Result<()> {
+    let discord: Arc<dyn Discord> = Arc::new(ReplayDiscord::default());
+    let cache = Arc::new(InMemoryCache::new());
+    let state = BotState::new(
+        Arc::new(Mutex::new(alb)),
+        Arc::new(Mutex::new(config)),
+        Arc::new(Mutex::new(offenses)),
+        Arc::new(Mutex::new(audit)),
+        Arc::new(Mutex::new(usage)),
+        Arc::clone(&cache),
+    );
+
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let Some(event) = parse(line?, EventTypeFlags::all()) else {
+            println!("{}: skipped", number + 1);
+            continue;
+        };
+        println!("{}: {:?}", number + 1, event.kind());
+        cache.update(&event);
+        if let Err(err) = handle_event(event, Arc::clone(&discord), state.clone()).await {
+            println!("  error: {}", err);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_events_are_recorded_redacted() {
+        assert_eq!(redacted(r#"{"op":11}"#), None);
+        assert_eq!(redacted("not json"), None);
+
+        let text = r#"{"op":0,"t":"INTERACTION_CREATE","s":3,"d":{
+            "token":"secret","member":{"nick":"Zoé","user":{"id":"42",
+            "username":"zoe","avatar":"a1b2","public_flags":0}}}}"#;
+        let payload: Value = serde_json::from_str(&redacted(text).unwrap()).unwrap();
+        assert_eq!(payload["t"], "INTERACTION_CREATE");
+        assert_eq!(payload["d"]["token"], REDACTED);
+        let member = &payload["d"]["member"];
+        assert_eq!(member["nick"], "Zoé");
+        assert_eq!(member["user"]["id"], "42");
+        assert_eq!(member["user"]["username"], REDACTED);
+        assert_eq!(member["user"]["avatar"], Value::Null);
+    }
+}
@@ -0,0 +1,1407 @@
+//! Command registry.
+//!
+//! Every command the bot understands is declared once in [`COMMANDS`] with
+//! its name, aliases, argument schema, permission level and description.
+//! Dispatch, argument checking and the help texts are all derived from it.
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use twilight_model::gateway::payload::incoming::MessageCreate;
+use twilight_model::id::{
+    marker::{ChannelMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use super::announcements;
+use super::audit;
+use super::command;
+use super::confirm;
+use super::custom;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::guard;
+use super::nicknames;
+use super::permissions;
+use super::reaction_roles;
+use super::role_picker;
+use super::schedules;
+use super::scopes;
+use super::stats;
+use super::BotState;
+use crate::i18n::{self, Locale, Message};
+
+pub const PREFIX: char = '!';
+
+/// Levels are ordered, each one can do everything the previous ones can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Everyone,
+    /// Looks after the album.
+    Curator,
+    Moderator,
+    Admin,
+}
+
+impl Permission {
+    /// Parses a level, in French or English.
+    pub fn parse(arg: &str) -> Option<Permission> {
+        match arg.to_lowercase().as_str() {
+            "everyone" | "tous" => Some(Self::Everyone),
+            "curator" | "curateur" | "curatrice" => Some(Self::Curator),
+            "moderator" | "moderateur" | "modérateur" | "moderatrice" | "modératrice" => {
+                Some(Self::Moderator)
+            }
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn label(self, locale: Locale) -> String {
+        let key = match self {
+            Self::Everyone => "level-everyone",
+            Self::Curator => "level-curator",
+            Self::Moderator => "level-moderator",
+            Self::Admin => "level-admin",
+        };
+        i18n::tr(locale, key, &[])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Required,
+    Optional,
+    /// Takes every remaining argument, at least one of them.
+    Variadic,
+    /// Takes every remaining argument, possibly none.
+    Rest,
+}
+
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub arity: Arity,
+    /// Message key of the description.
+    pub description: &'static str,
+}
+
+/// Everything a command handler gets to work with.
+pub struct Invocation {
+    pub msg: Box<MessageCreate>,
+    pub args: Vec<String>,
+    /// Usage line of the command being run.
+    pub usage: String,
+    pub http: Arc<dyn Discord>,
+    pub state: BotState,
+    pub locale: Locale,
+}
+
+impl Invocation {
+    /// Error for arguments that have the right count but can't be parsed.
+    pub fn bad_usage(&self) -> CommandError {
+        CommandError::BadUsage(self.usage.clone())
+    }
+}
+
+pub type CommandResult = Result<(), CommandError>;
+
+pub type Handler = fn(Invocation) -> BoxFuture<'static, CommandResult>;
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static [ArgSpec],
+    pub permission: Permission,
+    /// Message key of the description.
+    pub description: &'static str,
+    pub handler: Handler,
+    /// Subcommands, picked by the first argument. The parent's own
+    /// arguments and handler are only used when none of them matches.
+    pub subcommands: &'static [CommandSpec],
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "aled",
+        aliases: &["help"],
+        args: &[ArgSpec {
+            name: "commande",
+            arity: Arity::Rest,
+            description: "cmd-aled-commande",
+        }],
+        permission: Permission::Everyone,
+        description: "cmd-aled",
+        handler: |inv| Box::pin(command::helper(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "add",
+        aliases: &[],
+        args: &[ArgSpec {
+            name: "deck",
+            arity: Arity::Required,
+            description: "cmd-add-deck",
+        }],
+        permission: Permission::Curator,
+        description: "cmd-add",
+        handler: |inv| Box::pin(command::picture_add(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "delete_last",
+        aliases: &[],
+        args: &[],
+        permission: Permission::Curator,
+        description: "cmd-delete_last",
+        handler: |inv| Box::pin(command::delete_last(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "delete_pic",
+        aliases: &[],
+        args: &[
+            ArgSpec {
+                name: "deck",
+                arity: Arity::Required,
+                description: "cmd-delete_pic-deck",
+            },
+            ArgSpec {
+                name: "url",
+                arity: Arity::Required,
+                description: "cmd-delete_pic-url",
+            },
+        ],
+        permission: Permission::Curator,
+        description: "cmd-delete_pic",
+        handler: |inv| Box::pin(command::delete_picture(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "reset_nick",
+        aliases: &[],
+        args: &[ArgSpec {
+            name: "pseudos",
+            arity: Arity::Variadic,
+            description: "cmd-reset_nick-pseudos",
+        }],
+        permission: Permission::Moderator,
+        description: "cmd-reset_nick",
+        handler: |inv| Box::pin(nicknames::reset_nick(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "confirm",
+        aliases: &["oui"],
+        args: &[],
+        permission: Permission::Everyone,
+        description: "cmd-confirm",
+        handler: |inv| Box::pin(confirm::confirm(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "cancel",
+        aliases: &["annuler"],
+        args: &[],
+        permission: Permission::Everyone,
+        description: "cmd-cancel",
+        handler: |inv| Box::pin(confirm::cancel(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "langue",
+        aliases: &["lang"],
+        args: &[ArgSpec {
+            name: "langue",
+            arity: Arity::Required,
+            description: "cmd-langue-langue",
+        }],
+        permission: Permission::Admin,
+        description: "cmd-langue",
+        handler: |inv| Box::pin(command::set_locale(inv)),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "rr",
+        aliases: &[],
+        args: &[],
+        permission: Permission::Admin,
+        description: "cmd-rr",
+        handler: |inv| Box::pin(reaction_roles::list(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "list",
+                aliases: &[],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-rr-list",
+                handler: |inv| Box::pin(reaction_roles::list(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "create",
+                aliases: &[],
+                args: &[
+                    ArgSpec {
+                        name: "texte",
+                        arity: Arity::Required,
+                        description: "cmd-rr-create-texte",
+                    },
+                    ArgSpec {
+                        name: "exclusif",
+                        arity: Arity::Optional,
+                        description: "cmd-rr-create-exclusif",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-rr-create",
+                handler: |inv| Box::pin(reaction_roles::create(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "add",
+                aliases: &[],
+                args: &[
+                    ArgSpec {
+                        name: "message",
+                        arity: Arity::Required,
+                        description: "cmd-rr-message",
+                    },
+                    ArgSpec {
+                        name: "emoji",
+                        arity: Arity::Required,
+                        description: "cmd-rr-emoji",
+                    },
+                    ArgSpec {
+                        name: "role",
+                        arity: Arity::Required,
+                        description: "cmd-rr-role",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-rr-add",
+                handler: |inv| Box::pin(reaction_roles::add(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "remove",
+                aliases: &[],
+                args: &[
+                    ArgSpec {
+                        name: "message",
+                        arity: Arity::Required,
+                        description: "cmd-rr-message",
+                    },
+                    ArgSpec {
+                        name: "emoji",
+                        arity: Arity::Required,
+                        description: "cmd-rr-emoji",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-rr-remove",
+                handler: |inv| Box::pin(reaction_roles::remove(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "exclusive",
+                aliases: &["exclusif"],
+                args: &[
+                    ArgSpec {
+                        name: "message",
+                        arity: Arity::Required,
+                        description: "cmd-rr-message",
+                    },
+                    ArgSpec {
+                        name: "on|off",
+                        arity: Arity::Required,
+                        description: "cmd-rr-exclusive-switch",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-rr-exclusive",
+                handler: |inv| Box::pin(reaction_roles::set_exclusive(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "picker",
+                aliases: &[],
+                args: &[
+                    ArgSpec {
+                        name: "message",
+                        arity: Arity::Required,
+                        description: "cmd-rr-message",
+                    },
+                    ArgSpec {
+                        name: "reactions|buttons|select",
+                        arity: Arity::Required,
+                        description: "cmd-rr-picker-mode",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-rr-picker",
+                handler: |inv| Box::pin(role_picker::set_picker(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "delete",
+                aliases: &[],
+                args: &[ArgSpec {
+                    name: "message",
+                    arity: Arity::Required,
+                    description: "cmd-rr-message",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-rr-delete",
+                handler: |inv| Box::pin(reaction_roles::delete(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "sync",
+                aliases: &[],
+                args: &[ArgSpec {
+                    name: "dry-run",
+                    arity: Arity::Optional,
+                    description: "cmd-rr-sync-dry-run",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-rr-sync",
+                handler: |inv| Box::pin(reaction_roles::sync(inv)),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "guard",
+        aliases: &["garde"],
+        args: &[],
+        permission: Permission::Admin,
+        description: "cmd-guard",
+        handler: |inv| Box::pin(guard::list(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "list",
+                aliases: &[],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-guard-list",
+                handler: |inv| Box::pin(guard::list(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "protect",
+                aliases: &["proteger"],
+                args: &[
+                    ArgSpec {
+                        name: "membre",
+                        arity: Arity::Required,
+                        description: "cmd-guard-membre",
+                    },
+                    ArgSpec {
+                        name: "nom",
+                        arity: Arity::Required,
+                        description: "cmd-guard-protect-nom",
+                    },
+                    ArgSpec {
+                        name: "avertissement",
+                        arity: Arity::Optional,
+                        description: "cmd-guard-protect-avertissement",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-guard-protect",
+                handler: |inv| Box::pin(guard::protect(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "unprotect",
+                aliases: &[],
+                args: &[ArgSpec {
+                    name: "membre",
+                    arity: Arity::Required,
+                    description: "cmd-guard-membre",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-guard-unprotect",
+                handler: |inv| Box::pin(guard::unprotect(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "forgive",
+                aliases: &["pardon"],
+                args: &[ArgSpec {
+                    name: "membre",
+                    arity: Arity::Required,
+                    description: "cmd-guard-membre",
+                }],
+                permission: Permission::Moderator,
+                description: "cmd-guard-forgive",
+                handler: |inv| Box::pin(guard::forgive(inv)),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "audit",
+        aliases: &[],
+        args: &[ArgSpec {
+            name: "filtre",
+            arity: Arity::Optional,
+            description: "cmd-audit-filtre",
+        }],
+        permission: Permission::Moderator,
+        description: "cmd-audit",
+        handler: |inv| Box::pin(audit::show(inv)),
+        subcommands: &[CommandSpec {
+            name: "channel",
+            aliases: &["salon"],
+            args: &[ArgSpec {
+                name: "salon",
+                arity: Arity::Required,
+                description: "cmd-audit-channel-salon",
+            }],
+            permission: Permission::Admin,
+            description: "cmd-audit-channel",
+            handler: |inv| Box::pin(audit::set_channel(inv)),
+            subcommands: &[],
+        }],
+    },
+    CommandSpec {
+        name: "stats",
+        aliases: &["statistiques"],
+        args: &[ArgSpec {
+            name: "période",
+            arity: Arity::Optional,
+            description: "cmd-stats-periode",
+        }],
+        permission: Permission::Everyone,
+        description: "cmd-stats",
+        handler: |inv| Box::pin(stats::show(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "user",
+                aliases: &["membre"],
+                args: &[
+                    ArgSpec {
+                        name: "membre",
+                        arity: Arity::Required,
+                        description: "cmd-stats-membre",
+                    },
+                    ArgSpec {
+                        name: "période",
+                        arity: Arity::Optional,
+                        description: "cmd-stats-periode",
+                    },
+                ],
+                permission: Permission::Everyone,
+                description: "cmd-stats-user",
+                handler: |inv| Box::pin(stats::user(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "unused",
+                aliases: &["inutilises", "inutilisés"],
+                args: &[ArgSpec {
+                    name: "période",
+                    arity: Arity::Optional,
+                    description: "cmd-stats-periode",
+                }],
+                permission: Permission::Curator,
+                description: "cmd-stats-unused",
+                handler: |inv| Box::pin(stats::unused_decks(inv)),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "perm",
+        aliases: &["permissions"],
+        args: &[],
+        permission: Permission::Admin,
+        description: "cmd-perm",
+        handler: |inv| Box::pin(permissions::list(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "list",
+                aliases: &[],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-perm-list",
+                handler: |inv| Box::pin(permissions::list(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "role",
+                aliases: &[],
+                args: &[
+                    ArgSpec {
+                        name: "role",
+                        arity: Arity::Required,
+                        description: "cmd-rr-role",
+                    },
+                    ArgSpec {
+                        name: "niveau",
+                        arity: Arity::Required,
+                        description: "cmd-perm-niveau",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-perm-role",
+                handler: |inv| Box::pin(permissions::set_role(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "user",
+                aliases: &["membre"],
+                args: &[
+                    ArgSpec {
+                        name: "membre",
+                        arity: Arity::Required,
+                        description: "cmd-guard-membre",
+                    },
+                    ArgSpec {
+                        name: "niveau",
+                        arity: Arity::Required,
+                        description: "cmd-perm-niveau",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-perm-user",
+                handler: |inv| Box::pin(permissions::set_user(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "command",
+                aliases: &["commande"],
+                args: &[
+                    ArgSpec {
+                        name: "commande",
+                        arity: Arity::Required,
+                        description: "cmd-perm-command-commande",
+                    },
+                    ArgSpec {
+                        name: "niveau",
+                        arity: Arity::Required,
+                        description: "cmd-perm-niveau",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-perm-command",
+                handler: |inv| Box::pin(permissions::set_command(inv)),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "scope",
+        aliases: &["salons"],
+        args: &[],
+        permission: Permission::Admin,
+        description: "cmd-scope",
+        handler: |inv| Box::pin(scopes::list(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "list",
+                aliases: &[],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-scope-list",
+                handler: |inv| Box::pin(scopes::list(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "allow",
+                aliases: &["autoriser"],
+                args: &[
+                    ArgSpec {
+                        name: "commande",
+                        arity: Arity::Required,
+                        description: "cmd-scope-commande",
+                    },
+                    ArgSpec {
+                        name: "salon",
+                        arity: Arity::Required,
+                        description: "cmd-scope-salon",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-scope-allow",
+                handler: |inv| Box::pin(scopes::allow(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "deny",
+                aliases: &["interdire"],
+                args: &[
+                    ArgSpec {
+                        name: "commande",
+                        arity: Arity::Required,
+                        description: "cmd-scope-commande",
+                    },
+                    ArgSpec {
+                        name: "salon",
+                        arity: Arity::Required,
+                        description: "cmd-scope-salon",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-scope-deny",
+                handler: |inv| Box::pin(scopes::deny(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "clear",
+                aliases: &["effacer"],
+                args: &[ArgSpec {
+                    name: "commande",
+                    arity: Arity::Required,
+                    description: "cmd-scope-commande",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-scope-clear",
+                handler: |inv| Box::pin(scopes::clear(inv)),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "schedule",
+        aliases: &["programme"],
+        args: &[],
+        permission: Permission::Admin,
+        description: "cmd-schedule",
+        handler: |inv| Box::pin(schedules::list(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "list",
+                aliases: &[],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-schedule-list",
+                handler: |inv| Box::pin(schedules::list(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "add",
+                aliases: &["ajouter"],
+                args: &[
+                    ArgSpec {
+                        name: "salon",
+                        arity: Arity::Required,
+                        description: "cmd-schedule-salon",
+                    },
+                    ArgSpec {
+                        name: "cron",
+                        arity: Arity::Required,
+                        description: "cmd-schedule-cron",
+                    },
+                    ArgSpec {
+                        name: "deck",
+                        arity: Arity::Optional,
+                        description: "cmd-schedule-deck",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-schedule-add",
+                handler: |inv| Box::pin(schedules::add(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "remove",
+                aliases: &["retirer"],
+                args: &[ArgSpec {
+                    name: "numéro",
+                    arity: Arity::Required,
+                    description: "cmd-schedule-numero",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-schedule-remove",
+                handler: |inv| Box::pin(schedules::remove(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "timezone",
+                aliases: &["fuseau"],
+                args: &[ArgSpec {
+                    name: "fuseau",
+                    arity: Arity::Required,
+                    description: "cmd-schedule-fuseau",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-schedule-timezone",
+                handler: |inv| Box::pin(schedules::timezone(inv)),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "announce",
+        aliases: &["annonce"],
+        args: &[],
+        permission: Permission::Admin,
+        description: "cmd-announce",
+        handler: |inv| Box::pin(announcements::list(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "list",
+                aliases: &[],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-announce-list",
+                handler: |inv| Box::pin(announcements::list(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "add",
+                aliases: &["ajouter"],
+                args: &[
+                    ArgSpec {
+                        name: "modèle",
+                        arity: Arity::Required,
+                        description: "cmd-announce-modele",
+                    },
+                    ArgSpec {
+                        name: "cron",
+                        arity: Arity::Required,
+                        description: "cmd-schedule-cron",
+                    },
+                    ArgSpec {
+                        name: "salons",
+                        arity: Arity::Variadic,
+                        description: "cmd-announce-salons",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-announce-add",
+                handler: |inv| Box::pin(announcements::add(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "remove",
+                aliases: &["retirer"],
+                args: &[ArgSpec {
+                    name: "numéro",
+                    arity: Arity::Required,
+                    description: "cmd-announce-numero",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-announce-remove",
+                handler: |inv| Box::pin(announcements::remove(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "pause",
+                aliases: &[],
+                args: &[ArgSpec {
+                    name: "numéro",
+                    arity: Arity::Required,
+                    description: "cmd-announce-numero",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-announce-pause",
+                handler: |inv| Box::pin(announcements::pause(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "resume",
+                aliases: &["reprendre"],
+                args: &[ArgSpec {
+                    name: "numéro",
+                    arity: Arity::Required,
+                    description: "cmd-announce-numero",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-announce-resume",
+                handler: |inv| Box::pin(announcements::resume(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "preview",
+                aliases: &["apercu", "aperçu"],
+                args: &[ArgSpec {
+                    name: "numéro",
+                    arity: Arity::Required,
+                    description: "cmd-announce-numero",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-announce-preview",
+                handler: |inv| Box::pin(announcements::preview(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "template",
+                aliases: &["modele", "modèle"],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-announce-template",
+                handler: |inv| Box::pin(announcements::templates(inv)),
+                subcommands: &[
+                    CommandSpec {
+                        name: "set",
+                        aliases: &["ecrire", "écrire"],
+                        args: &[
+                            ArgSpec {
+                                name: "modèle",
+                                arity: Arity::Required,
+                                description: "cmd-announce-modele",
+                            },
+                            ArgSpec {
+                                name: "texte",
+                                arity: Arity::Variadic,
+                                description: "cmd-announce-template-texte",
+                            },
+                        ],
+                        permission: Permission::Admin,
+                        description: "cmd-announce-template-set",
+                        handler: |inv| Box::pin(announcements::set_template(inv)),
+                        subcommands: &[],
+                    },
+                    CommandSpec {
+                        name: "delete",
+                        aliases: &["supprimer"],
+                        args: &[ArgSpec {
+                            name: "modèle",
+                            arity: Arity::Required,
+                            description: "cmd-announce-modele",
+                        }],
+                        permission: Permission::Admin,
+                        description: "cmd-announce-template-delete",
+                        handler: |inv| Box::pin(announcements::delete_template(inv)),
+                        subcommands: &[],
+                    },
+                ],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "cmd",
+        aliases: &["commandes"],
+        args: &[],
+        permission: Permission::Admin,
+        description: "cmd-cmd",
+        handler: |inv| Box::pin(custom::list(inv)),
+        subcommands: &[
+            CommandSpec {
+                name: "list",
+                aliases: &[],
+                args: &[],
+                permission: Permission::Admin,
+                description: "cmd-cmd-list",
+                handler: |inv| Box::pin(custom::list(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "show",
+                aliases: &["voir"],
+                args: &[ArgSpec {
+                    name: "nom",
+                    arity: Arity::Required,
+                    description: "cmd-cmd-nom",
+                }],
+                permission: Permission::Admin,
+                description: "cmd-cmd-show",
+                handler: |inv| Box::pin(custom::show(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "add",
+                aliases: &["ajouter"],
+                args: &[
+                    ArgSpec {
+                        name: "nom",
+                        arity: Arity::Required,
+                        description: "cmd-cmd-nom",
+                    },
+                    ArgSpec {
+                        name: "réponse",
+                        arity: Arity::Variadic,
+                        description: "cmd-cmd-add-reponse",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-cmd-add",
+                handler: |inv| Box::pin(custom::add(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "remove",
+                aliases: &["retirer"],
+                args: &[
+                    ArgSpec {
+                        name: "nom",
+                        arity: Arity::Required,
+                        description: "cmd-cmd-nom",
+                    },
+                    ArgSpec {
+                        name: "numéro",
+                        arity: Arity::Optional,
+                        description: "cmd-cmd-remove-numero",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-cmd-remove",
+                handler: |inv| Box::pin(custom::remove(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "trigger",
+                aliases: &["declencheur"],
+                args: &[
+                    ArgSpec {
+                        name: "nom",
+                        arity: Arity::Required,
+                        description: "cmd-cmd-nom",
+                    },
+                    ArgSpec {
+                        name: "mot-clé",
+                        arity: Arity::Variadic,
+                        description: "cmd-cmd-mot-cle",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-cmd-trigger",
+                handler: |inv| Box::pin(custom::trigger(inv)),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "untrigger",
+                aliases: &[],
+                args: &[
+                    ArgSpec {
+                        name: "nom",
+                        arity: Arity::Required,
+                        description: "cmd-cmd-nom",
+                    },
+                    ArgSpec {
+                        name: "mot-clé",
+                        arity: Arity::Variadic,
+                        description: "cmd-cmd-mot-cle",
+                    },
+                ],
+                permission: Permission::Admin,
+                description: "cmd-cmd-untrigger",
+                handler: |inv| Box::pin(custom::untrigger(inv)),
+                subcommands: &[],
+            },
+        ],
+    },
+];
+
+fn find_in(commands: &'static [CommandSpec], name: &str) -> Option<&'static CommandSpec> {
+    commands
+        .iter()
+        .find(|cmd| cmd.name == name || cmd.aliases.contains(&name))
+}
+
+/// Finds a command by name or alias.
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    find_in(COMMANDS, name)
+}
+
+/// A command once its subcommands were followed.
+pub struct Resolved {
+    /// Full name, such as `rr add`.
+    pub path: String,
+    pub spec: &'static CommandSpec,
+    pub args: Vec<String>,
+}
+
+impl Resolved {
+    pub fn usage(&self) -> String {
+        self.spec.usage_as(&self.path)
+    }
+
+    pub fn help(&self, locale: Locale) -> String {
+        self.spec.help_as(&self.path, locale)
+    }
+}
+
+/// Parses a role given as a mention (`<@&id>`) or a bare id.
+pub fn parse_role(arg: &str) -> Option<Id<RoleMarker>> {
+    let arg = arg
+        .strip_prefix("<@&")
+        .and_then(|arg| arg.strip_suffix('>'))
+        .unwrap_or(arg);
+    arg.parse().ok()
+}
+
+/// Parses a user given as a mention (`<@id>` or `<@!id>`) or a bare id.
+pub fn parse_user(arg: &str) -> Option<Id<UserMarker>> {
+    let arg = arg
+        .strip_prefix("<@")
+        .and_then(|arg| arg.strip_suffix('>'))
+        .map(|arg| arg.strip_prefix('!').unwrap_or(arg))
+        .unwrap_or(arg);
+    arg.parse().ok()
+}
+
+/// Parses a channel given as a mention (`<#id>`) or a bare id.
+pub fn parse_channel(arg: &str) -> Option<Id<ChannelMarker>> {
+    let arg = arg
+        .strip_prefix("<#")
+        .and_then(|arg| arg.strip_suffix('>'))
+        .unwrap_or(arg);
+    arg.parse().ok()
+}
+
+/// Parses an on/off switch, in French or English.
+pub fn parse_switch(arg: &str) -> Option<bool> {
+    match arg.to_lowercase().as_str() {
+        "on" | "oui" | "yes" | "true" => Some(true),
+        "off" | "non" | "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Follows subcommands of `cmd` as long as the first argument names one.
+pub fn resolve(cmd: &'static CommandSpec, mut args: Vec<String>) -> Resolved {
+    let mut path = cmd.name.to_owned();
+    let mut spec = cmd;
+    while let Some(sub) = args
+        .first()
+        .and_then(|first| find_in(spec.subcommands, first))
+    {
+        path.push(' ');
+        path.push_str(sub.name);
+        spec = sub;
+        args.remove(0);
+    }
+    Resolved { path, spec, args }
+}
+
+/// Full name of the command written in `arg`, such as `rr add` for
+/// `"!rr add"`.
+pub fn command_path(inv: &Invocation, arg: &str) -> Result<String, CommandError> {
+    let Ok(words) = tokenize(arg) else {
+        return Err(inv.bad_usage());
+    };
+    let Some((name, sub_args)) = words.split_first() else {
+        return Err(inv.bad_usage());
+    };
+    let name = name.trim_start_matches(PREFIX);
+    let Some(cmd) = find(name) else {
+        return Err(CommandError::NotFound(
+            Message::new("help-unknown-command").arg("name", format!("{}{}", PREFIX, name)),
+        ));
+    };
+    Ok(resolve(cmd, sub_args.to_vec()).path)
+}
+
+/// A message split into a command name and the raw text that follows it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Parsed<'a> {
+    pub name: &'a str,
+    pub rest: &'a str,
+}
+
+/// Splits `!name rest...` into its parts. Returns `None` when the message
+/// isn't addressed to the bot.
+pub fn parse(content: &str) -> Option<Parsed<'_>> {
+    let content = content.strip_prefix(PREFIX)?;
+    let (name, rest) = match content.find(char::is_whitespace) {
+        Some(idx) => (&content[..idx], content[idx..].trim_start()),
+        None => (content, ""),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(Parsed { name, rest })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenizeError {
+    UnterminatedQuote,
+}
+
+impl std::fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnterminatedQuote => write!(f, "guillemet non fermé"),
+        }
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// Splits arguments on whitespace. Double quotes group words into a single
+/// argument and a backslash escapes the next character.
+///
+/// Single quotes are left alone on purpose, they are far too common in
+/// French text to be treated as delimiters.
+pub fn tokenize(input: &str) -> Result<Vec<String>, TokenizeError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut in_quotes = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_token = true;
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_quotes {
+        return Err(TokenizeError::UnterminatedQuote);
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+impl CommandSpec {
+    /// One line summary such as `!delete_pic <deck> <url>`.
+    pub fn usage(&self) -> String {
+        self.usage_as(self.name)
+    }
+
+    /// Usage line of the command when reached through `path`.
+    pub fn usage_as(&self, path: &str) -> String {
+        let mut usage = format!("{}{}", PREFIX, path);
+        if !self.subcommands.is_empty() {
+            let names: Vec<&str> = self.subcommands.iter().map(|sub| sub.name).collect();
+            usage.push_str(&format!(" <{}>", names.join("|")));
+            return usage;
+        }
+        for arg in self.args {
+            match arg.arity {
+                Arity::Required => usage.push_str(&format!(" <{}>", arg.name)),
+                Arity::Optional => usage.push_str(&format!(" [{}]", arg.name)),
+                Arity::Variadic => usage.push_str(&format!(" <{}>...", arg.name)),
+                Arity::Rest => usage.push_str(&format!(" [{}]...", arg.name)),
+            }
+        }
+        usage
+    }
+
+    /// Checks the number of arguments against the schema.
+    pub fn check_args(&self, args: &[String]) -> bool {
+        let required = self
+            .args
+            .iter()
+            .filter(|arg| matches!(arg.arity, Arity::Required | Arity::Variadic))
+            .count();
+        let variadic = self
+            .args
+            .iter()
+            .any(|arg| matches!(arg.arity, Arity::Variadic | Arity::Rest));
+        args.len() >= required && (variadic || args.len() <= self.args.len())
+    }
+
+    /// Detailed help, as shown by `!help <command>`.
+    pub fn help_as(&self, path: &str, locale: Locale) -> String {
+        let mut help = format!(
+            "`{}`\n{}",
+            self.usage_as(path),
+            i18n::tr(locale, self.description, &[])
+        );
+        if !self.aliases.is_empty() {
+            let aliases: Vec<String> = self
+                .aliases
+                .iter()
+                .map(|alias| format!("{}{}", PREFIX, alias))
+                .collect();
+            help.push('\n');
+            help.push_str(
+                &Message::new("help-aliases")
+                    .arg("aliases", aliases.join(", "))
+                    .render(locale),
+            );
+        }
+        for arg in self.args {
+            help.push_str(&format!(
+                "\n- `{}` : {}",
+                arg.name,
+                i18n::tr(locale, arg.description, &[])
+            ));
+        }
+        for sub in self.subcommands {
+            let sub_path = format!("{} {}", path, sub.name);
+            help.push_str(&format!(
+                "\n- `{}` : {}",
+                sub.usage_as(&sub_path),
+                i18n::tr(locale, sub.description, &[])
+            ));
+        }
+        if self.permission != Permission::Everyone {
+            help.push('\n');
+            help.push_str(
+                &Message::new("help-restricted")
+                    .arg("level", self.permission.label(locale))
+                    .render(locale),
+            );
+        }
+        help
+    }
+}
+
+/// Short list of every command, as shown by `!aled`.
+pub fn summary(locale: Locale) -> String {
+    let mut summary = i18n::tr(locale, "help-commands", &[]);
+    for cmd in COMMANDS {
+        summary.push_str(&format!(
+            "\n`{}` : {}",
+            cmd.usage(),
+            i18n::tr(locale, cmd.description, &[])
+        ));
+    }
+    summary.push('\n');
+    summary.push_str(
+        &Message::new("help-footer")
+            .arg("help", format!("{}help <commande>", PREFIX))
+            .render(locale),
+    );
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_splits_name_and_rest() {
+        assert_eq!(
+            parse("!delete_pic  mood http://a.png"),
+            Some(Parsed {
+                name: "delete_pic",
+                rest: "mood http://a.png"
+            })
+        );
+        assert_eq!(
+            parse("!aled"),
+            Some(Parsed {
+                name: "aled",
+                rest: ""
+            })
+        );
+        assert_eq!(parse("!"), None);
+        assert_eq!(parse("hello"), None);
+    }
+
+    #[test]
+    fn find_matches_whole_names_only() {
+        assert!(find("add").is_some());
+        assert!(find("addition").is_none());
+        assert_eq!(find("help").map(|cmd| cmd.name), Some("aled"));
+    }
+
+    #[test]
+    fn tokenize_quotes_and_escapes() {
+        assert_eq!(
+            tokenize(r#"one "two three"  fo\"ur l'autre"#).unwrap(),
+            vec!["one", "two three", "fo\"ur", "l'autre"]
+        );
+        assert_eq!(tokenize(r#""""#).unwrap(), vec![""]);
+        assert_eq!(tokenize("   ").unwrap(), Vec::<String>::new());
+        assert_eq!(
+            tokenize(r#"oops "unterminated"#),
+            Err(TokenizeError::UnterminatedQuote)
+        );
+    }
+
+    #[test]
+    fn check_args_follows_schema() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let delete_pic = find("delete_pic").unwrap();
+        assert!(delete_pic.check_args(&args(&["mood", "url"])));
+        assert!(!delete_pic.check_args(&args(&["mood"])));
+        assert!(!delete_pic.check_args(&args(&["mood", "url", "extra"])));
+
+        let reset_nick = find("reset_nick").unwrap();
+        assert!(!reset_nick.check_args(&args(&[])));
+        assert!(reset_nick.check_args(&args(&["a", "b", "c"])));
+
+        let aled = find("aled").unwrap();
+        assert!(aled.check_args(&args(&[])));
+        assert!(aled.check_args(&args(&["add"])));
+    }
+
+    fn check_catalog(commands: &[CommandSpec]) {
+        for locale in Locale::ALL {
+            for cmd in commands {
+                assert!(i18n::has_message(locale, cmd.description));
+                for arg in cmd.args {
+                    assert!(i18n::has_message(locale, arg.description));
+                }
+            }
+        }
+        for cmd in commands {
+            check_catalog(cmd.subcommands);
+        }
+    }
+
+    #[test]
+    fn descriptions_are_in_the_catalog() {
+        check_catalog(COMMANDS);
+    }
+
+    #[test]
+    fn resolve_follows_subcommands() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let rr = find("rr").unwrap();
+        let resolved = resolve(rr, args(&["add", "1", "🌻", "<@&2>"]));
+        assert_eq!(resolved.path, "rr add");
+        assert_eq!(resolved.args, args(&["1", "🌻", "<@&2>"]));
+        assert_eq!(resolved.usage(), "!rr add <message> <emoji> <role>");
+
+        let resolved = resolve(rr, args(&["nope"]));
+        assert_eq!(resolved.path, "rr");
+        assert_eq!(
+            resolved.usage(),
+            "!rr <list|create|add|remove|exclusive|picker|delete|sync>"
+        );
+    }
+
+    #[test]
+    fn parse_role_mentions() {
+        assert_eq!(parse_role("<@&42>"), Some(Id::new(42)));
+        assert_eq!(parse_role("42"), Some(Id::new(42)));
+        assert_eq!(parse_role("<@42>"), None);
+    }
+
+    #[test]
+    fn parse_user_mentions() {
+        assert_eq!(parse_user("<@42>"), Some(Id::new(42)));
+        assert_eq!(parse_user("<@!42>"), Some(Id::new(42)));
+        assert_eq!(parse_user("42"), Some(Id::new(42)));
+        assert_eq!(parse_user("<@&42>"), None);
+    }
+
+    #[test]
+    fn parse_channel_mentions() {
+        assert_eq!(parse_channel("<#42>"), Some(Id::new(42)));
+        assert_eq!(parse_channel("<@42>"), None);
+    }
+
+    #[test]
+    fn usage_is_generated() {
+        assert_eq!(
+            find("delete_pic").unwrap().usage(),
+            "!delete_pic <deck> <url>"
+        );
+        assert_eq!(
+            find("reset_nick").unwrap().usage(),
+            "!reset_nick <pseudos>..."
+        );
+        assert_eq!(find("aled").unwrap().usage(), "!aled [commande]...");
+    }
+}
@@ -0,0 +1,525 @@
+//! Role picker made of message components: members click a button or pick
+//! their roles in a select menu instead of reacting.
+//!
+//! It is another way to display a reaction role message, chosen with
+//! `!rr picker`, and shares the message's roles and exclusivity. Clicks come
+//! in as interactions and get an ephemeral reply listing what changed.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+use twilight_model::application::interaction::{Interaction, InteractionData};
+use twilight_model::channel::message::component::{
+    ActionRow, Button, ButtonStyle, SelectMenu, SelectMenuOption,
+};
+use twilight_model::channel::message::{Component, MessageFlags};
+use twilight_model::http::interaction::{
+    InteractionResponse, InteractionResponseData, InteractionResponseType,
+};
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::lookup;
+use super::reaction_roles::{ReactionRole, ReactionRoleMessage, RoleChange};
+use super::registry::{CommandResult, Invocation};
+use super::BotState;
+use crate::i18n::{Locale, Message};
+
+/// How a reaction role message lets members pick their roles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Picker {
+    #[default]
+    Reactions,
+    /// One button per role, clicking it toggles the role.
+    Buttons,
+    /// A select menu holding every role.
+    Select,
+}
+
+impl Picker {
+    fn parse(arg: &str) -> Option<Picker> {
+        match arg {
+            "reactions" | "réactions" => Some(Self::Reactions),
+            "buttons" | "boutons" => Some(Self::Buttons),
+            "select" | "menu" => Some(Self::Select),
+            _ => None,
+        }
+    }
+}
+
+/// Buttons hold `rr:<role id>` as custom id, the select menu `rr:select`.
+const CUSTOM_ID_PREFIX: &str = "rr:";
+const SELECT_CUSTOM_ID: &str = "rr:select";
+
+/// Discord allows 5 buttons per row and 5 rows per message.
+const BUTTONS_PER_ROW: usize = 5;
+const MAX_ROWS: usize = 5;
+/// Discord allows 25 options per select menu.
+const MAX_OPTIONS: usize = 25;
+
+/// Roles of `rr` its picker has room to show, the others cannot be picked.
+fn shown_roles(rr: &ReactionRoleMessage) -> &[ReactionRole] {
+    let limit = match rr.picker {
+        Picker::Reactions => usize::MAX,
+        Picker::Buttons => BUTTONS_PER_ROW * MAX_ROWS,
+        Picker::Select => MAX_OPTIONS,
+    };
+    &rr.roles[..rr.roles.len().min(limit)]
+}
+
+/// Builds the components displaying `rr`. Roles missing from `role_names`
+/// are labelled with their id.
+pub fn components(
+    rr: &ReactionRoleMessage,
+    role_names: &HashMap<Id<RoleMarker>, String>,
+    locale: Locale,
+) -> Vec<Component> {
+    let label = |role: &ReactionRole| {
+        role_names
+            .get(&role.role_id)
+            .cloned()
+            .unwrap_or_else(|| role.role_id.to_string())
+    };
+    match rr.picker {
+        Picker::Reactions => Vec::new(),
+        Picker::Buttons => shown_roles(rr)
+            .chunks(BUTTONS_PER_ROW)
+            .map(|row| {
+                let buttons = row
+                    .iter()
+                    .map(|role| {
+                        Component::Button(Button {
+                            custom_id: Some(format!("{}{}", CUSTOM_ID_PREFIX, role.role_id)),
+                            disabled: false,
+                            emoji: Some(role.emoji.reaction_type()),
+                            label: Some(label(role)),
+                            style: ButtonStyle::Secondary,
+                            url: None,
+                        })
+                    })
+                    .collect();
+                Component::ActionRow(ActionRow {
+                    components: buttons,
+                })
+            })
+            .collect(),
+        Picker::Select if rr.roles.is_empty() => Vec::new(),
+        Picker::Select => {
+            let options: Vec<SelectMenuOption> = shown_roles(rr)
+                .iter()
+                .map(|role| SelectMenuOption {
+                    default: false,
+                    description: None,
+                    emoji: Some(role.emoji.reaction_type()),
+                    label: label(role),
+                    value: role.role_id.to_string(),
+                })
+                .collect();
+            let max_values = if rr.exclusive { 1 } else { options.len() as u8 };
+            let menu = SelectMenu {
+                custom_id: SELECT_CUSTOM_ID.to_owned(),
+                disabled: false,
+                max_values: Some(max_values),
+                min_values: Some(0),
+                options,
+                placeholder: Some(Message::new("picker-placeholder").render(locale)),
+            };
+            vec![Component::ActionRow(ActionRow {
+                components: vec![Component::SelectMenu(menu)],
+            })]
+        }
+    }
+}
+
+/// Replaces the components of `rr`'s message to match its picker and roles.
+pub async fn render(
+    http: &dyn Discord,
+    state: &BotState,
+    rr: &ReactionRoleMessage,
+    channel_id: Id<ChannelMarker>,
+    locale: Locale,
+) -> Result<(), CommandError> {
+    if rr.roles.len() > shown_roles(rr).len() {
+        tracing::warn!(message = %rr.message_id, roles = rr.roles.len(), "too many roles for the picker, some are not shown");
+    }
+    let role_names = lookup::role_names(http, state, rr.guild_id).await?;
+    let components = components(rr, &role_names, locale);
+    http.update_components(channel_id, rr.message_id, &components)
+        .await?;
+    Ok(())
+}
+
+/// Role changes for a member holding `held` who clicked the button of
+/// `clicked`: the role is toggled, and on exclusive messages picking a role
+/// drops the others. Only the roles shown by the picker are changed.
+pub fn toggle_changes(
+    rr: &ReactionRoleMessage,
+    user_id: Id<UserMarker>,
+    held: &[Id<RoleMarker>],
+    clicked: Id<RoleMarker>,
+) -> Vec<RoleChange> {
+    let shown = shown_roles(rr);
+    if !shown.iter().any(|role| role.role_id == clicked) {
+        return Vec::new();
+    }
+    if held.contains(&clicked) {
+        return vec![RoleChange {
+            user_id,
+            role_id: clicked,
+            add: false,
+        }];
+    }
+    let mut changes = vec![RoleChange {
+        user_id,
+        role_id: clicked,
+        add: true,
+    }];
+    if rr.exclusive {
+        changes.extend(
+            shown
+                .iter()
+                .filter(|role| role.role_id != clicked && held.contains(&role.role_id))
+                .map(|role| RoleChange {
+                    user_id,
+                    role_id: role.role_id,
+                    add: false,
+                }),
+        );
+    }
+    changes
+}
+
+/// Role changes for a member holding `held` who picked `selected` in the
+/// select menu: they end up with exactly the selected roles among those the
+/// menu shows.
+pub fn select_changes(
+    rr: &ReactionRoleMessage,
+    user_id: Id<UserMarker>,
+    held: &[Id<RoleMarker>],
+    selected: &[Id<RoleMarker>],
+) -> Vec<RoleChange> {
+    let selected = if rr.exclusive && selected.len() > 1 {
+        &selected[..1]
+    } else {
+        selected
+    };
+    shown_roles(rr)
+        .iter()
+        .filter_map(|role| {
+            let wanted = selected.contains(&role.role_id);
+            (wanted != held.contains(&role.role_id)).then_some(RoleChange {
+                user_id,
+                role_id: role.role_id,
+                add: wanted,
+            })
+        })
+        .collect()
+}
+
+fn mentions(roles: &[Id<RoleMarker>]) -> String {
+    roles
+        .iter()
+        .map(|role_id| format!("<@&{}>", role_id))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Acknowledges `interaction` with an ephemeral "thinking" message, to be
+/// replaced by [`respond`] once the roles are changed.
+async fn defer(http: &dyn Discord, interaction: &Interaction) -> Result<(), CommandError> {
+    let response = InteractionResponse {
+        kind: InteractionResponseType::DeferredChannelMessageWithSource,
+        data: Some(InteractionResponseData {
+            flags: Some(MessageFlags::EPHEMERAL),
+            ..Default::default()
+        }),
+    };
+    http.respond_to_interaction(
+        interaction.application_id,
+        interaction.id,
+        &interaction.token,
+        &response,
+    )
+    .await?;
+    Ok(())
+}
+
+async fn respond(
+    http: &dyn Discord,
+    interaction: &Interaction,
+    content: String,
+) -> Result<(), CommandError> {
+    http.update_interaction_response(interaction.application_id, &interaction.token, &content)
+        .await?;
+    Ok(())
+}
+
+fn find_message(state: &BotState, message_id: Id<MessageMarker>) -> Option<ReactionRoleMessage> {
+    let config = state.config.lock().ok()?;
+    config
+        .reaction_roles
+        .iter()
+        .find(|rr| rr.message_id == message_id)
+        .cloned()
+}
+
+pub async fn on_interaction(
+    interaction: &Interaction,
+    http: &dyn Discord,
+    state: &BotState,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(InteractionData::MessageComponent(data)) = &interaction.data else {
+        return Ok(());
+    };
+    let Some(clicked) = data.custom_id.strip_prefix(CUSTOM_ID_PREFIX) else {
+        return Ok(());
+    };
+    let (Some(message), Some(member)) = (&interaction.message, &interaction.member) else {
+        return Ok(());
+    };
+    let Some(user_id) = interaction.author_id() else {
+        return Ok(());
+    };
+    let locale = interaction
+        .locale
+        .as_deref()
+        .and_then(Locale::from_tag)
+        .unwrap_or_else(|| state.locale(interaction.guild_id));
+    // Discord waits three seconds for an answer, changing roles may take
+    // longer.
+    defer(http, interaction).await?;
+
+    let Some(rr) = find_message(state, message.id) else {
+        let content = Message::new("picker-unknown").render(locale);
+        respond(http, interaction, content).await?;
+        return Ok(());
+    };
+
+    let changes = if data.custom_id == SELECT_CUSTOM_ID {
+        let selected: Vec<Id<RoleMarker>> = data
+            .values
+            .iter()
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        select_changes(&rr, user_id, &member.roles, &selected)
+    } else {
+        match clicked.parse() {
+            Ok(clicked) => toggle_changes(&rr, user_id, &member.roles, clicked),
+            Err(_) => Vec::new(),
+        }
+    };
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut failed = Vec::new();
+    for change in changes {
+        let result = if change.add {
+            http.add_role(rr.guild_id, user_id, change.role_id).await
+        } else {
+            http.remove_role(rr.guild_id, user_id, change.role_id).await
+        };
+        match result {
+            Ok(_) if change.add => added.push(change.role_id),
+            Ok(_) => removed.push(change.role_id),
+            Err(err) => {
+                tracing::warn!(%change, %err, "failed to apply role picker change");
+                failed.push(change.role_id);
+            }
+        }
+        tracing::info!(%change, "role picker");
+    }
+
+    let mut lines = Vec::new();
+    for (key, roles) in [
+        ("picker-added", &added),
+        ("picker-removed", &removed),
+        ("picker-failed", &failed),
+    ] {
+        if !roles.is_empty() {
+            lines.push(
+                Message::new(key)
+                    .arg("roles", mentions(roles))
+                    .render(locale),
+            );
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Message::new("picker-unchanged").render(locale));
+    }
+    respond(http, interaction, lines.join("\n")).await?;
+    Ok(())
+}
+
+pub async fn set_picker(inv: Invocation) -> CommandResult {
+    let message_id: Id<MessageMarker> = inv.args[0].parse().map_err(|_| inv.bad_usage())?;
+    let Some(picker) = Picker::parse(&inv.args[1]) else {
+        return Err(inv.bad_usage());
+    };
+
+    let rr = inv.state.update_config(|config| {
+        let rr = config
+            .reaction_roles
+            .iter_mut()
+            .find(|rr| rr.message_id == message_id)?;
+        rr.picker = picker;
+        Some(rr.clone())
+    })?;
+    let Some(rr) = rr else {
+        return Err(CommandError::NotFound(
+            Message::new("rr-unknown-message").arg("message", message_id.to_string()),
+        ));
+    };
+    let Some(channel_id) = rr.channel_id else {
+        return Err(CommandError::NotFound(
+            Message::new("picker-no-channel").arg("message", message_id.to_string()),
+        ));
+    };
+    render(&*inv.http, &inv.state, &rr, channel_id, inv.locale).await?;
+    let record = AuditRecord::new(
+        Action::ReactionRoles,
+        Some(inv.msg.author.id),
+        message_id.to_string(),
+    )
+    .after(format!("picker: {:?}", picker).to_lowercase());
+    audit::log(&*inv.http, &inv.state, record).await;
+
+    let response = Message::new("picker-set").arg("message", message_id.to_string());
+    reply_in_chann(&*inv.http, &inv.msg, &response.render(inv.locale)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::reaction_roles::RoleEmoji;
+
+    fn message(exclusive: bool, picker: Picker) -> ReactionRoleMessage {
+        let role = |emoji: &str, role_id| ReactionRole {
+            emoji: RoleEmoji::Unicode(emoji.to_owned()),
+            role_id: Id::new(role_id),
+        };
+        ReactionRoleMessage {
+            guild_id: Id::new(1),
+            channel_id: Some(Id::new(2)),
+            message_id: Id::new(3),
+            exclusive,
+            picker,
+            roles: vec![role("🌻", 10), role("🌸", 11), role("🍀", 12)],
+        }
+    }
+
+    fn change(role_id: u64, add: bool) -> RoleChange {
+        RoleChange {
+            user_id: Id::new(100),
+            role_id: Id::new(role_id),
+            add,
+        }
+    }
+
+    #[test]
+    fn toggle_adds_and_removes() {
+        let rr = message(false, Picker::Buttons);
+        let user = Id::new(100);
+        assert_eq!(
+            toggle_changes(&rr, user, &[Id::new(11)], Id::new(10)),
+            vec![change(10, true)]
+        );
+        assert_eq!(
+            toggle_changes(&rr, user, &[Id::new(10)], Id::new(10)),
+            vec![change(10, false)]
+        );
+        assert!(toggle_changes(&rr, user, &[], Id::new(50)).is_empty());
+    }
+
+    #[test]
+    fn toggle_exclusive_drops_other_roles() {
+        let rr = message(true, Picker::Buttons);
+        assert_eq!(
+            toggle_changes(&rr, Id::new(100), &[Id::new(11), Id::new(50)], Id::new(10)),
+            vec![change(10, true), change(11, false)]
+        );
+    }
+
+    #[test]
+    fn select_sets_exact_roles() {
+        let rr = message(false, Picker::Select);
+        assert_eq!(
+            select_changes(
+                &rr,
+                Id::new(100),
+                &[Id::new(11), Id::new(50)],
+                &[Id::new(10), Id::new(11)]
+            ),
+            vec![change(10, true)]
+        );
+        assert_eq!(
+            select_changes(&rr, Id::new(100), &[Id::new(11), Id::new(12)], &[]),
+            vec![change(11, false), change(12, false)]
+        );
+        let exclusive = message(true, Picker::Select);
+        assert_eq!(
+            select_changes(&exclusive, Id::new(100), &[], &[Id::new(12), Id::new(10)]),
+            vec![change(12, true)]
+        );
+    }
+
+    #[test]
+    fn hidden_roles_are_left_alone() {
+        let mut rr = message(false, Picker::Select);
+        rr.roles = (0..MAX_OPTIONS as u64 + 1)
+            .map(|n| ReactionRole {
+                emoji: RoleEmoji::Unicode("🌻".to_owned()),
+                role_id: Id::new(10 + n),
+            })
+            .collect();
+        let hidden = Id::new(10 + MAX_OPTIONS as u64);
+        assert_eq!(
+            select_changes(&rr, Id::new(100), &[hidden], &[Id::new(10)]),
+            vec![change(10, true)]
+        );
+        rr.picker = Picker::Buttons;
+        rr.exclusive = true;
+        assert!(toggle_changes(&rr, Id::new(100), &[], hidden).is_empty());
+        assert_eq!(
+            toggle_changes(&rr, Id::new(100), &[hidden], Id::new(10)),
+            vec![change(10, true)]
+        );
+    }
+
+    #[test]
+    fn components_follow_picker() {
+        let names = HashMap::from([(Id::new(10), "il".to_owned())]);
+        assert!(components(&message(false, Picker::Reactions), &names, Locale::Fr).is_empty());
+
+        let buttons = components(&message(false, Picker::Buttons), &names, Locale::Fr);
+        let [Component::ActionRow(row)] = buttons.as_slice() else {
+            panic!("expected a single row, got {:?}", buttons);
+        };
+        let Component::Button(first) = &row.components[0] else {
+            panic!("expected a button");
+        };
+        assert_eq!(first.custom_id.as_deref(), Some("rr:10"));
+        assert_eq!(first.label.as_deref(), Some("il"));
+        let Component::Button(second) = &row.components[1] else {
+            panic!("expected a button");
+        };
+        assert_eq!(second.label.as_deref(), Some("11"));
+
+        let select = components(&message(true, Picker::Select), &names, Locale::Fr);
+        let [Component::ActionRow(row)] = select.as_slice() else {
+            panic!("expected a single row, got {:?}", select);
+        };
+        let Component::SelectMenu(menu) = &row.components[0] else {
+            panic!("expected a select menu");
+        };
+        assert_eq!(menu.options.len(), 3);
+        assert_eq!(menu.max_values, Some(1));
+    }
+}
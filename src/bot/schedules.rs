@@ -0,0 +1,473 @@
+//! Scheduled pictures: the bot posts a random picture to a channel at set
+//! times, such as a picture of the day.
+//!
+//! Times are cron expressions read in the configured timezone. Schedules
+//! are checked at least every minute, so changes apply without a restart.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::discord::Discord;
+use super::error::CommandError;
+use super::lookup;
+use super::outbox::Outgoing;
+use super::registry::{self, CommandResult, Invocation};
+use super::BotState;
+use crate::config::Config;
+use crate::i18n::Message;
+
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Europe::Paris;
+/// Longest wait between two checks.
+const CHECK_EVERY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: u32,
+    pub channel: Id<ChannelMarker>,
+    /// Cron expression, with or without seconds.
+    pub cron: String,
+    /// Deck to pick from. Every deck when none, each picture with the same
+    /// chance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deck: Option<String>,
+}
+
+impl Schedule {
+    /// Next time it runs after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>, timezone: Tz) -> Option<DateTime<Utc>> {
+        next_run(&self.cron, after, timezone)
+    }
+
+    /// Whether it runs after `from`, up to `to` included.
+    pub fn is_due(&self, from: DateTime<Utc>, to: DateTime<Utc>, timezone: Tz) -> bool {
+        self.next_after(from, timezone)
+            .is_some_and(|next| next <= to)
+    }
+
+    fn describe(&self, timezone: Tz) -> String {
+        let deck = match &self.deck {
+            Some(deck) => deck.clone(),
+            None => "*".to_owned(),
+        };
+        let mut line = format!("`{}` <#{}> `{}` {}", self.id, self.channel, self.cron, deck);
+        if let Some(next) = self.next_after(Utc::now(), timezone) {
+            line.push_str(&format!(" → <t:{}:f>", next.timestamp()));
+        }
+        line
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Timezone of the cron expressions, an IANA name.
+    pub timezone: Tz,
+    pub schedules: Vec<Schedule>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            timezone: DEFAULT_TIMEZONE,
+            schedules: Vec::new(),
+        }
+    }
+}
+
+impl ScheduleConfig {
+    fn next_id(&self) -> u32 {
+        self.schedules
+            .iter()
+            .map(|schedule| schedule.id + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Schedules running after `from`, up to `to` included.
+    pub fn due(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<&Schedule> {
+        self.schedules
+            .iter()
+            .filter(|schedule| schedule.is_due(from, to, self.timezone))
+            .collect()
+    }
+
+    /// Next time any schedule runs after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedules
+            .iter()
+            .filter_map(|schedule| schedule.next_after(after, self.timezone))
+            .min()
+    }
+}
+
+/// Parses a cron expression. The usual five fields, starting with minutes,
+/// are accepted as well as the six or seven of the `cron` crate.
+pub fn parse_cron(expr: &str) -> Option<cron::Schedule> {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let expr = match fields.as_slice() {
+        [minutes, hours, days, months, weekdays] => format!(
+            "0 {} {} {} {} {}",
+            minutes,
+            hours,
+            days,
+            months,
+            crontab_weekdays(weekdays)?
+        ),
+        _ => expr.trim().to_owned(),
+    };
+    cron::Schedule::from_str(&expr).ok()
+}
+
+/// Converts a crontab weekday field, where 0 and 7 are Sunday and 1 is
+/// Monday, to the `cron` crate's, where 1 is Sunday. Names are kept.
+fn crontab_weekdays(field: &str) -> Option<String> {
+    let convert = |day: u8| if day == 7 { 1 } else { day + 1 };
+    let items: Option<Vec<String>> = field
+        .split(',')
+        .map(|item| {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (item, None),
+            };
+            if !range.chars().all(|c| c.is_ascii_digit() || c == '-') {
+                return Some(item.to_owned());
+            }
+            let days: Option<Vec<u8>> = range
+                .split('-')
+                .map(|day| day.parse().ok().filter(|day| *day <= 7))
+                .collect();
+            let range = match days?.as_slice() {
+                [day] => convert(*day).to_string(),
+                [0, 7] => "1-7".to_owned(),
+                // Ranges ending on Sunday wrap around in the `cron` crate's
+                // numbering, which is only written without a step.
+                [start, 7] if step.is_none() => format!("{}-7,1", start + 1),
+                [start, end] if start <= end && *end < 7 => {
+                    format!("{}-{}", convert(*start), convert(*end))
+                }
+                _ => return None,
+            };
+            Some(match step {
+                Some(step) => format!("{}/{}", range, step),
+                None => range,
+            })
+        })
+        .collect();
+    Some(items?.join(","))
+}
+
+/// Next time the cron expression `cron` runs after `after`.
+pub fn next_run(cron: &str, after: DateTime<Utc>, timezone: Tz) -> Option<DateTime<Utc>> {
+    parse_cron(cron)?
+        .after(&after.with_timezone(&timezone))
+        .next()
+        .map(|next| next.with_timezone(&Utc))
+}
+
+/// Sleeps until the next run after `last` given by `next`, or for
+/// [`CHECK_EVERY`] if that is sooner, and returns the time it woke up.
+pub async fn sleep_until_next(
+    state: &BotState,
+    last: DateTime<Utc>,
+    next: impl Fn(&Config, DateTime<Utc>) -> Option<DateTime<Utc>>,
+) -> DateTime<Utc> {
+    let next = match state.config.lock() {
+        Ok(config) => next(&config, last),
+        Err(_) => None,
+    };
+    let wait = next
+        .and_then(|next| (next - Utc::now()).to_std().ok())
+        .map_or(CHECK_EVERY, |wait| wait.min(CHECK_EVERY));
+    tokio::time::sleep(wait).await;
+    Utc::now()
+}
+
+/// Posts scheduled pictures, forever.
+pub async fn run(http: Arc<dyn Discord>, state: BotState) {
+    let mut last = Utc::now();
+    loop {
+        let now = sleep_until_next(&state, last, |config, after| {
+            config.schedules.next_after(after)
+        })
+        .await;
+        let due: Vec<Schedule> = match state.config.lock() {
+            Ok(config) => config
+                .schedules
+                .due(last, now)
+                .into_iter()
+                .cloned()
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        last = now;
+        for schedule in due {
+            if let Err(err) = post(&*http, &state, &schedule).await {
+                tracing::warn!(%err, id = schedule.id, "failed to post scheduled picture");
+            }
+        }
+    }
+}
+
+async fn post(http: &dyn Discord, state: &BotState, schedule: &Schedule) -> CommandResult {
+    let link = {
+        let mut album = state.album.lock()?;
+        let deck = match &schedule.deck {
+            Some(deck) => Some(deck.clone()),
+            None => album.get_rand_deck().map(str::to_owned),
+        };
+        deck.and_then(|deck| album.get_rand_pic(&deck).map(str::to_owned))
+    };
+    let Some(link) = link else {
+        tracing::warn!(id = schedule.id, deck = ?schedule.deck, "no picture to post");
+        return Ok(());
+    };
+    tracing::info!(id = schedule.id, %link, "posting scheduled picture");
+    Outgoing::new(schedule.channel)
+        .content(&link)
+        .send(http)
+        .await?;
+    Ok(())
+}
+
+pub async fn list(inv: Invocation) -> CommandResult {
+    let config = inv.state.config.lock()?.schedules.clone();
+    let response = if config.schedules.is_empty() {
+        Message::new("schedule-list-empty").render(inv.locale)
+    } else {
+        let mut response = Message::new("schedule-list-header")
+            .arg("timezone", config.timezone.name())
+            .render(inv.locale);
+        for schedule in &config.schedules {
+            response.push_str("\n- ");
+            response.push_str(&schedule.describe(config.timezone));
+        }
+        response
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn add(inv: Invocation) -> CommandResult {
+    let Some(channel_id) = registry::parse_channel(&inv.args[0]) else {
+        return Err(inv.bad_usage());
+    };
+    let cron = inv.args[1].trim().to_owned();
+    if parse_cron(&cron).is_none() {
+        return Err(CommandError::NotFound(
+            Message::new("schedule-bad-cron").arg("cron", cron),
+        ));
+    }
+    let deck = match inv.args.get(2) {
+        None => None,
+        Some(deck) => match inv.state.album.lock()?.deck(deck) {
+            Some((name, _)) => Some(name.to_owned()),
+            None => {
+                return Err(CommandError::NotFound(
+                    Message::new("schedule-unknown-deck").arg("deck", deck.as_str()),
+                ))
+            }
+        },
+    };
+    let guild_id = lookup::channel_guild(&*inv.http, &inv.state, channel_id).await?;
+    if guild_id.is_none() || guild_id != inv.msg.guild_id {
+        return Err(CommandError::NotFound(
+            Message::new("schedule-unknown-channel").arg("channel", channel_id.to_string()),
+        ));
+    }
+
+    let (schedule, timezone) = inv.state.update_config(|config| {
+        let schedule = Schedule {
+            id: config.schedules.next_id(),
+            channel: channel_id,
+            cron,
+            deck,
+        };
+        config.schedules.schedules.push(schedule.clone());
+        (schedule, config.schedules.timezone)
+    })?;
+    let description = schedule.describe(timezone);
+    let record = AuditRecord::new(
+        Action::Schedules,
+        Some(inv.msg.author.id),
+        format!("<#{}>", channel_id),
+    )
+    .after(description.as_str());
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("schedule-added")
+        .arg("schedule", description)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn remove(inv: Invocation) -> CommandResult {
+    let Ok(id) = inv.args[0].parse::<u32>() else {
+        return Err(inv.bad_usage());
+    };
+    let removed = inv.state.update_config(|config| {
+        let index = config
+            .schedules
+            .schedules
+            .iter()
+            .position(|schedule| schedule.id == id)?;
+        let schedule = config.schedules.schedules.remove(index);
+        Some(schedule.describe(config.schedules.timezone))
+    })?;
+    let Some(description) = removed else {
+        return Err(CommandError::NotFound(
+            Message::new("schedule-unknown").arg("id", id.to_string()),
+        ));
+    };
+    let record = AuditRecord::new(
+        Action::Schedules,
+        Some(inv.msg.author.id),
+        format!("#{}", id),
+    )
+    .before(description);
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("schedule-removed")
+        .arg("id", id.to_string())
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn timezone(inv: Invocation) -> CommandResult {
+    let Ok(timezone) = inv.args[0].parse::<Tz>() else {
+        return Err(CommandError::NotFound(
+            Message::new("schedule-bad-timezone").arg("timezone", inv.args[0].as_str()),
+        ));
+    };
+    let before = inv
+        .state
+        .update_config(|config| std::mem::replace(&mut config.schedules.timezone, timezone))?;
+    let record = AuditRecord::new(Action::Schedules, Some(inv.msg.author.id), "timezone")
+        .before(before.name())
+        .after(timezone.name());
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("schedule-timezone-set")
+        .arg("timezone", timezone.name())
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn schedule(id: u32, cron: &str) -> Schedule {
+        Schedule {
+            id,
+            channel: Id::new(1),
+            cron: cron.to_owned(),
+            deck: None,
+        }
+    }
+
+    #[test]
+    fn cron_expressions_parse() {
+        assert!(parse_cron("0 9 * * *").is_some());
+        assert!(parse_cron("0 0 9 * * Mon-Fri").is_some());
+        assert!(parse_cron("tous les jours").is_none());
+        assert!(parse_cron("61 9 * * *").is_none());
+        assert!(parse_cron("0 9 * * 8").is_none());
+    }
+
+    #[test]
+    fn crontab_weekdays_start_on_sunday() {
+        use chrono::{Datelike, Weekday};
+
+        // 2024-03-03 is a Sunday.
+        let sunday = Utc.with_ymd_and_hms(2024, 3, 3, 0, 0, 0).unwrap();
+        let weekdays = |cron: &str| -> Vec<Weekday> {
+            parse_cron(cron)
+                .unwrap()
+                .after(&sunday)
+                .take(7)
+                .map(|next| next.weekday())
+                .collect()
+        };
+        assert_eq!(
+            weekdays("0 9 * * 1-5"),
+            [
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri
+            ]
+            .repeat(2)[..7]
+        );
+        assert_eq!(weekdays("0 9 * * 0")[..2], [Weekday::Sun, Weekday::Sun]);
+        assert_eq!(weekdays("0 9 * * 7")[0], Weekday::Sun);
+        assert_eq!(
+            weekdays("0 9 * * 5-7")[..3],
+            [Weekday::Sun, Weekday::Fri, Weekday::Sat]
+        );
+        assert_eq!(weekdays("0 9 * * 1,3")[..2], [Weekday::Mon, Weekday::Wed]);
+        assert_eq!(weekdays("0 9 * * Mon-Fri"), weekdays("0 9 * * 1-5"));
+    }
+
+    #[test]
+    fn runs_in_the_timezone() {
+        let every_day = schedule(1, "0 9 * * *");
+        let paris = DEFAULT_TIMEZONE;
+        // 9:00 in Paris is 7:00 UTC in summer and 8:00 UTC in winter.
+        let summer = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            every_day.next_after(summer, paris),
+            Some(Utc.with_ymd_and_hms(2024, 7, 1, 7, 0, 0).unwrap())
+        );
+        let winter = Utc.with_ymd_and_hms(2024, 12, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            every_day.next_after(winter, paris),
+            Some(Utc.with_ymd_and_hms(2024, 12, 1, 8, 0, 0).unwrap())
+        );
+        assert_eq!(
+            every_day.next_after(winter, chrono_tz::UTC),
+            Some(Utc.with_ymd_and_hms(2024, 12, 1, 9, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn due_schedules() {
+        let config = ScheduleConfig {
+            timezone: chrono_tz::UTC,
+            schedules: vec![
+                schedule(1, "0 9 * * *"),
+                schedule(2, "30 9 * * *"),
+                schedule(3, "not cron"),
+            ],
+        };
+        let at = |hour, minute| Utc.with_ymd_and_hms(2024, 3, 4, hour, minute, 0).unwrap();
+        let ids = |due: Vec<&Schedule>| due.iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(ids(config.due(at(8, 59), at(9, 0))), vec![1]);
+        assert_eq!(ids(config.due(at(9, 0), at(9, 1))), Vec::<u32>::new());
+        assert_eq!(ids(config.due(at(8, 0), at(10, 0))), vec![1, 2]);
+        assert_eq!(config.next_after(at(9, 10)), Some(at(9, 30)));
+        assert_eq!(config.next_id(), 4);
+    }
+
+    #[test]
+    fn config_roundtrips() {
+        let config: ScheduleConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, ScheduleConfig::default());
+        let config: ScheduleConfig = serde_json::from_str(
+            r#"{"timezone":"America/New_York","schedules":[{"id":1,"channel":"5","cron":"0 9 * * *","deck":"chat"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.timezone, chrono_tz::America::New_York);
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ScheduleConfig>(&json).unwrap(),
+            config
+        );
+    }
+}
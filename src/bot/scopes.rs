@@ -0,0 +1,267 @@
+//! Channel scopes: commands, pictures and the mention guard can be limited
+//! to some channels, or kept out of others.
+//!
+//! A command follows the scope of its full name, such as `rr add`, then the
+//! one of its parents and finally the scope set for every command. Managing
+//! scopes works everywhere, so that admins can't lock themselves out.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use super::audit::{self, Action, AuditRecord};
+use super::command::reply_in_chann;
+use super::error::CommandError;
+use super::registry::{self, CommandResult, Invocation};
+use super::BotState;
+use crate::i18n::Message;
+
+/// Scope of every command without one of its own.
+pub const ALL_COMMANDS: &str = "*";
+pub const PICTURES: &str = "pictures";
+pub const MENTION_GUARD: &str = "mention_guard";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelScope {
+    /// When not empty, only these channels are allowed.
+    pub allow: Vec<Id<ChannelMarker>>,
+    pub deny: Vec<Id<ChannelMarker>>,
+}
+
+impl ChannelScope {
+    pub fn permits(&self, channel_id: Id<ChannelMarker>) -> bool {
+        !self.deny.contains(&channel_id)
+            && (self.allow.is_empty() || self.allow.contains(&channel_id))
+    }
+
+    fn describe(&self) -> String {
+        let mentions = |channels: &[Id<ChannelMarker>]| {
+            channels
+                .iter()
+                .map(|channel_id| format!("<#{}>", channel_id))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        match (self.allow.is_empty(), self.deny.is_empty()) {
+            (true, true) => "-".to_owned(),
+            (false, true) => format!("+ {}", mentions(&self.allow)),
+            (true, false) => format!("- {}", mentions(&self.deny)),
+            (false, false) => format!("+ {} / - {}", mentions(&self.allow), mentions(&self.deny)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScopeConfig {
+    /// By command name, [`ALL_COMMANDS`], [`PICTURES`] or [`MENTION_GUARD`].
+    pub scopes: HashMap<String, ChannelScope>,
+}
+
+impl ScopeConfig {
+    pub fn permits(&self, key: &str, channel_id: Id<ChannelMarker>) -> bool {
+        self.scopes
+            .get(key)
+            .is_none_or(|scope| scope.permits(channel_id))
+    }
+
+    /// Whether the command at `path` can be run in the channel.
+    pub fn permits_command(&self, path: &str, channel_id: Id<ChannelMarker>) -> bool {
+        if is_locked(path) {
+            return true;
+        }
+        let mut key = path;
+        loop {
+            if let Some(scope) = self.scopes.get(key) {
+                return scope.permits(channel_id);
+            }
+            match key.rsplit_once(' ') {
+                Some((parent, _)) => key = parent,
+                None => return self.permits(ALL_COMMANDS, channel_id),
+            }
+        }
+    }
+}
+
+fn is_locked(path: &str) -> bool {
+    path == "scope" || path.starts_with("scope ")
+}
+
+pub fn permits(state: &BotState, key: &str, channel_id: Id<ChannelMarker>) -> bool {
+    match state.config.lock() {
+        Ok(config) => config.channel_scopes.permits(key, channel_id),
+        Err(_) => true,
+    }
+}
+
+pub fn permits_command(state: &BotState, path: &str, channel_id: Id<ChannelMarker>) -> bool {
+    match state.config.lock() {
+        Ok(config) => config.channel_scopes.permits_command(path, channel_id),
+        Err(_) => true,
+    }
+}
+
+/// Scope key named by `arg`: a command, or one of the special keys.
+fn parse_key(inv: &Invocation, arg: &str) -> Result<String, CommandError> {
+    let key = match arg {
+        ALL_COMMANDS | "all" | "tout" => ALL_COMMANDS.to_owned(),
+        PICTURES | "images" => PICTURES.to_owned(),
+        MENTION_GUARD | "mentions" => MENTION_GUARD.to_owned(),
+        arg => registry::command_path(inv, arg)?,
+    };
+    if is_locked(&key) {
+        return Err(CommandError::NotFound(
+            Message::new("scope-locked").arg("command", format!("{}{}", registry::PREFIX, key)),
+        ));
+    }
+    Ok(key)
+}
+
+fn key_name(key: &str) -> String {
+    match key {
+        ALL_COMMANDS | PICTURES | MENTION_GUARD => format!("`{}`", key),
+        path => format!("`{}{}`", registry::PREFIX, path),
+    }
+}
+
+pub async fn list(inv: Invocation) -> CommandResult {
+    let scopes = inv.state.config.lock()?.channel_scopes.scopes.clone();
+    let mut lines: Vec<String> = scopes
+        .iter()
+        .map(|(key, scope)| format!("- {} : {}", key_name(key), scope.describe()))
+        .collect();
+    lines.sort();
+    let response = if lines.is_empty() {
+        Message::new("scope-list-empty").render(inv.locale)
+    } else {
+        format!(
+            "{}\n{}",
+            Message::new("scope-list-header").render(inv.locale),
+            lines.join("\n")
+        )
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+/// Applies `change` to the scope named by the first argument, audits it and
+/// replies with the new scope.
+async fn update_scope(
+    inv: Invocation,
+    change: impl FnOnce(&mut ChannelScope, Id<ChannelMarker>),
+) -> CommandResult {
+    let key = parse_key(&inv, &inv.args[0])?;
+    let Some(channel_id) = registry::parse_channel(&inv.args[1]) else {
+        return Err(inv.bad_usage());
+    };
+    let (before, after) = inv.state.update_config(|config| {
+        let scope = config.channel_scopes.scopes.entry(key.clone()).or_default();
+        let before = scope.describe();
+        change(scope, channel_id);
+        (before, scope.describe())
+    })?;
+    let target = key_name(&key);
+    let record = AuditRecord::new(
+        Action::ChannelScopes,
+        Some(inv.msg.author.id),
+        target.as_str(),
+    )
+    .before(before)
+    .after(after.as_str());
+    audit::log(&*inv.http, &inv.state, record).await;
+    let response = Message::new("scope-set")
+        .arg("command", target)
+        .arg("scope", after)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+pub async fn allow(inv: Invocation) -> CommandResult {
+    update_scope(inv, |scope, channel_id| {
+        scope.deny.retain(|denied| *denied != channel_id);
+        if !scope.allow.contains(&channel_id) {
+            scope.allow.push(channel_id);
+        }
+    })
+    .await
+}
+
+pub async fn deny(inv: Invocation) -> CommandResult {
+    update_scope(inv, |scope, channel_id| {
+        scope.allow.retain(|allowed| *allowed != channel_id);
+        if !scope.deny.contains(&channel_id) {
+            scope.deny.push(channel_id);
+        }
+    })
+    .await
+}
+
+pub async fn clear(inv: Invocation) -> CommandResult {
+    let key = parse_key(&inv, &inv.args[0])?;
+    let before = inv
+        .state
+        .update_config(|config| config.channel_scopes.scopes.remove(&key))?;
+    let target = key_name(&key);
+    if let Some(before) = before {
+        let record = AuditRecord::new(
+            Action::ChannelScopes,
+            Some(inv.msg.author.id),
+            target.as_str(),
+        )
+        .before(before.describe());
+        audit::log(&*inv.http, &inv.state, record).await;
+    }
+    let response = Message::new("scope-cleared")
+        .arg("command", target)
+        .render(inv.locale);
+    reply_in_chann(&*inv.http, &inv.msg, &response).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_and_deny_lists() {
+        let scope = ChannelScope {
+            allow: vec![Id::new(1), Id::new(2)],
+            deny: vec![Id::new(2)],
+        };
+        assert!(scope.permits(Id::new(1)));
+        assert!(!scope.permits(Id::new(2)));
+        assert!(!scope.permits(Id::new(3)));
+
+        let scope = ChannelScope {
+            allow: Vec::new(),
+            deny: vec![Id::new(2)],
+        };
+        assert!(scope.permits(Id::new(1)));
+        assert!(!scope.permits(Id::new(2)));
+    }
+
+    #[test]
+    fn commands_fall_back_to_parents() {
+        let only = |channel| ChannelScope {
+            allow: vec![Id::new(channel)],
+            deny: Vec::new(),
+        };
+        let config = ScopeConfig {
+            scopes: HashMap::from([
+                ("rr".to_owned(), only(10)),
+                ("rr list".to_owned(), ChannelScope::default()),
+                (ALL_COMMANDS.to_owned(), only(20)),
+                (PICTURES.to_owned(), only(30)),
+            ]),
+        };
+        assert!(config.permits_command("rr add", Id::new(10)));
+        assert!(!config.permits_command("rr add", Id::new(20)));
+        assert!(config.permits_command("rr list", Id::new(20)));
+        assert!(config.permits_command("aled", Id::new(20)));
+        assert!(!config.permits_command("aled", Id::new(10)));
+        assert!(config.permits_command("scope allow", Id::new(10)));
+        assert!(config.permits(PICTURES, Id::new(30)));
+        assert!(!config.permits(PICTURES, Id::new(20)));
+        assert!(config.permits(MENTION_GUARD, Id::new(20)));
+    }
+}
@@ -0,0 +1,438 @@
+//! Usage statistics: every picture sent is appended to a usage file next to
+//! the album, one JSON record per line, and `!stats` sums them up over a
+//! time window.
+//!
+//! Counting never makes a picture fail, errors are only traced.
+
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{marker::UserMarker, Id};
+
+use super::command::reply_in_chann;
+use super::error::CommandError;
+use super::registry::{self, CommandResult, Invocation};
+use super::BotState;
+use crate::i18n::{Locale, Message};
+
+/// Name of the usage file, in the directory of the album.
+pub const USAGE_FILE: &str = "usage.jsonl";
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Where the usage file of the album at `album_path` is.
+pub fn path_next_to(album_path: &str) -> String {
+    let path = Path::new(album_path).with_file_name(USAGE_FILE);
+    path.to_string_lossy().into_owned()
+}
+
+/// A picture sent on request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// Unix time, in seconds.
+    pub time: u64,
+    /// Name of the deck as stored in the album.
+    pub deck: String,
+    pub url: String,
+    /// Who asked for it.
+    pub user: Id<UserMarker>,
+}
+
+impl UsageRecord {
+    pub fn new(deck: impl Into<String>, url: impl Into<String>, user: Id<UserMarker>) -> Self {
+        Self {
+            time: now(),
+            deck: deck.into(),
+            url: url.into(),
+            user,
+        }
+    }
+}
+
+/// The usage file.
+pub struct UsageLog {
+    path: String,
+}
+
+impl UsageLog {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+        }
+    }
+
+    /// The usage file of the album at `album_path`.
+    pub fn next_to(album_path: &str) -> Self {
+        Self::new(&path_next_to(album_path))
+    }
+
+    pub fn append(&self, record: &UsageRecord) -> anyhow::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(&mut file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    /// Every record, oldest first. Lines that can't be read are skipped.
+    pub fn read(&self) -> anyhow::Result<Vec<UsageRecord>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut records = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            match serde_json::from_str(&line?) {
+                Ok(record) => records.push(record),
+                Err(err) => tracing::warn!(%err, line = number + 1, "skipping usage record"),
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Counts a picture sent to `user`.
+pub fn count(state: &BotState, deck: &str, url: &str, user: Id<UserMarker>) {
+    let record = UsageRecord::new(deck, url, user);
+    match state.usage.lock() {
+        Ok(usage) => {
+            if let Err(err) = usage.append(&record) {
+                tracing::warn!(%err, "failed to write usage record");
+            }
+        }
+        Err(err) => tracing::warn!(%err, "failed to lock usage log"),
+    }
+}
+
+/// How far back statistics go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Hours(u64),
+    Days(u64),
+    All,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self::Days(30)
+    }
+}
+
+impl Window {
+    /// Parses `24h`, `7d`, `7j`, `2w`, `2s`, or `all`/`tout`.
+    pub fn parse(arg: &str) -> Option<Window> {
+        let arg = arg.to_lowercase();
+        if arg == "all" || arg == "tout" {
+            return Some(Self::All);
+        }
+        let unit = arg.chars().last()?;
+        let count: u64 = arg[..arg.len() - unit.len_utf8()].parse().ok()?;
+        if count == 0 {
+            return None;
+        }
+        match unit {
+            'h' => Some(Self::Hours(count)),
+            'd' | 'j' => Some(Self::Days(count)),
+            'w' | 's' => count.checked_mul(7).map(Self::Days),
+            _ => None,
+        }
+    }
+
+    /// Oldest time in the window, given the current time.
+    pub fn start(self, now: u64) -> u64 {
+        match self {
+            Self::Hours(hours) => now.saturating_sub(hours.saturating_mul(3600)),
+            Self::Days(days) => now.saturating_sub(days.saturating_mul(86400)),
+            Self::All => 0,
+        }
+    }
+
+    fn label(self, locale: Locale) -> String {
+        let message = match self {
+            Self::Hours(hours) => Message::new("stats-window-hours").arg("count", hours as usize),
+            Self::Days(days) => Message::new("stats-window-days").arg("count", days as usize),
+            Self::All => Message::new("stats-window-all"),
+        };
+        message.render(locale)
+    }
+}
+
+/// Records in the window ending at `now`.
+pub fn within(records: &[UsageRecord], window: Window, now: u64) -> Vec<&UsageRecord> {
+    let start = window.start(now);
+    records
+        .iter()
+        .filter(|record| record.time >= start)
+        .collect()
+}
+
+/// The `limit` most frequent keys, most frequent first, ties in key order.
+pub fn top<K: Ord>(keys: impl IntoIterator<Item = K>, limit: usize) -> Vec<(K, usize)> {
+    let mut counts = BTreeMap::new();
+    for key in keys {
+        *counts.entry(key).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(K, usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts.truncate(limit);
+    counts
+}
+
+/// Decks none of the records is about, in order.
+pub fn unused<'a>(
+    decks: impl IntoIterator<Item = &'a str>,
+    records: &[&UsageRecord],
+) -> Vec<&'a str> {
+    let mut unused: Vec<&str> = decks
+        .into_iter()
+        .filter(|deck| !records.iter().any(|record| record.deck == *deck))
+        .collect();
+    unused.sort();
+    unused
+}
+
+/// Writes records to `path`, as CSV or JSON depending on its extension.
+pub fn export(records: &[&UsageRecord], path: &str) -> anyhow::Result<()> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("csv") => {
+            let mut writer = csv::Writer::from_path(path)?;
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Some("json") => serde_json::to_writer_pretty(std::fs::File::create(path)?, records)?,
+        _ => anyhow::bail!("can't tell the format of {}, use .csv or .json", path),
+    }
+    Ok(())
+}
+
+/// Entries of each list of `!stats`.
+const STATS_TOP: usize = 5;
+/// Keeps `!stats` replies under Discord's message length.
+const STATS_MAX_CHARS: usize = 1900;
+
+fn read(state: &BotState) -> Result<Vec<UsageRecord>, CommandError> {
+    state
+        .usage
+        .lock()?
+        .read()
+        .map_err(|err| CommandError::Storage(err.to_string()))
+}
+
+fn window_arg(inv: &Invocation, index: usize) -> Result<Window, CommandError> {
+    match inv.args.get(index) {
+        None => Ok(Window::default()),
+        Some(arg) => Window::parse(arg).ok_or_else(|| inv.bad_usage()),
+    }
+}
+
+/// Adds a titled, numbered list to `response`.
+fn push_list(response: &mut String, title: String, lines: Vec<String>) {
+    if lines.is_empty() {
+        return;
+    }
+    response.push_str(&format!("\n**{}**", title));
+    for (rank, line) in lines.into_iter().enumerate() {
+        response.push_str(&format!("\n{}. {}", rank + 1, line));
+    }
+}
+
+fn deck_lines(records: &[&UsageRecord]) -> Vec<String> {
+    top(records.iter().map(|record| record.deck.as_str()), STATS_TOP)
+        .into_iter()
+        .map(|(deck, count)| format!("{} — {}", deck, count))
+        .collect()
+}
+
+fn picture_lines(records: &[&UsageRecord]) -> Vec<String> {
+    let pictures = records
+        .iter()
+        .map(|record| (record.url.as_str(), record.deck.as_str()));
+    top(pictures, STATS_TOP)
+        .into_iter()
+        .map(|((url, deck), count)| format!("<{}> ({}) — {}", url, deck, count))
+        .collect()
+}
+
+fn clipped(mut response: String) -> String {
+    if response.chars().count() > STATS_MAX_CHARS {
+        response = response.chars().take(STATS_MAX_CHARS - 1).collect();
+        response.push('…');
+    }
+    response
+}
+
+pub async fn show(inv: Invocation) -> CommandResult {
+    let window = window_arg(&inv, 0)?;
+    let records = read(&inv.state)?;
+    let found = within(&records, window, now());
+    if found.is_empty() {
+        let response = Message::new("stats-empty")
+            .arg("window", window.label(inv.locale))
+            .render(inv.locale);
+        return reply_in_chann(&*inv.http, &inv.msg, &response).await;
+    }
+
+    let mut response = Message::new("stats-header")
+        .arg("count", found.len())
+        .arg("window", window.label(inv.locale))
+        .render(inv.locale);
+    let title = |key| Message::new(key).render(inv.locale);
+    push_list(&mut response, title("stats-decks"), deck_lines(&found));
+    push_list(
+        &mut response,
+        title("stats-pictures"),
+        picture_lines(&found),
+    );
+    let users = top(found.iter().map(|record| record.user), STATS_TOP)
+        .into_iter()
+        .map(|(user, count)| format!("<@{}> — {}", user, count))
+        .collect();
+    push_list(&mut response, title("stats-users"), users);
+    reply_in_chann(&*inv.http, &inv.msg, &clipped(response)).await
+}
+
+pub async fn user(inv: Invocation) -> CommandResult {
+    let user_id = registry::parse_user(&inv.args[0]).ok_or_else(|| inv.bad_usage())?;
+    let window = window_arg(&inv, 1)?;
+    let records = read(&inv.state)?;
+    let found: Vec<&UsageRecord> = within(&records, window, now())
+        .into_iter()
+        .filter(|record| record.user == user_id)
+        .collect();
+
+    let mut response = Message::new("stats-user-header")
+        .arg("user", format!("<@{}>", user_id))
+        .arg("count", found.len())
+        .arg("window", window.label(inv.locale))
+        .render(inv.locale);
+    let title = |key| Message::new(key).render(inv.locale);
+    push_list(&mut response, title("stats-decks"), deck_lines(&found));
+    push_list(
+        &mut response,
+        title("stats-pictures"),
+        picture_lines(&found),
+    );
+    reply_in_chann(&*inv.http, &inv.msg, &clipped(response)).await
+}
+
+pub async fn unused_decks(inv: Invocation) -> CommandResult {
+    let window = window_arg(&inv, 0)?;
+    let records = read(&inv.state)?;
+    let found = within(&records, window, now());
+    let response = {
+        let album = inv.state.album.lock()?;
+        let decks = unused(album.deck_names().map(String::as_str), &found);
+        if decks.is_empty() {
+            Message::new("stats-unused-none")
+                .arg("window", window.label(inv.locale))
+                .render(inv.locale)
+        } else {
+            let mut response = Message::new("stats-unused")
+                .arg("count", decks.len())
+                .arg("window", window.label(inv.locale))
+                .render(inv.locale);
+            response.push('\n');
+            response.push_str(&decks.join(", "));
+            response
+        }
+    };
+    reply_in_chann(&*inv.http, &inv.msg, &clipped(response)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(time: u64, deck: &str, url: &str, user: u64) -> UsageRecord {
+        UsageRecord {
+            time,
+            deck: deck.to_owned(),
+            url: url.to_owned(),
+            user: Id::new(user),
+        }
+    }
+
+    #[test]
+    fn windows_parse() {
+        assert_eq!(Window::parse("24h"), Some(Window::Hours(24)));
+        assert_eq!(Window::parse("7j"), Some(Window::Days(7)));
+        assert_eq!(Window::parse("2S"), Some(Window::Days(14)));
+        assert_eq!(Window::parse("tout"), Some(Window::All));
+        assert_eq!(Window::parse("0d"), None);
+        assert_eq!(Window::parse("d"), None);
+        assert_eq!(Window::parse("chat"), None);
+        assert_eq!(Window::Days(1).start(100_000), 100_000 - 86400);
+        assert_eq!(Window::Hours(1000).start(10), 0);
+        assert_eq!(Window::parse(&format!("{}w", u64::MAX)), None);
+        assert_eq!(Window::Days(u64::MAX).start(100_000), 0);
+        assert_eq!(Window::Hours(u64::MAX).start(100_000), 0);
+    }
+
+    #[test]
+    fn counts_within_window() {
+        let records = vec![
+            record(10, "chat", "https://a", 1),
+            record(90_000, "chat", "https://b", 2),
+            record(90_100, "chien", "https://c", 2),
+            record(90_200, "chat", "https://b", 1),
+        ];
+        let found = within(&records, Window::Days(1), 100_000);
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            top(found.iter().map(|record| record.deck.as_str()), 5),
+            vec![("chat", 2), ("chien", 1)]
+        );
+        assert_eq!(
+            top(found.iter().map(|record| record.user), 1),
+            vec![(Id::new(2), 2)]
+        );
+        assert_eq!(
+            unused(["poney", "chien", "chat", "ane"], &found),
+            vec!["ane", "poney"]
+        );
+        let all = within(&records, Window::All, 100_000);
+        assert_eq!(
+            top(all.iter().map(|record| record.url.as_str()), 5)[0],
+            ("https://b", 2)
+        );
+    }
+
+    #[test]
+    fn records_roundtrip_and_export() {
+        let dir = std::env::temp_dir().join(format!("usage-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = UsageLog::next_to(dir.join("save.json").to_str().unwrap());
+        assert!(log.read().unwrap().is_empty());
+        log.append(&record(1, "chat", "https://a", 1)).unwrap();
+        log.append(&record(2, "chien", "https://b", 2)).unwrap();
+        let records = log.read().unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(dir.join(USAGE_FILE).exists());
+
+        let all = within(&records, Window::All, 3);
+        let csv = dir.join("usage.csv");
+        export(&all, csv.to_str().unwrap()).unwrap();
+        let text = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(text.lines().next(), Some("time,deck,url,user"));
+        assert_eq!(text.lines().count(), 3);
+        assert!(export(&all, dir.join("usage.txt").to_str().unwrap()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,500 @@
+//! Command line: running the bot, and working on the album offline, which
+//! doesn't need a Discord token.
+
+use std::path::Path;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::album::exchange::{self, Format, Merge};
+use crate::album::Album;
+use crate::bot;
+use crate::bot::stats::{self, Window};
+use crate::config::Config;
+
+const ALBUM_FILE: &str = "save.json";
+const CONFIG_FILE: &str = "config.json";
+const OFFENSES_FILE: &str = "offenses.json";
+const AUDIT_FILE: &str = "audit.jsonl";
+/// Entries listed by `album stats` and `usage`.
+const STATS_TOP: usize = 10;
+
+#[derive(Parser)]
+#[command(version, about = "Discord bot sending pictures on command")]
+pub struct Cli {
+    /// Runs the bot when none is given.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Connects to Discord and runs the bot.
+    Run {
+        #[command(flatten)]
+        files: Files,
+        /// Records the events received to this file.
+        #[arg(long, value_name = "FILE")]
+        record: Option<String>,
+    },
+    /// Feeds a recording through the bot, against copies of the data files.
+    Replay {
+        recording: String,
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Edits the album.
+    Album {
+        #[arg(long, default_value = ALBUM_FILE)]
+        album: String,
+        #[command(subcommand)]
+        command: AlbumCommand,
+    },
+    /// Checks that the data files can be read and make sense.
+    Validate {
+        #[command(flatten)]
+        files: Files,
+    },
+    /// Writes every picture of the album to a file.
+    Export {
+        file: String,
+        #[arg(long, default_value = ALBUM_FILE)]
+        album: String,
+        /// Guessed from the extension of the file when not given.
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Adds the pictures of an exported file to the album.
+    Import {
+        file: String,
+        #[arg(long, default_value = ALBUM_FILE)]
+        album: String,
+        /// Guessed from the extension of the file when not given.
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        /// What to do with the decks the album already has.
+        #[arg(long, value_enum, default_value_t)]
+        merge: Merge,
+    },
+    /// Sums up the pictures requested, or exports the requests.
+    Usage {
+        #[arg(long, default_value = ALBUM_FILE)]
+        album: String,
+        /// 24h, 7d, 2w or all.
+        #[arg(long, default_value = "30d", value_parser = parse_window)]
+        since: Window,
+        /// Writes the requests to this file instead, as CSV or JSON
+        /// depending on its extension.
+        #[arg(long, value_name = "FILE")]
+        export: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AlbumCommand {
+    /// Lists the decks.
+    List,
+    /// Lists the pictures of a deck.
+    Show { deck: String },
+    /// Adds pictures to a deck.
+    Add {
+        deck: String,
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+    /// Removes a picture, or the whole deck.
+    Remove { deck: String, url: Option<String> },
+    /// Renames a deck, merging it into the deck of that name if there is one.
+    Rename { deck: String, new_name: String },
+    /// Counts decks and pictures.
+    Stats,
+}
+
+#[derive(Args)]
+struct Files {
+    #[arg(long, default_value = CONFIG_FILE)]
+    config: String,
+    /// The offenses, audit and usage files are kept next to it.
+    #[arg(long, default_value = ALBUM_FILE)]
+    album: String,
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self {
+            config: CONFIG_FILE.to_owned(),
+            album: ALBUM_FILE.to_owned(),
+        }
+    }
+}
+
+type Data = (
+    Album,
+    Config,
+    bot::guard::Offenses,
+    bot::audit::AuditLog,
+    stats::UsageLog,
+);
+
+impl Files {
+    /// Where the data `file` of the album is.
+    fn next_to_album(&self, file: &str) -> String {
+        let path = Path::new(&self.album).with_file_name(file);
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Every file read or written by the bot.
+    fn all(&self) -> [String; 5] {
+        [
+            self.album.clone(),
+            self.config.clone(),
+            self.next_to_album(OFFENSES_FILE),
+            self.next_to_album(AUDIT_FILE),
+            stats::path_next_to(&self.album),
+        ]
+    }
+
+    /// Loads the data files, finding them with `path`.
+    fn load(&self, path: impl Fn(&str) -> String) -> anyhow::Result<Data> {
+        let alb = Album::from_file(&path(&self.album))?;
+        let config = Config::from_file(&path(&self.config))?;
+        let offenses = bot::guard::Offenses::from_file(&path(&self.next_to_album(OFFENSES_FILE)))?;
+        let audit = bot::audit::AuditLog::new(&path(&self.next_to_album(AUDIT_FILE)));
+        let usage = stats::UsageLog::next_to(&path(&self.album));
+        Ok((alb, config, offenses, audit, usage))
+    }
+}
+
+impl Cli {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self.command {
+            None => run(Files::default(), None).await,
+            Some(Command::Run { files, record }) => run(files, record.as_deref()).await,
+            Some(Command::Replay { recording, files }) => replay(&recording, files).await,
+            Some(Command::Album { album, command }) => edit_album(&album, command),
+            Some(Command::Validate { files }) => validate(&files),
+            Some(Command::Export {
+                file,
+                album,
+                format,
+            }) => export(&album, &file, format).await,
+            Some(Command::Import {
+                file,
+                album,
+                format,
+                merge,
+            }) => import(&album, &file, format, merge),
+            Some(Command::Usage {
+                album,
+                since,
+                export,
+            }) => usage(&album, since, export.as_deref()),
+        }
+    }
+}
+
+async fn run(files: Files, record_to: Option<&str>) -> anyhow::Result<()> {
+    let (alb, config, offenses, audit, usage) = files.load(str::to_owned)?;
+    bot::start(alb, config, offenses, audit, usage, record_to).await
+}
+
+async fn replay(recording: &str, files: Files) -> anyhow::Result<()> {
+    let all = files.all();
+    let scratch = bot::recording::Scratch::new(&all.each_ref().map(String::as_str))?;
+    let (alb, config, offenses, audit, usage) = files.load(|file| scratch.path(file))?;
+    bot::recording::replay(recording, alb, config, offenses, audit, usage).await
+}
+
+fn edit_album(path: &str, command: AlbumCommand) -> anyhow::Result<()> {
+    let mut album = Album::from_file(path)?;
+    match command {
+        AlbumCommand::List => {
+            let mut decks: Vec<(&str, usize)> = album
+                .deck_names()
+                .filter_map(|name| album.deck(name))
+                .map(|(name, pictures)| (name, pictures.len()))
+                .collect();
+            decks.sort();
+            for (name, count) in decks {
+                println!("{} ({})", name, count);
+            }
+        }
+        AlbumCommand::Show { deck } => {
+            let Some((_, pictures)) = album.deck(&deck) else {
+                anyhow::bail!("no deck called {}", deck);
+            };
+            for url in pictures {
+                println!("{}", url);
+            }
+        }
+        AlbumCommand::Add { deck, urls } => {
+            for url in &urls {
+                album.add_picture(&deck, url);
+            }
+            album.save()?;
+            println!("added {} pictures to {}", urls.len(), deck);
+        }
+        AlbumCommand::Remove {
+            deck,
+            url: Some(url),
+        } => {
+            let Some((name, _)) = album.deck(&deck) else {
+                anyhow::bail!("no deck called {}", deck);
+            };
+            let name = name.to_owned();
+            if !album.remove_picture(&name, &url) {
+                anyhow::bail!("{} is not in {}", url, name);
+            }
+            album.save()?;
+            println!("removed {} from {}", url, name);
+        }
+        AlbumCommand::Remove { deck, url: None } => {
+            let Some(pictures) = album.remove_deck(&deck) else {
+                anyhow::bail!("no deck called {}", deck);
+            };
+            album.save()?;
+            println!("removed {} with its {} pictures", deck, pictures.len());
+        }
+        AlbumCommand::Rename { deck, new_name } => {
+            let Some(moved) = album.rename_deck(&deck, &new_name) else {
+                anyhow::bail!("no deck called {}", deck);
+            };
+            album.save()?;
+            println!("moved {} pictures from {} to {}", moved, deck, new_name);
+        }
+        AlbumCommand::Stats => {
+            println!("{} decks", album.deck_count());
+            println!("{} pictures", album.picture_count());
+            let mut decks: Vec<(&str, usize)> = album
+                .deck_names()
+                .filter_map(|name| album.deck(name))
+                .map(|(name, pictures)| (name, pictures.len()))
+                .collect();
+            decks.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+            for (name, count) in decks.into_iter().take(STATS_TOP) {
+                println!("  {:>5} {}", count, name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What is wrong with the pictures of an album.
+fn album_problems(album: &Album) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for picture in album.pictures() {
+        if !picture.url.starts_with("https://") && !picture.url.starts_with("http://") {
+            problems.push(format!("{}: {} is not a link", picture.deck, picture.url));
+        }
+        if !seen.insert((picture.deck.clone(), picture.url.clone())) {
+            problems.push(format!("{}: {} is there twice", picture.deck, picture.url));
+        }
+    }
+    problems
+}
+
+/// What is wrong with a configuration.
+fn config_problems(config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for rr in &config.reaction_roles {
+        if rr.roles.is_empty() {
+            problems.push(format!("reaction roles of {} give no role", rr.message_id));
+        }
+        if !seen.insert(rr.message_id) {
+            problems.push(format!("reaction roles of {} are set twice", rr.message_id));
+        }
+    }
+    for schedule in &config.schedules.schedules {
+        if bot::schedules::parse_cron(&schedule.cron).is_none() {
+            problems.push(format!(
+                "schedule {} has an invalid time: {}",
+                schedule.id, schedule.cron
+            ));
+        }
+    }
+    for announcement in &config.announcements.announcements {
+        if bot::schedules::parse_cron(&announcement.cron).is_none() {
+            problems.push(format!(
+                "announcement {} has an invalid time: {}",
+                announcement.id, announcement.cron
+            ));
+        }
+        let template = &announcement.template;
+        if template != bot::announcements::ETIQUETTE
+            && !config.announcements.templates.contains_key(template)
+        {
+            problems.push(format!(
+                "announcement {} uses the missing template {}",
+                announcement.id, template
+            ));
+        }
+    }
+    for command in &config.custom_commands.commands {
+        if command.responses.is_empty() {
+            problems.push(format!("custom command {} has no response", command.name));
+        }
+    }
+    problems
+}
+
+fn validate(files: &Files) -> anyhow::Result<()> {
+    let (album, config, _, _, _) = files.load(str::to_owned)?;
+    let problems: Vec<String> = album_problems(&album)
+        .into_iter()
+        .map(|problem| format!("{}: {}", files.album, problem))
+        .chain(
+            config_problems(&config)
+                .into_iter()
+                .map(|problem| format!("{}: {}", files.config, problem)),
+        )
+        .collect();
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("found {} problems", problems.len());
+    }
+    println!(
+        "ok: {} decks, {} pictures, {} reaction role messages",
+        album.deck_count(),
+        album.picture_count(),
+        config.reaction_roles.len()
+    );
+    Ok(())
+}
+
+fn format_of(file: &str, format: Option<Format>) -> anyhow::Result<Format> {
+    match format.or_else(|| Format::of_file(file)) {
+        Some(format) => Ok(format),
+        None => anyhow::bail!("can't tell the format of {}, give it with --format", file),
+    }
+}
+
+async fn export(path: &str, file: &str, format: Option<Format>) -> anyhow::Result<()> {
+    let format = format_of(file, format)?;
+    let album = Album::from_file(path)?;
+    let report = exchange::export(&album, file, format).await?;
+    println!("exported {} pictures to {}", report.pictures, file);
+    if report.failed_downloads > 0 {
+        println!(
+            "{} pictures could not be downloaded",
+            report.failed_downloads
+        );
+    }
+    Ok(())
+}
+
+fn import(path: &str, file: &str, format: Option<Format>, strategy: Merge) -> anyhow::Result<()> {
+    let format = format_of(file, format)?;
+    let mut album = Album::from_file(path)?;
+    let entries = exchange::import(file, format)?;
+    let report = exchange::merge(&mut album, &entries, strategy);
+    album.save()?;
+    println!(
+        "imported {} pictures, skipped {}, replaced {} decks",
+        report.added, report.skipped, report.replaced_decks
+    );
+    Ok(())
+}
+
+fn parse_window(arg: &str) -> Result<Window, String> {
+    Window::parse(arg).ok_or_else(|| format!("{} is not a period like 24h, 7d, 2w or all", arg))
+}
+
+fn usage(path: &str, since: Window, export: Option<&str>) -> anyhow::Result<()> {
+    let album = Album::from_file(path)?;
+    let records = stats::UsageLog::next_to(path).read()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let found = stats::within(&records, since, now);
+    if let Some(file) = export {
+        stats::export(&found, file)?;
+        println!("exported {} requests to {}", found.len(), file);
+        return Ok(());
+    }
+
+    println!("{} pictures requested", found.len());
+    println!("top decks:");
+    let decks = found.iter().map(|record| record.deck.as_str());
+    for (deck, count) in stats::top(decks, STATS_TOP) {
+        println!("  {:>5} {}", count, deck);
+    }
+    println!("top users:");
+    for (user, count) in stats::top(found.iter().map(|record| record.user), STATS_TOP) {
+        println!("  {:>5} {}", count, user);
+    }
+    let unused = stats::unused(album.deck_names().map(String::as_str), &found);
+    println!("{} decks never requested", unused.len());
+    for deck in unused {
+        println!("  {}", deck);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn default_is_run() {
+        let cli = Cli::try_parse_from(["oxytrouille"]).unwrap();
+        assert!(cli.command.is_none());
+        let cli = Cli::try_parse_from(["oxytrouille", "album", "rename", "chat", "chats"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Album {
+                command: AlbumCommand::Rename { .. },
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["oxytrouille", "album", "add", "chat"]).is_err());
+    }
+
+    #[test]
+    fn data_files_follow_the_album() {
+        let files = Files {
+            config: "config.json".to_owned(),
+            album: "data/save.json".to_owned(),
+        };
+        let all = files.all();
+        assert_eq!(
+            all[2],
+            Path::new("data").join("offenses.json").to_str().unwrap()
+        );
+        assert_eq!(
+            all[3],
+            Path::new("data").join("audit.jsonl").to_str().unwrap()
+        );
+        assert_eq!(
+            all[4],
+            Path::new("data").join("usage.jsonl").to_str().unwrap()
+        );
+        assert_eq!(
+            Files::default().next_to_album(OFFENSES_FILE),
+            "offenses.json"
+        );
+    }
+
+    #[test]
+    fn problems_are_found() {
+        let mut album = Album::new();
+        album.add_picture("chat", "https://example.com/chat.png");
+        album.add_picture("chat", "https://example.com/chat.png");
+        album.add_picture("chien", "chien.png");
+        assert_eq!(album_problems(&album).len(), 2);
+
+        let mut config = Config::default();
+        assert!(config_problems(&config).is_empty());
+        config.reaction_roles.push(config.reaction_roles[0].clone());
+        assert_eq!(config_problems(&config).len(), 1);
+    }
+}
@@ -0,0 +1,133 @@
+//! Settings changed at runtime through admin commands, saved as JSON next to
+//! the album.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker},
+    Id,
+};
+
+use crate::bot::announcements::AnnouncementConfig;
+use crate::bot::custom::CustomConfig;
+use crate::bot::guard::GuardConfig;
+use crate::bot::nicknames::NicknamePolicy;
+use crate::bot::permissions::PermissionConfig;
+use crate::bot::rate_limit::RateLimitConfig;
+use crate::bot::reaction_roles::{self, ReactionRoleMessage, ResyncMode};
+use crate::bot::schedules::ScheduleConfig;
+use crate::bot::scopes::ScopeConfig;
+use crate::i18n::Locale;
+
+#[derive(Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub guild_locales: HashMap<Id<GuildMarker>, Locale>,
+    #[serde(default = "reaction_roles::legacy")]
+    pub reaction_roles: Vec<ReactionRoleMessage>,
+    #[serde(default)]
+    pub reaction_roles_resync: ResyncMode,
+    #[serde(default)]
+    pub guard: GuardConfig,
+    /// Where admin actions are posted.
+    #[serde(default)]
+    pub mod_log_channel: Option<Id<ChannelMarker>>,
+    #[serde(default)]
+    pub permissions: PermissionConfig,
+    #[serde(default)]
+    pub nickname_policy: NicknamePolicy,
+    #[serde(default)]
+    pub picture_rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub channel_scopes: ScopeConfig,
+    /// Pictures posted on their own.
+    #[serde(default)]
+    pub schedules: ScheduleConfig,
+    /// Reminders posted again and again.
+    #[serde(default)]
+    pub announcements: AnnouncementConfig,
+    /// Text commands written at runtime.
+    #[serde(default)]
+    pub custom_commands: CustomConfig,
+    #[serde(skip)]
+    source_file: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            guild_locales: HashMap::new(),
+            reaction_roles: reaction_roles::legacy(),
+            reaction_roles_resync: ResyncMode::default(),
+            guard: GuardConfig::default(),
+            mod_log_channel: None,
+            permissions: PermissionConfig::default(),
+            nickname_policy: NicknamePolicy::default(),
+            picture_rate_limit: RateLimitConfig::default(),
+            channel_scopes: ScopeConfig::default(),
+            schedules: ScheduleConfig::default(),
+            announcements: AnnouncementConfig::default(),
+            custom_commands: CustomConfig::default(),
+            source_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration at `path`, or starts from the defaults when
+    /// the file doesn't exist yet. It will be created on the first save.
+    pub fn from_file(path: &str) -> anyhow::Result<Config> {
+        let mut config: Config = match std::fs::File::open(path) {
+            Ok(file) => serde_json::from_reader(&file)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(err) => return Err(err.into()),
+        };
+        config.source_file = Some(path.to_owned());
+        Ok(config)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(file_name) = &self.source_file else {
+            anyhow::bail!("configuration was not loaded from a file and cannot be saved");
+        };
+        let mut file = std::fs::File::create(file_name)?;
+        let to_write = serde_json::to_string_pretty(&self)?;
+        writeln!(&mut file, "{}", to_write)?;
+        Ok(())
+    }
+
+    pub fn locale(&self, guild_id: Option<Id<GuildMarker>>) -> Locale {
+        guild_id
+            .and_then(|guild_id| self.guild_locales.get(&guild_id).copied())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guild_locales_roundtrip() {
+        let mut config = Config::default();
+        config
+            .guild_locales
+            .insert(Id::new(416194652744450048), Locale::En);
+        let json = serde_json::to_string(&config).unwrap();
+        let config: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.locale(Some(Id::new(416194652744450048))), Locale::En);
+        assert_eq!(config.locale(Some(Id::new(1))), Locale::Fr);
+        assert_eq!(config.locale(None), Locale::Fr);
+    }
+
+    #[test]
+    fn missing_fields_default() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert!(config.guild_locales.is_empty());
+        assert_eq!(config.reaction_roles, reaction_roles::legacy());
+        assert_eq!(config.guard, GuardConfig::default());
+        assert!(!config.nickname_policy.enabled);
+    }
+}
@@ -0,0 +1,221 @@
+//! Message catalog for the bot's replies.
+//!
+//! Messages are Fluent files under `locales/`, embedded in the binary.
+//! French is the default, English is available.
+
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Fr,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::Fr, Locale::En];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Fr => "fr",
+            Self::En => "en",
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Self::Fr => include_str!("../locales/fr.ftl"),
+            Self::En => include_str!("../locales/en.ftl"),
+        }
+    }
+
+    /// Parses a locale as given by a user (`fr`, `en`) or by Discord
+    /// (`fr`, `en-US`, `en-GB`...).
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        let lang = tag.split(['-', '_']).next()?.to_lowercase();
+        Self::ALL.into_iter().find(|locale| locale.code() == lang)
+    }
+}
+
+/// Value of a message argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Text(String),
+    Number(i64),
+}
+
+impl From<&str> for Arg {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_owned())
+    }
+}
+
+impl From<String> for Arg {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<usize> for Arg {
+    fn from(value: usize) -> Self {
+        Self::Number(value as i64)
+    }
+}
+
+/// A message key with its arguments, to be rendered once the locale is
+/// known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub key: &'static str,
+    pub args: Vec<(&'static str, Arg)>,
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Self {
+            key,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl Into<Arg>) -> Self {
+        self.args.push((name, value.into()));
+        self
+    }
+
+    pub fn render(&self, locale: Locale) -> String {
+        tr(locale, self.key, &self.args)
+    }
+}
+
+struct Catalog {
+    bundles: Vec<(Locale, FluentBundle<FluentResource>)>,
+}
+
+fn make_bundle(locale: Locale) -> FluentBundle<FluentResource> {
+    let resource = FluentResource::try_new(locale.source().to_owned())
+        .unwrap_or_else(|(_, errs)| panic!("invalid {} catalog: {:?}", locale.code(), errs));
+    let lang = locale
+        .code()
+        .parse()
+        .expect("locale codes are valid language identifiers");
+    let mut bundle = FluentBundle::new_concurrent(vec![lang]);
+    // NOTE: Unicode isolation marks show up as garbage in some Discord
+    // clients.
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .unwrap_or_else(|errs| panic!("invalid {} catalog: {:?}", locale.code(), errs));
+    bundle
+}
+
+fn catalog() -> &'static Catalog {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    CATALOG.get_or_init(|| Catalog {
+        bundles: Locale::ALL
+            .into_iter()
+            .map(|locale| (locale, make_bundle(locale)))
+            .collect(),
+    })
+}
+
+fn format(
+    bundle: &FluentBundle<FluentResource>,
+    key: &str,
+    args: &[(&str, Arg)],
+) -> Option<String> {
+    let pattern = bundle.get_message(key)?.value()?;
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        match value {
+            Arg::Text(text) => fluent_args.set(*name, FluentValue::from(text.as_str())),
+            Arg::Number(num) => fluent_args.set(*name, FluentValue::from(*num)),
+        }
+    }
+    let mut errors = Vec::new();
+    let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+    if !errors.is_empty() {
+        tracing::warn!(key, ?errors, "errors while formatting message");
+    }
+    Some(text.into_owned())
+}
+
+#[cfg(test)]
+pub fn has_message(locale: Locale, key: &str) -> bool {
+    catalog()
+        .bundles
+        .iter()
+        .any(|(candidate, bundle)| *candidate == locale && bundle.has_message(key))
+}
+
+/// Renders the message `key` in `locale`, falling back to French and then to
+/// the key itself when it is missing.
+pub fn tr(locale: Locale, key: &str, args: &[(&str, Arg)]) -> String {
+    let bundles = &catalog().bundles;
+    let find = |wanted: Locale| bundles.iter().find(|(locale, _)| *locale == wanted);
+    [locale, Locale::default()]
+        .into_iter()
+        .filter_map(find)
+        .find_map(|(_, bundle)| format(bundle, key, args))
+        .unwrap_or_else(|| {
+            tracing::error!(key, "missing message in catalog");
+            key.to_owned()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn keys(locale: Locale) -> BTreeSet<String> {
+        let resource = FluentResource::try_new(locale.source().to_owned()).unwrap();
+        resource
+            .entries()
+            .filter_map(|entry| match entry {
+                fluent_syntax::ast::Entry::Message(msg) => Some(msg.id.name.to_owned()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_key_exists_in_every_locale() {
+        let reference = keys(Locale::default());
+        assert!(!reference.is_empty());
+        for locale in Locale::ALL {
+            let other = keys(locale);
+            let missing: Vec<_> = reference.difference(&other).collect();
+            let extra: Vec<_> = other.difference(&reference).collect();
+            assert!(
+                missing.is_empty() && extra.is_empty(),
+                "{}: missing {:?}, unknown {:?}",
+                locale.code(),
+                missing,
+                extra
+            );
+        }
+    }
+
+    #[test]
+    fn render_with_args() {
+        let msg = Message::new("picture-added").arg("count", 2usize);
+        assert_eq!(msg.render(Locale::Fr), "J'ai ajouté 2 image·s !");
+        assert_eq!(msg.render(Locale::En), "I added 2 pictures!");
+        let msg = Message::new("picture-added").arg("count", 1usize);
+        assert_eq!(msg.render(Locale::En), "I added 1 picture!");
+    }
+
+    #[test]
+    fn from_tag() {
+        assert_eq!(Locale::from_tag("fr"), Some(Locale::Fr));
+        assert_eq!(Locale::from_tag("en-US"), Some(Locale::En));
+        assert_eq!(Locale::from_tag("EN"), Some(Locale::En));
+        assert_eq!(Locale::from_tag("de"), None);
+    }
+}
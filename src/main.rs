@@ -1,5 +1,10 @@
 mod album;
 mod bot;
+mod cli;
+mod config;
+mod i18n;
+
+use clap::Parser;
 // use std::io::Write;
 
 #[tokio::main]
@@ -12,9 +17,5 @@ async fn main() -> anyhow::Result<()> {
     // let mut file = std::fs::File::create("albums.ron").unwrap();
     // writeln!(&mut file, "{}", ron::to_string(&alb).unwrap()).unwrap();
     // bot::start().await
-    let alb = album::Album::from_file("save.json")?;
-
-    bot::start(alb).await?;
-
-    Ok(())
+    cli::Cli::parse().run().await
 }
@@ -0,0 +1,190 @@
+//! Runs the bot against the fake Discord API.
+
+mod fake_discord;
+
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use hyper::Method;
+use serde_json::json;
+use tokio::process::{Child, Command};
+
+use fake_discord::FakeDiscord;
+
+const GUILD_ID: u64 = 416194652744450048;
+const CHANNEL_ID: u64 = 600;
+const MESSAGE_ID: u64 = 500;
+const MEMBER_ID: u64 = 42;
+const ROLE_ID: u64 = 77;
+
+/// The bot binary.
+const BIN: &str = env!("CARGO_BIN_EXE_oxytrouille");
+
+/// A bot process started in a directory of its own, with the given
+/// configuration and album. Killed and cleaned up once dropped.
+struct Bot {
+    dir: PathBuf,
+    _process: Child,
+}
+
+impl Bot {
+    fn start(name: &str, fake: &FakeDiscord, config: serde_json::Value, args: &[&str]) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("oxytrouille-it-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let album = json!({ "pictures": { "chat": ["https://example.com/chat.png"] } });
+        std::fs::write(dir.join("save.json"), album.to_string()).unwrap();
+        std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+        let process = Command::new(BIN)
+            .args(args)
+            .current_dir(&dir)
+            .env("DISCORD_TOKEN", "fake-token")
+            .env("DISCORD_API_URL", fake.url())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        Self {
+            dir,
+            _process: process,
+        }
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// One reaction role, without resyncing on start unless asked to.
+fn reaction_role_config(resync: &str) -> serde_json::Value {
+    json!({
+        "reaction_roles": [{
+            "guild_id": GUILD_ID.to_string(),
+            "channel_id": CHANNEL_ID.to_string(),
+            "message_id": MESSAGE_ID.to_string(),
+            "roles": [{ "emoji": { "unicode": "🦊" }, "role_id": ROLE_ID.to_string() }],
+        }],
+        "reaction_roles_resync": resync,
+    })
+}
+
+#[tokio::test]
+async fn replies_to_commands() {
+    let fake = FakeDiscord::start().await;
+    let _bot = Bot::start("commands", &fake, reaction_role_config("off"), &[]);
+    fake.connected().await;
+
+    let message =
+        fake_discord::message_create(GUILD_ID, CHANNEL_ID, MESSAGE_ID, MEMBER_ID, "!chat");
+    fake.dispatch("MESSAGE_CREATE", message);
+    let reply = fake
+        .request(Method::POST, &format!("/channels/{}/messages", CHANNEL_ID))
+        .await;
+    assert_eq!(reply.body["content"], "https://example.com/chat.png");
+    assert_eq!(
+        reply.body["message_reference"]["message_id"],
+        MESSAGE_ID.to_string()
+    );
+}
+
+#[tokio::test]
+async fn reactions_give_roles() {
+    let fake = FakeDiscord::start().await;
+    let _bot = Bot::start("reactions", &fake, reaction_role_config("off"), &[]);
+    fake.connected().await;
+
+    let reaction = json!({
+        "channel_id": CHANNEL_ID.to_string(),
+        "guild_id": GUILD_ID.to_string(),
+        "message_id": MESSAGE_ID.to_string(),
+        "user_id": MEMBER_ID.to_string(),
+        "emoji": { "name": "🦊" },
+    });
+    fake.dispatch("MESSAGE_REACTION_ADD", reaction);
+    fake.request(
+        Method::PUT,
+        &format!(
+            "/guilds/{}/members/{}/roles/{}",
+            GUILD_ID, MEMBER_ID, ROLE_ID
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn reaction_roles_are_resynced_on_start() {
+    let fake = FakeDiscord::start().await;
+    fake.add_member(GUILD_ID, MEMBER_ID, &[]);
+    fake.add_reaction(MESSAGE_ID, "🦊", MEMBER_ID);
+    let _bot = Bot::start("resync", &fake, reaction_role_config("apply"), &[]);
+
+    fake.request(
+        Method::PUT,
+        &format!(
+            "/guilds/{}/members/{}/roles/{}",
+            GUILD_ID, MEMBER_ID, ROLE_ID
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn resync_finds_the_channel_of_older_messages() {
+    let fake = FakeDiscord::start().await;
+    fake.add_member(GUILD_ID, MEMBER_ID, &[]);
+    fake.add_reaction(MESSAGE_ID, "🦊", MEMBER_ID);
+    fake.add_message(GUILD_ID, CHANNEL_ID, MESSAGE_ID);
+    let mut config = reaction_role_config("apply");
+    config["reaction_roles"][0]
+        .as_object_mut()
+        .unwrap()
+        .remove("channel_id");
+    let bot = Bot::start("resync-channel", &fake, config, &[]);
+
+    fake.request(
+        Method::PUT,
+        &format!(
+            "/guilds/{}/members/{}/roles/{}",
+            GUILD_ID, MEMBER_ID, ROLE_ID
+        ),
+    )
+    .await;
+    let config = std::fs::read_to_string(bot.dir.join("config.json")).unwrap();
+    assert!(config.contains(&format!("\"channel_id\": \"{}\"", CHANNEL_ID)));
+}
+
+#[tokio::test]
+async fn recorded_events_can_be_replayed() {
+    let fake = FakeDiscord::start().await;
+    let bot = Bot::start(
+        "replay",
+        &fake,
+        reaction_role_config("off"),
+        &["run", "--record", "events.jsonl"],
+    );
+    fake.connected().await;
+
+    let message =
+        fake_discord::message_create(GUILD_ID, CHANNEL_ID, MESSAGE_ID, MEMBER_ID, "!chat");
+    fake.dispatch("MESSAGE_CREATE", message);
+    fake.request(Method::POST, &format!("/channels/{}/messages", CHANNEL_ID))
+        .await;
+
+    let recording = std::fs::read_to_string(bot.dir.join("events.jsonl")).unwrap();
+    assert!(recording.contains("\"MESSAGE_CREATE\""));
+    assert!(!recording.contains("user42"));
+
+    let replay = Command::new(BIN)
+        .args(["replay", "events.jsonl"])
+        .current_dir(&bot.dir)
+        .output()
+        .await
+        .unwrap();
+    assert!(replay.status.success());
+    let output = String::from_utf8(replay.stdout).unwrap();
+    assert!(output.contains("MessageCreate"));
+    assert!(output.contains("https://example.com/chat.png"));
+}
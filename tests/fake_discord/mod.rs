@@ -0,0 +1,482 @@
+//! Fake Discord API for the integration tests.
+//!
+//! A single local server answers REST requests under `/api/v10` and accepts
+//! gateway connections at its root, so the bot only needs the base URL given
+//! by [`FakeDiscord::url`]. It speaks just enough of the protocol for the bot
+//! to identify and receive dispatches: REST requests are recorded and get
+//! plausible answers built from the members, roles and reactions set up by
+//! the test.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// How long to wait for the bot before failing the test.
+const TIMEOUT: Duration = Duration::from_secs(15);
+const POLL: Duration = Duration::from_millis(20);
+const BOT_USER_ID: u64 = 1;
+const TIMESTAMP: &str = "2024-01-01T00:00:00.000000+00:00";
+
+/// A REST request made by the bot.
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub method: Method,
+    /// Without the `/api/v10` prefix nor the query.
+    pub path: String,
+    pub body: Value,
+}
+
+#[derive(Default)]
+struct Shared {
+    requests: Vec<Recorded>,
+    /// Members by guild, as returned by the API.
+    members: HashMap<u64, Vec<Value>>,
+    /// Users who reacted, by message and emoji.
+    reactions: HashMap<(u64, String), Vec<u64>>,
+    /// Text channels by guild.
+    channels: HashMap<u64, Vec<u64>>,
+    /// Channel of each message.
+    messages: HashMap<u64, u64>,
+    /// Gateway connections which identified.
+    sessions: Vec<mpsc::UnboundedSender<String>>,
+    sequence: u64,
+    next_id: u64,
+}
+
+#[derive(Clone)]
+pub struct FakeDiscord {
+    addr: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl FakeDiscord {
+    pub async fn start() -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let fake = Self {
+            addr: listener.local_addr().unwrap(),
+            shared: Arc::default(),
+        };
+        let server = fake.clone();
+        let make_service = make_service_fn(move |_| {
+            let server = server.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                }))
+            }
+        });
+        let server = Server::from_tcp(listener).unwrap().serve(make_service);
+        tokio::spawn(server);
+        fake
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    fn shared(&self) -> std::sync::MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap()
+    }
+
+    pub fn add_member(&self, guild_id: u64, user_id: u64, roles: &[u64]) {
+        let roles: Vec<String> = roles.iter().map(u64::to_string).collect();
+        let member = json!({
+            "user": user(user_id),
+            "nick": null,
+            "roles": roles,
+            "joined_at": TIMESTAMP,
+            "deaf": false,
+            "mute": false,
+            "flags": 0,
+            "communication_disabled_until": null,
+        });
+        self.shared()
+            .members
+            .entry(guild_id)
+            .or_default()
+            .push(member);
+    }
+
+    pub fn add_reaction(&self, message_id: u64, emoji: &str, user_id: u64) {
+        self.shared()
+            .reactions
+            .entry((message_id, emoji.to_owned()))
+            .or_default()
+            .push(user_id);
+    }
+
+    pub fn add_message(&self, guild_id: u64, channel_id: u64, message_id: u64) {
+        let mut shared = self.shared();
+        shared
+            .channels
+            .entry(guild_id)
+            .or_default()
+            .push(channel_id);
+        shared.messages.insert(message_id, channel_id);
+    }
+
+    /// Waits for the bot to identify on the gateway.
+    pub async fn connected(&self) {
+        self.wait("the bot to connect", || {
+            (!self.shared().sessions.is_empty()).then_some(())
+        })
+        .await
+    }
+
+    /// Sends an event to every connected session.
+    pub fn dispatch(&self, kind: &str, data: Value) {
+        let mut shared = self.shared();
+        shared.sequence += 1;
+        let payload = json!({ "op": 0, "t": kind, "s": shared.sequence, "d": data }).to_string();
+        shared
+            .sessions
+            .retain(|session| session.send(payload.clone()).is_ok());
+    }
+
+    /// Waits for a REST request matching `method` and `path`.
+    pub async fn request(&self, method: Method, path: &str) -> Recorded {
+        let description = format!("{} {}", method, path);
+        self.wait(&description, || {
+            self.shared()
+                .requests
+                .iter()
+                .find(|request| request.method == method && request.path == path)
+                .cloned()
+        })
+        .await
+    }
+
+    async fn wait<T>(&self, what: &str, mut found: impl FnMut() -> Option<T>) -> T {
+        let start = Instant::now();
+        loop {
+            if let Some(found) = found() {
+                return found;
+            }
+            if start.elapsed() > TIMEOUT {
+                panic!(
+                    "timed out waiting for {}, requests: {:#?}",
+                    what,
+                    self.shared().requests
+                );
+            }
+            tokio::time::sleep(POLL).await;
+        }
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.headers().contains_key(header::SEC_WEBSOCKET_KEY) {
+            return self.upgrade(request);
+        }
+        let method = request.method().clone();
+        let path = request
+            .uri()
+            .path()
+            .strip_prefix("/api/v10")
+            .unwrap_or(request.uri().path())
+            .to_owned();
+        let query = request.uri().query().unwrap_or("").to_owned();
+        let bytes = hyper::body::to_bytes(request.into_body())
+            .await
+            .unwrap_or_default();
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        let response = self.answer(&method, &path, &query, &body);
+        self.shared().requests.push(Recorded { method, path, body });
+        match response {
+            Some(Value::Null) => status(StatusCode::NO_CONTENT, Body::empty()),
+            Some(value) => status(StatusCode::OK, Body::from(value.to_string())),
+            None => status(
+                StatusCode::NOT_FOUND,
+                Body::from(json!({ "message": "Unknown", "code": 0 }).to_string()),
+            ),
+        }
+    }
+
+    /// Body of the answer to a REST request: null when there is none, `None`
+    /// for unknown routes.
+    fn answer(&self, method: &Method, path: &str, query: &str, body: &Value) -> Option<Value> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let mut shared = self.shared();
+        let answer = match (method, segments.as_slice()) {
+            (&Method::POST, ["channels", channel_id, "messages"]) => {
+                shared.next_id += 1;
+                message(shared.next_id, channel_id, body)
+            }
+            (&Method::PATCH, ["channels", channel_id, "messages", message_id]) => {
+                message(message_id.parse().ok()?, channel_id, body)
+            }
+            (&Method::GET, ["channels", channel_id, "messages", message_id]) => {
+                let message_id = message_id.parse().ok()?;
+                if shared.messages.get(&message_id)?.to_string() != *channel_id {
+                    return None;
+                }
+                message(message_id, channel_id, &Value::Null)
+            }
+            (&Method::GET, ["guilds", guild_id, "channels"]) => {
+                let channels: Vec<Value> = shared
+                    .channels
+                    .get(&guild_id.parse().ok()?)
+                    .into_iter()
+                    .flatten()
+                    .map(|channel_id| {
+                        json!({ "id": channel_id.to_string(), "type": 0, "name": "general" })
+                    })
+                    .collect();
+                Value::Array(channels)
+            }
+            (&Method::GET, ["channels", channel_id]) => {
+                json!({ "id": channel_id, "type": 0, "name": "general" })
+            }
+            (&Method::GET, ["channels", _, "messages", message_id, "reactions", emoji]) => {
+                let key = (message_id.parse().ok()?, percent_decode(emoji));
+                let after: u64 = param(query, "after").unwrap_or(0);
+                let users: Vec<Value> = shared
+                    .reactions
+                    .get(&key)
+                    .into_iter()
+                    .flatten()
+                    .filter(|user_id| **user_id > after)
+                    .map(|user_id| user(*user_id))
+                    .collect();
+                Value::Array(users)
+            }
+            (&Method::GET, ["guilds", guild_id, "members"]) => {
+                let after: u64 = param(query, "after").unwrap_or(0);
+                let members: Vec<Value> = shared
+                    .members
+                    .get(&guild_id.parse().ok()?)
+                    .into_iter()
+                    .flatten()
+                    .filter(|member| {
+                        member["user"]["id"]
+                            .as_str()
+                            .unwrap()
+                            .parse::<u64>()
+                            .unwrap()
+                            > after
+                    })
+                    .cloned()
+                    .collect();
+                Value::Array(members)
+            }
+            (&Method::GET, ["guilds", _, "roles"]) => json!([]),
+            (
+                &Method::PUT | &Method::DELETE,
+                ["channels", _, "messages", _, "reactions", ..]
+                | ["guilds", _, "members", _, "roles", _],
+            ) => Value::Null,
+            (&Method::POST, ["interactions", _, _, "callback"]) => Value::Null,
+            (&Method::PATCH, ["webhooks", _, _, "messages", "@original"]) => {
+                shared.next_id += 1;
+                message(shared.next_id, "0", body)
+            }
+            _ => return None,
+        };
+        Some(answer)
+    }
+
+    /// Accepts a gateway connection.
+    fn upgrade(&self, request: Request<Body>) -> Response<Body> {
+        let accept = derive_accept_key(request.headers()[header::SEC_WEBSOCKET_KEY].as_bytes());
+        let fake = self.clone();
+        tokio::spawn(async move {
+            match hyper::upgrade::on(request).await {
+                Ok(upgraded) => {
+                    let socket =
+                        WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                    fake.gateway(socket).await;
+                }
+                Err(err) => eprintln!("gateway upgrade failed: {}", err),
+            }
+        });
+        Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn gateway(&self, socket: WebSocketStream<hyper::upgrade::Upgraded>) {
+        let (mut sink, mut stream) = socket.split();
+        let (sender, mut dispatches) = mpsc::unbounded_channel();
+        let hello = json!({ "op": 10, "d": { "heartbeat_interval": 41250 } });
+        if sink.send(Message::Text(hello.to_string())).await.is_err() {
+            return;
+        }
+        loop {
+            let reply = tokio::select! {
+                frame = stream.next() => {
+                    let Some(Ok(Message::Text(text))) = frame else {
+                        break;
+                    };
+                    let payload: Value = serde_json::from_str(&text).unwrap_or_default();
+                    match payload["op"].as_u64() {
+                        // Heartbeat.
+                        Some(1) => json!({ "op": 11 }).to_string(),
+                        // Identify.
+                        Some(2) => {
+                            let ready = self.ready();
+                            self.shared().sessions.push(sender.clone());
+                            ready
+                        }
+                        _ => continue,
+                    }
+                }
+                Some(dispatch) = dispatches.recv() => dispatch,
+            };
+            if sink.send(Message::Text(reply)).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    fn ready(&self) -> String {
+        let mut shared = self.shared();
+        shared.sequence += 1;
+        let guilds: Vec<Value> = shared
+            .members
+            .keys()
+            .map(|guild_id| json!({ "id": guild_id.to_string(), "unavailable": true }))
+            .collect();
+        json!({
+            "op": 0,
+            "t": "READY",
+            "s": shared.sequence,
+            "d": {
+                "v": 10,
+                "user": {
+                    "id": BOT_USER_ID.to_string(),
+                    "username": "oxytrouille",
+                    "discriminator": "0000",
+                    "avatar": null,
+                    "accent_color": null,
+                    "banner": null,
+                    "bot": true,
+                    "mfa_enabled": false,
+                },
+                "guilds": guilds,
+                "session_id": "fake-session",
+                "resume_gateway_url": format!("ws://{}", self.addr),
+                "application": { "id": BOT_USER_ID.to_string(), "flags": 0 },
+            },
+        })
+        .to_string()
+    }
+}
+
+fn status(status: StatusCode, body: Body) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap()
+}
+
+pub fn user(user_id: u64) -> Value {
+    json!({
+        "id": user_id.to_string(),
+        "username": format!("user{}", user_id),
+        "discriminator": "0001",
+        "avatar": null,
+        "public_flags": 0,
+    })
+}
+
+/// Message posted by the bot, from the body of the request.
+fn message(message_id: u64, channel_id: &str, body: &Value) -> Value {
+    json!({
+        "id": message_id.to_string(),
+        "channel_id": channel_id,
+        "author": user(BOT_USER_ID),
+        "content": body["content"].as_str().unwrap_or(""),
+        "timestamp": TIMESTAMP,
+        "tts": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "attachments": [],
+        "embeds": [],
+        "pinned": false,
+        "type": 0,
+    })
+}
+
+fn param<T: std::str::FromStr>(query: &str, name: &str) -> Option<T> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+fn percent_decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = match (byte, tail) {
+            (b'%', [high, low, ..]) => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// `MESSAGE_CREATE` payload of a message sent by a member.
+pub fn message_create(
+    guild_id: u64,
+    channel_id: u64,
+    message_id: u64,
+    author: u64,
+    content: &str,
+) -> Value {
+    json!({
+        "id": message_id.to_string(),
+        "channel_id": channel_id.to_string(),
+        "guild_id": guild_id.to_string(),
+        "author": user(author),
+        "member": {
+            "roles": [],
+            "joined_at": TIMESTAMP,
+            "deaf": false,
+            "mute": false,
+            "flags": 0,
+        },
+        "content": content,
+        "timestamp": TIMESTAMP,
+        "tts": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "attachments": [],
+        "embeds": [],
+        "pinned": false,
+        "type": 0,
+    })
+}